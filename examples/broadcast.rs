@@ -0,0 +1,54 @@
+/*
+ * This example sends one message to many
+ * connected sockets at once, using
+ * `Uring::broadcast` to submit every send
+ * with a single `io_uring_enter`.
+ */
+
+use std::{
+    io::{Read, Result},
+    net::{TcpListener, TcpStream},
+};
+
+const CLIENTS: usize = 100;
+
+fn main() -> Result<()> {
+    let ring = rio::new()?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let mut clients = vec![];
+    let mut servers = vec![];
+    for _ in 0..CLIENTS {
+        clients.push(TcpStream::connect(addr)?);
+        servers.push(listener.accept()?.0);
+    }
+
+    let message = b"hello everyone!";
+
+    let enters_before = ring.metrics_snapshot().sq_enters;
+
+    let sockets: Vec<&TcpStream> = servers.iter().collect();
+    let completions = ring.broadcast(&sockets, message);
+
+    let enters =
+        ring.metrics_snapshot().sq_enters - enters_before;
+
+    for completion in completions {
+        assert_eq!(completion.wait()?, message.len());
+    }
+
+    for client in &mut clients {
+        let mut buf = [0; 15];
+        client.read_exact(&mut buf)?;
+        assert_eq!(&buf, message);
+    }
+
+    println!(
+        "sent {} messages with {} submission syscall(s)",
+        CLIENTS, enters
+    );
+
+    Ok(())
+}
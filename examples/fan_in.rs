@@ -0,0 +1,51 @@
+/*
+ * This example measures how quickly batches
+ * of 10k NOP completions can be waited on,
+ * both from a single thread and from several
+ * threads waiting on their own share of each
+ * batch at the same time.
+ */
+
+use std::time::Instant;
+
+const BATCH: usize = 10_000;
+const ROUNDS: usize = 100;
+const THREADS: usize = 4;
+
+fn main() {
+    let ring = rio::new().expect("create uring");
+
+    let pre = Instant::now();
+    for _ in 0..ROUNDS {
+        let completions: Vec<_> =
+            (0..BATCH).map(|_| ring.nop()).collect();
+        for completion in completions {
+            completion.wait().unwrap();
+        }
+    }
+    let single_waiter = pre.elapsed();
+
+    let pre = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    let completions: Vec<_> = (0..BATCH
+                        / THREADS)
+                        .map(|_| ring.nop())
+                        .collect();
+                    for completion in completions {
+                        completion.wait().unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let many_waiters = pre.elapsed();
+
+    dbg!(single_waiter, many_waiters);
+}
@@ -22,12 +22,7 @@ fn serve(ring: rio::Rio, acceptor: TcpListener) -> io::Result<()> {
     extreme::run(async move {
         loop {
             let stream = ring.accept(&acceptor).wait()?;
-            let mut buf = RESP;
-            while !buf.is_empty() {
-                let written_bytes =
-                    ring.write_at(&stream, &buf, 0).await?;
-                buf = &buf[written_bytes..];
-            }
+            ring.send_all(&stream, &RESP).await?;
             COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
     })
@@ -12,24 +12,13 @@ fn main() -> Result<()> {
     config.print_profile_on_drop = true;
     let ring = config.start().expect("create uring");
 
-    let mut completions = vec![];
+    let result = ring.benchmark_nops(1_000_000)?;
 
-    let pre = std::time::Instant::now();
-
-    for _ in 0..(1_000_000) {
-        let completion = ring.nop();
-        completions.push(completion);
-    }
-
-    let post_submit = std::time::Instant::now();
-
-    for completion in completions.into_iter() {
-        completion.wait().unwrap();
-    }
-
-    let post_complete = std::time::Instant::now();
-
-    dbg!(post_submit - pre, post_complete - post_submit);
+    dbg!(
+        result.submit_time,
+        result.complete_time,
+        result.ops_per_sec()
+    );
 
     Ok(())
 }
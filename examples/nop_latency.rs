@@ -0,0 +1,41 @@
+/*
+ * This example measures the round-trip latency
+ * of a single NOP, waited on before the next one
+ * is submitted, with and without having the
+ * reaper spin on the completion queue before it
+ * blocks. See `Config::cq_spin_us`.
+ */
+
+use std::time::{Duration, Instant};
+
+const ROUND_TRIPS: u32 = 100_000;
+
+fn round_trip(cq_spin_us: Option<u32>) -> Duration {
+    let config = rio::Config {
+        cq_spin_us,
+        ..rio::Config::default()
+    };
+    let ring = config.start().expect("create uring");
+
+    // warm up, so that the first measured round
+    // trips don't pay for faulting things in
+    for _ in 0..1000 {
+        ring.nop().wait().unwrap();
+    }
+
+    let pre = Instant::now();
+    for _ in 0..ROUND_TRIPS {
+        ring.nop().wait().unwrap();
+    }
+    pre.elapsed() / ROUND_TRIPS
+}
+
+fn main() {
+    for &cq_spin_us in &[None, Some(10), Some(100)] {
+        println!(
+            "cq_spin_us {:?}: {:?} per nop round trip",
+            cq_spin_us,
+            round_trip(cq_spin_us)
+        );
+    }
+}
@@ -37,8 +37,10 @@ fn main() -> Result<()> {
     let mut completions = vec![];
 
     let pre = std::time::Instant::now();
-    for i in 0..(10 * 1024) {
-        let at = i * CHUNK_SIZE;
+    for i in 0..(10 * 1024_u64) {
+        let at = i
+            .checked_mul(CHUNK_SIZE)
+            .expect("offset overflowed");
 
         // By setting the `Link` order,
         // we specify that the following
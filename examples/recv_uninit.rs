@@ -0,0 +1,63 @@
+/*
+ * This example compares receiving into a
+ * buffer that is zeroed before every recv
+ * with receiving into an uninitialized one,
+ * using a 64KiB buffer over loopback TCP.
+ */
+
+use std::{
+    io::{Result, Write},
+    mem::MaybeUninit,
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
+
+const BUF_SIZE: usize = 64 * 1024;
+const ROUNDS: usize = 10_000;
+
+fn main() -> Result<()> {
+    let ring = rio::new()?;
+
+    let acceptor = TcpListener::bind("127.0.0.1:0")?;
+    let addr = acceptor.local_addr()?;
+
+    let writer = std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let chunk = vec![42_u8; BUF_SIZE];
+        // write until the reader hangs up
+        while stream.write_all(&chunk).is_ok() {}
+    });
+
+    let (stream, _) = acceptor.accept()?;
+
+    let mut zeroed = vec![0_u8; BUF_SIZE];
+    let pre = Instant::now();
+    let mut received = 0;
+    while received < ROUNDS * BUF_SIZE {
+        // this is what `recv` forces callers to do
+        // when they can't reuse a buffer as-is.
+        for byte in zeroed.iter_mut() {
+            *byte = 0;
+        }
+        received += ring.recv(&stream, &zeroed).wait()?;
+    }
+    let zeroed_time = pre.elapsed();
+
+    let mut uninit = vec![MaybeUninit::uninit(); BUF_SIZE];
+    let pre = Instant::now();
+    let mut received = 0;
+    while received < ROUNDS * BUF_SIZE {
+        received += ring
+            .recv_uninit(&stream, &mut uninit)
+            .wait()?
+            .len();
+    }
+    let uninit_time = pre.elapsed();
+
+    drop(stream);
+    writer.join().unwrap();
+
+    dbg!(zeroed_time, uninit_time);
+
+    Ok(())
+}
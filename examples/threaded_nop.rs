@@ -0,0 +1,46 @@
+/*
+ * This example measures NOP throughput when
+ * many threads submit and wait on their own
+ * operations at the same time, which is where
+ * contention over tickets shows up.
+ */
+
+use std::time::Instant;
+
+const THREADS: usize = 16;
+const PER_THREAD: usize = 100_000;
+const BATCH: usize = 64;
+
+fn main() {
+    let ring = rio::new().expect("create uring");
+
+    let pre = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for _ in 0..PER_THREAD / BATCH {
+                    let completions: Vec<_> = (0..BATCH)
+                        .map(|_| ring.nop())
+                        .collect();
+                    for completion in completions {
+                        completion.wait().unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let elapsed = pre.elapsed();
+
+    let ops = THREADS * (PER_THREAD / BATCH) * BATCH;
+    println!(
+        "{} threads: {} nops in {:?}, {:.0} ops/s",
+        THREADS,
+        ops,
+        elapsed,
+        ops as f64 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,59 @@
+/*
+ * This example measures NOP throughput with 8
+ * threads fanning out batches of operations at
+ * once, with the reaper handing tickets back
+ * after every batch of completions, and with it
+ * holding them for bigger batches. See
+ * `Config::ticket_return_batch`.
+ */
+
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 8;
+const PER_THREAD: usize = 100_000;
+const BATCH: usize = 64;
+
+fn fan_out(ticket_return_batch: usize) -> Duration {
+    let config = rio::Config {
+        depth: 256,
+        ticket_return_batch,
+        ..rio::Config::default()
+    };
+    let ring = config.start().expect("create uring");
+
+    let pre = Instant::now();
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for _ in 0..PER_THREAD / BATCH {
+                    let completions: Vec<_> = (0..BATCH)
+                        .map(|_| ring.nop())
+                        .collect();
+                    for completion in completions {
+                        completion.wait().unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    pre.elapsed()
+}
+
+fn main() {
+    let ops = THREADS * (PER_THREAD / BATCH) * BATCH;
+    for &ticket_return_batch in &[0, 32, 128] {
+        let elapsed = fan_out(ticket_return_batch);
+        println!(
+            "ticket_return_batch {}: {} nops in {:?}, \
+             {:.0} ops/s",
+            ticket_return_batch,
+            ops,
+            elapsed,
+            ops as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
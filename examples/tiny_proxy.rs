@@ -0,0 +1,53 @@
+/*
+ * This example proxies single bytes from one
+ * socket to another, which stresses the
+ * per-operation overhead of tiny reads and
+ * writes rather than any actual IO.
+ */
+
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    time::Instant,
+};
+
+const ROUNDS: usize = 100_000;
+
+fn main() {
+    let ring = rio::new().expect("create uring");
+
+    let (mut client, proxy_in) =
+        UnixStream::pair().unwrap();
+    let (proxy_out, mut server) =
+        UnixStream::pair().unwrap();
+
+    let byte = &mut [0_u8; 1];
+    let mut received = [0_u8; 1];
+
+    let pre = Instant::now();
+    for i in 0..ROUNDS {
+        client.write_all(&[i as u8]).unwrap();
+
+        let read = ring
+            .read_at(&proxy_in, byte, 0)
+            .wait()
+            .unwrap();
+        assert_eq!(read, 1);
+        let written = ring
+            .write_at(&proxy_out, byte, 0)
+            .wait()
+            .unwrap();
+        assert_eq!(written, 1);
+
+        server.read_exact(&mut received).unwrap();
+        assert_eq!(received[0], i as u8);
+    }
+    let elapsed = pre.elapsed();
+
+    println!(
+        "proxied {} bytes one at a time in {:?}, {:?} each",
+        ROUNDS,
+        elapsed,
+        elapsed / ROUNDS as u32
+    );
+}
@@ -1,35 +1,150 @@
 use std::{
+    any::Any,
     future::Future,
     io,
     marker::PhantomData,
+    panic::{catch_unwind, AssertUnwindSafe},
     pin::Pin,
-    sync::{Arc, Condvar, Mutex},
+    sync::{
+        atomic::{
+            AtomicBool, AtomicUsize, Ordering::SeqCst,
+        },
+        Arc, Condvar, Mutex,
+    },
     task::{Context, Poll, Waker},
 };
 
-use super::{
-    io_uring::io_uring_cqe, FromCqe, Measure, Uring, M,
-};
+use super::{io_uring::io_uring_cqe, FromCqe, Uring};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct CompletionState {
-    done: bool,
+    // set once `slot` has been filled. Blocked
+    // waiters sleep on their ring's `Wakeup`
+    // until it is.
+    done: AtomicBool,
+    slot: Mutex<Slot>,
+}
+
+#[derive(Debug, Default)]
+struct Slot {
     item: Option<io::Result<io_uring_cqe>>,
     waker: Option<Waker>,
+    // whatever the kernel may still be using, left
+    // here by a `Completion` that was dropped
+    // without waiting, to be freed once filled.
+    orphaned: Option<Box<dyn Any + Send>>,
+    on_success: Option<OnSuccess>,
+}
+
+/// Called by `Filler::fill` with the result of
+/// an operation that succeeded. If it panics,
+/// the operation fails instead.
+struct OnSuccess(Box<dyn FnOnce(i32) + Send>);
+
+impl std::fmt::Debug for OnSuccess {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "OnSuccess")
+    }
+}
+
+/// Shared by all of a ring's `Completion`s, so
+/// that the reaper can wake every blocked waiter
+/// with a single notification per batch of
+/// completions, rather than one per completion.
+#[derive(Debug, Default)]
+pub(crate) struct Wakeup {
+    mu: Mutex<()>,
+    cv: Condvar,
+    sleepers: AtomicUsize,
 }
 
-impl Default for CompletionState {
-    fn default() -> CompletionState {
-        CompletionState {
-            done: false,
-            item: None,
-            waker: None,
+impl Wakeup {
+    /// Block until `done` returns true, which
+    /// must become true before the next call to
+    /// `notify`.
+    #[cfg_attr(feature = "single_thread", allow(dead_code))]
+    pub(crate) fn wait_until<F: Fn() -> bool>(
+        &self,
+        done: F,
+    ) {
+        if done() {
+            return;
         }
+
+        let mut guard = self.mu.lock().unwrap();
+
+        // NB: this is SeqCst, along with setting and
+        // checking `done`, so that either we see a
+        // `done` that was set before `notify`
+        // checks for sleepers, or it sees us.
+        self.sleepers.fetch_add(1, SeqCst);
+        while !done() {
+            guard = self.cv.wait(guard).unwrap();
+        }
+        self.sleepers.fetch_sub(1, SeqCst);
+    }
+
+    /// Wakes up everything blocked in `wait_until`,
+    /// if anything is.
+    pub(crate) fn notify(&self) {
+        if self.sleepers.load(SeqCst) == 0 {
+            return;
+        }
+
+        // a sleeper that has been counted but has not
+        // started waiting yet holds the mutex, so
+        // this blocks until it has.
+        drop(self.mu.lock().unwrap());
+        self.cv.notify_all();
     }
 }
 
 /// A Future value which may or may not be filled
 ///
+/// # Dropping in async code
+///
+/// Dropping a `Completion` blocks the thread
+/// until its operation is done, because the
+/// kernel may still be using the buffers that
+/// it borrows. In an async runtime, that stalls
+/// the executor thread, and every other task
+/// on it, so if one that has been polled is
+/// dropped before it resolves, like when it
+/// loses a `select!` to a timeout, its
+/// operation is canceled first. Then the drop
+/// only blocks for as long as the cancellation
+/// takes, rather than for something like a
+/// `recv` that may never complete. That is
+/// still a blocking call though, so racing
+/// operations against timeouts is better done
+/// with `Uring::recv_timeout` and the like.
+/// The cancellation needs a free ticket, and
+/// is skipped if every one is in use, in which
+/// case the drop waits for the operation.
+///
+/// # Rings shared through `Rio`
+///
+/// A `Completion` borrows the `Uring` that it
+/// was submitted on, which a `Rio` derefs to, so
+/// the `Rio` that it came from can't be dropped
+/// before it is. Other clones can be, because
+/// the ring lives for as long as any of them
+/// does. When the last one goes, dropping the
+/// `Uring` cancels the operations that nothing
+/// is waiting on anymore, like detached or
+/// leaked ones, and then waits for every one
+/// submitted before then to complete, so the
+/// reaper never fills in a result after the
+/// ring is gone. What it does touch, it holds
+/// its own references to. Kernels before 5.19
+/// can't cancel them all at once, so there the
+/// drop waits for them to complete on their
+/// own, which for a recv on an idle socket is
+/// forever.
+///
 /// # Safety
 ///
 /// To prevent undefined behavior in the form of
@@ -40,17 +155,167 @@ impl Default for CompletionState {
 #[derive(Debug)]
 pub struct Completion<'a, C: FromCqe> {
     lifetime: PhantomData<&'a C>,
-    mu: Arc<Mutex<CompletionState>>,
-    cv: Arc<Condvar>,
+    state: Arc<CompletionState>,
     uring: &'a Uring,
+    pub(crate) user_data: u64,
     pub(crate) sqe_id: u64,
+    // filled in by an `Injector`, rather than by
+    // an operation, so there's nothing to submit
+    pub(crate) injected: bool,
+    polled: bool,
+    // handed off to the reaper by `orphan`, so
+    // dropping this doesn't wait for anything
+    orphaned: bool,
+    // NB: this is dropped after `Drop::drop` has
+    // waited for the operation to complete.
+    pub(crate) keep_alive: Option<KeepAlive>,
+}
+
+/// A `Completion` for an operation whose buffers
+/// (or other kernel-visible state) are owned by the
+/// `OwnedCompletion` itself, rather than borrowed
+/// from the caller. The owned state is handed back,
+/// or used to build the result, when the operation
+/// completes.
+///
+/// Dropping an `OwnedCompletion` waits for the
+/// underlying operation to complete before the
+/// owned state is freed.
+pub struct OwnedCompletion<'a, T> {
+    // NB: this must come before `finish` so that it
+    // is dropped (and waits for the kernel to be
+    // done with the owned state) before `finish`,
+    // which holds that state, is freed.
+    completion: Option<Completion<'a, usize>>,
+    finish: Option<Finish<'a, T>>,
+    // the state that the kernel may be using, when
+    // it's all owned, so that it can be handed off
+    // to the reaper with `detach_owned_on_drop`.
+    owned: Option<Owned>,
+}
+
+type Owned = Box<dyn Any + Send>;
+
+type Finish<'a, T> = Box<
+    dyn FnOnce(
+            io::Result<usize>,
+            Option<Owned>,
+        ) -> io::Result<T>
+        + Send
+        + 'a,
+>;
+
+impl<'a, T> std::fmt::Debug for OwnedCompletion<'a, T> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "OwnedCompletion {{ .. }}")
+    }
+}
+
+/// Values that the kernel may access while an
+/// operation submitted with `Uring::submit_raw`
+/// is in-flight, like buffers or structs that the
+/// SQE points to. They are held by the returned
+/// `Completion`, and only dropped after the
+/// operation has completed.
+#[derive(Default)]
+pub struct KeepAlive {
+    items: Vec<Box<dyn Any + Send>>,
+}
+
+impl std::fmt::Debug for KeepAlive {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(
+            f,
+            "KeepAlive {{ {} items }}",
+            self.items.len()
+        )
+    }
+}
+
+impl KeepAlive {
+    /// Create an empty `KeepAlive`.
+    pub fn new() -> KeepAlive {
+        KeepAlive::default()
+    }
+
+    /// Hold on to `item` until the operation is
+    /// complete, returning a pointer to it that
+    /// stays valid until then, even after the
+    /// `KeepAlive` is moved.
+    pub fn hold<T: Any + Send>(
+        &mut self,
+        item: T,
+    ) -> *mut T {
+        let mut boxed = Box::new(item);
+        let ptr: *mut T = &mut *boxed;
+        self.items.push(boxed);
+        ptr
+    }
 }
 
 /// The completer side of the Future
 #[derive(Debug)]
 pub struct Filler {
-    mu: Arc<Mutex<CompletionState>>,
-    cv: Arc<Condvar>,
+    state: Arc<CompletionState>,
+}
+
+/// Resolves a `Completion` returned by
+/// `Uring::inject_completion`, with whatever
+/// result you like, whenever you like. If it's
+/// dropped without being filled, the
+/// `Completion` fails with `ECANCELED`.
+#[cfg(feature = "testing")]
+#[derive(Debug)]
+pub struct Injector<'a> {
+    uring: &'a Uring,
+    filler: Option<Filler>,
+}
+
+#[cfg(feature = "testing")]
+impl<'a> Injector<'a> {
+    pub(crate) const fn new(
+        uring: &'a Uring,
+        filler: Filler,
+    ) -> Injector<'a> {
+        Injector {
+            uring,
+            filler: Some(filler),
+        }
+    }
+
+    /// Resolve the `Completion` with `result`, and
+    /// wake up anything waiting on it.
+    pub fn fill(
+        mut self,
+        result: io::Result<io_uring_cqe>,
+    ) {
+        self.fill_inner(result);
+    }
+
+    fn fill_inner(
+        &mut self,
+        result: io::Result<io_uring_cqe>,
+    ) {
+        if let Some(filler) = self.filler.take() {
+            filler.fill(result);
+            self.uring.notify_waiters();
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl Drop for Injector<'_> {
+    fn drop(&mut self) {
+        self.fill_inner(Err(io::Error::from_raw_os_error(
+            libc::ECANCELED,
+        )));
+    }
 }
 
 /// Create a new `Filler` and the `Completion`
@@ -58,22 +323,50 @@ pub struct Filler {
 pub fn pair<'a, C: FromCqe>(
     uring: &'a Uring,
 ) -> (Completion<'a, C>, Filler) {
-    let mu =
-        Arc::new(Mutex::new(CompletionState::default()));
-    let cv = Arc::new(Condvar::new());
+    let state = Arc::new(CompletionState::default());
     let future = Completion {
         lifetime: PhantomData,
-        mu: mu.clone(),
-        cv: cv.clone(),
+        state: state.clone(),
+        user_data: 0,
         sqe_id: 0,
+        injected: false,
+        polled: false,
+        orphaned: false,
+        keep_alive: None,
         uring,
     };
-    let filler = Filler { mu, cv };
+    let filler = Filler { state };
 
     (future, filler)
 }
 
 impl<'a, C: FromCqe> Completion<'a, C> {
+    /// A `Completion` that has already failed with
+    /// `e`, without submitting anything.
+    pub(crate) fn failed(
+        uring: &'a Uring,
+        e: io::Error,
+    ) -> Completion<'a, C> {
+        let (completion, filler) = pair(uring);
+        filler.fill(Err(e));
+        completion
+    }
+
+    /// A `Completion` that has already succeeded
+    /// with `res`, without submitting anything.
+    pub(crate) fn succeeded(
+        uring: &'a Uring,
+        res: i32,
+    ) -> Completion<'a, C> {
+        let (completion, filler) = pair(uring);
+        filler.fill(Ok(io_uring_cqe {
+            user_data: 0,
+            res,
+            flags: 0,
+        }));
+        completion
+    }
+
     /// Block on the `Completion`'s completion
     /// or dropping of the `Filler`
     pub fn wait(self) -> io::Result<C>
@@ -83,37 +376,184 @@ impl<'a, C: FromCqe> Completion<'a, C> {
         self.wait_inner().unwrap()
     }
 
+    /// Like `wait`, but also returns the flags of
+    /// the CQE that the operation completed with,
+    /// like `IORING_CQE_F_SOCK_NONEMPTY` after a
+    /// receive, for the hints that the kernel
+    /// reports there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let ((), flags) =
+    ///     ring.nop().wait_with_flags().unwrap();
+    /// assert_eq!(flags, 0);
+    /// ```
+    pub fn wait_with_flags(self) -> io::Result<(C, u32)>
+    where
+        C: FromCqe,
+    {
+        let res = self.wait_cqe().unwrap();
+        let flags = res.as_ref().map_or(0, |cqe| cqe.flags);
+        C::from_result(res).map(|item| (item, flags))
+    }
+
     fn wait_inner(&self) -> Option<io::Result<C>>
     where
         C: FromCqe,
     {
-        debug_assert_ne!(
-            self.sqe_id,
-            0,
+        self.wait_cqe().map(FromCqe::from_result)
+    }
+
+    fn wait_cqe(&self) -> Option<io::Result<io_uring_cqe>> {
+        debug_assert!(
+            self.sqe_id != 0
+                || self.injected
+                || self.is_done(),
             "sqe_id was never filled-in for this Completion",
         );
 
-        self.uring
-            .ensure_submitted(self.sqe_id)
-            .expect("failed to submit SQE from wait_inner");
+        if let Err(e) =
+            self.uring.ensure_submitted(self.sqe_id)
+        {
+            // dropping this still waits for the
+            // operation, once it reaches the kernel
+            return Some(Err(e));
+        }
 
-        let _ = Measure::new(&M.wait);
+        self.wait_submitted()
+    }
+
+    /// Like `wait_cqe`, once the SQE has been
+    /// submitted.
+    fn wait_submitted(
+        &self,
+    ) -> Option<io::Result<io_uring_cqe>> {
+        let _ = self.uring.metrics().measure(|m| &m.wait);
 
-        let mut inner = self.mu.lock().unwrap();
+        self.uring.wait_until(|| self.is_done());
+
+        // the lock is released before converting, so
+        // that a conversion that panics can't poison
+        // it for the drop that runs while unwinding
+        self.state.slot.lock().unwrap().item.take()
+    }
 
-        while !inner.done {
-            inner = self.cv.wait(inner).unwrap();
+    pub(crate) fn is_done(&self) -> bool {
+        self.state.done.load(SeqCst)
+    }
+
+    pub(crate) fn is_from(&self, uring: &Uring) -> bool {
+        std::ptr::eq(self.uring, uring)
+    }
+
+    /// Calls `f` with the operation's `res` if it
+    /// succeeds, as soon as it completes, which is
+    /// usually on the reaper thread, rather than
+    /// whenever it's waited on. This must be called
+    /// before the `Completion` is waited on.
+    pub(crate) fn on_success<F>(&self, f: F)
+    where
+        F: 'static + Send + FnOnce(i32),
+    {
+        let done_res = {
+            let mut slot = self.state.slot.lock().unwrap();
+            match &slot.item {
+                None => {
+                    slot.on_success =
+                        Some(OnSuccess(Box::new(f)));
+                    return;
+                }
+                Some(Ok(cqe)) => Some(cqe.res),
+                Some(Err(_)) => None,
+            }
+        };
+
+        // it's already done, so nobody else will
+        if let Some(res) = done_res {
+            f(res);
+        }
+    }
+
+    /// Drops this without waiting for the operation
+    /// to complete, leaving `owned`, and anything
+    /// else that the kernel may be using, to be
+    /// freed when it does. The operation is still
+    /// canceled if dropping would have canceled it.
+    fn orphan(mut self, owned: Owned) {
+        if let Err(e) =
+            self.uring.ensure_submitted(self.sqe_id)
+        {
+            // the SQE goes along with the next
+            // submission, and `owned` stays around
+            // until it's done either way
+            if let Some(handler) =
+                self.uring.drop_error_handler()
+            {
+                handler(e);
+            }
         }
 
-        inner.item.take().map(|io_result| {
-            io_result.map(FromCqe::from_cqe)
-        })
+        {
+            let mut slot = self.state.slot.lock().unwrap();
+            if slot.item.is_some() || self.is_done() {
+                // the usual drop won't block
+                return;
+            }
+            slot.waker = None;
+            slot.orphaned = Some(Box::new((
+                owned,
+                self.keep_alive.take(),
+            )));
+        }
+        self.orphaned = true;
+
+        if self.uring.cancel_on_drop() || self.polled {
+            self.uring.try_cancel_detached(self.user_data);
+        }
     }
 }
 
 impl<'a, C: FromCqe> Drop for Completion<'a, C> {
     fn drop(&mut self) {
-        self.wait_inner();
+        if self.orphaned {
+            return;
+        }
+
+        self.uring.ensure_submitted_for_drop(self.sqe_id);
+
+        let mut canceled = false;
+        if self.uring.cancel_on_drop() || self.polled {
+            // this fails with ENOENT if the op
+            // finished in the mean time, which is
+            // fine, because we only need it to not
+            // be running by the time we return. If
+            // every ticket is taken, we just wait.
+            if !self.is_done()
+                && self
+                    .uring
+                    .try_cancel(self.user_data)
+                    .is_some()
+            {
+                canceled = true;
+            }
+        }
+
+        let res = self.wait_submitted().map(C::from_result);
+        if let Some(Err(e)) = res {
+            let expected = canceled
+                && e.raw_os_error()
+                    == Some(libc::ECANCELED);
+            if let Some(handler) =
+                self.uring.drop_error_handler()
+            {
+                if !expected {
+                    handler(e);
+                }
+            }
+        }
     }
 }
 
@@ -121,43 +561,210 @@ impl<'a, C: FromCqe> Future for Completion<'a, C> {
     type Output = io::Result<C>;
 
     fn poll(
-        self: Pin<&mut Self>,
+        mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Self::Output> {
-        self.uring
-            .ensure_submitted(self.sqe_id)
-            .expect("failed to submit SQE from wait_inner");
-
-        let mut state = self.mu.lock().unwrap();
-        if state.item.is_some() {
-            Poll::Ready(
-                state
-                    .item
-                    .take()
-                    .unwrap()
-                    .map(FromCqe::from_cqe),
-            )
+        self.polled = true;
+        if let Err(e) =
+            self.uring.ensure_submitted(self.sqe_id)
+        {
+            // see `wait_cqe`
+            return Poll::Ready(Err(e));
+        }
+
+        #[cfg(feature = "single_thread")]
+        self.uring.reap_unless(|| self.is_done());
+
+        let mut slot = self.state.slot.lock().unwrap();
+        if let Some(item) = slot.item.take() {
+            Poll::Ready(FromCqe::from_result(item))
         } else {
-            if !state.done {
-                state.waker = Some(cx.waker().clone());
+            if !self.is_done() {
+                slot.waker = Some(cx.waker().clone());
             }
+
+            // nothing will reap this for us, so we
+            // need to be polled again to do it. This
+            // isn't a busy loop, since `reap_unless`
+            // waited a bit for a completion first.
+            #[cfg(feature = "single_thread")]
+            cx.waker().wake_by_ref();
+
             Poll::Pending
         }
     }
 }
 
+impl<'a, T> OwnedCompletion<'a, T> {
+    /// `finish` owns any state that must outlive
+    /// the operation, and is called with the
+    /// operation's result to produce the output.
+    pub(crate) fn new<F>(
+        completion: Completion<'a, usize>,
+        finish: F,
+    ) -> OwnedCompletion<'a, T>
+    where
+        F: 'a + Send + FnOnce(usize) -> io::Result<T>,
+    {
+        OwnedCompletion::new_fallible(completion, |res| {
+            finish(res?)
+        })
+    }
+
+    /// Like `new`, but `finish` is also called if
+    /// the operation fails, with its error.
+    pub(crate) fn new_fallible<F>(
+        completion: Completion<'a, usize>,
+        finish: F,
+    ) -> OwnedCompletion<'a, T>
+    where
+        F: 'a
+            + Send
+            + FnOnce(io::Result<usize>) -> io::Result<T>,
+    {
+        OwnedCompletion {
+            completion: Some(completion),
+            finish: Some(Box::new(move |res, _| {
+                finish(res)
+            })),
+            owned: None,
+        }
+    }
+
+    /// Like `new`, but for operations where all of
+    /// the state that the kernel may use is in
+    /// `owned`, which `finish` is then called with.
+    /// With `Config::detach_owned_on_drop`, these
+    /// can be dropped without blocking.
+    pub(crate) fn owning<O, F>(
+        completion: Completion<'a, usize>,
+        owned: O,
+        finish: F,
+    ) -> OwnedCompletion<'a, T>
+    where
+        O: 'static + Send,
+        F: 'a + Send + FnOnce(usize, O) -> io::Result<T>,
+    {
+        OwnedCompletion {
+            completion: Some(completion),
+            finish: Some(Box::new(move |res, stashed| {
+                let res = res?;
+                let unboxed = stashed
+                    .and_then(|boxed| boxed.downcast().ok())
+                    .expect("owned state went missing");
+                finish(res, *unboxed)
+            })),
+            owned: Some(Box::new(owned)),
+        }
+    }
+
+    /// Block on the `OwnedCompletion`'s completion,
+    /// returning its output.
+    pub fn wait(mut self) -> io::Result<T> {
+        let res = self.completion.take().unwrap().wait();
+        (self.finish.take().unwrap())(
+            res,
+            self.owned.take(),
+        )
+    }
+}
+
+impl<'a, T> Drop for OwnedCompletion<'a, T> {
+    fn drop(&mut self) {
+        if let Some(completion) = self.completion.take() {
+            match self.owned.take() {
+                Some(owned)
+                    if completion
+                        .uring
+                        .detach_owned_on_drop() =>
+                {
+                    completion.orphan(owned);
+                }
+                // dropped before `owned`, so that this
+                // waits for the kernel to be done with it
+                _ => drop(completion),
+            }
+        }
+    }
+}
+
+impl<'a, T> Future for OwnedCompletion<'a, T> {
+    type Output = io::Result<T>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let completion = self.completion.as_mut().unwrap();
+        let res = match Pin::new(completion).poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.completion = None;
+        let owned = self.owned.take();
+        Poll::Ready((self.finish.take().unwrap())(
+            res, owned,
+        ))
+    }
+}
+
 impl Filler {
-    /// Complete the `Completion`
+    /// A `Filler` for an operation whose result
+    /// nobody is waiting for.
+    pub(crate) fn detached() -> Filler {
+        Filler {
+            state: Arc::new(CompletionState::default()),
+        }
+    }
+
+    /// Complete the `Completion`. Anything blocked
+    /// on it in `Completion::wait` is only woken up
+    /// by the next call to `Wakeup::notify`, which
+    /// the reaper makes after each batch.
     pub fn fill(self, inner: io::Result<io_uring_cqe>) {
-        let mut state = self.mu.lock().unwrap();
+        let succeeded =
+            inner.as_ref().ok().map(|cqe| cqe.res);
+        let (to_wake, orphaned, callback) = {
+            let mut slot = self.state.slot.lock().unwrap();
+            slot.item = Some(inner);
+            (
+                slot.waker.take(),
+                slot.orphaned.take(),
+                slot.on_success.take(),
+            )
+        };
 
-        if let Some(waker) = state.waker.take() {
-            waker.wake();
+        // the callback runs before this is marked as
+        // done, so that whatever it does is visible
+        // to waiters as soon as they see that, and
+        // so that anything borrowed by the operation
+        // is still borrowed while it runs
+        if let (Some(res), Some(OnSuccess(f))) =
+            (succeeded, callback)
+        {
+            // this is usually the reaper thread, which
+            // would take every other waiter on the
+            // ring down with it, so a panic only fails
+            // this one operation
+            if catch_unwind(AssertUnwindSafe(|| f(res)))
+                .is_err()
+            {
+                self.state.slot.lock().unwrap().item =
+                    Some(Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "the operation's completion \
+                         callback panicked",
+                    )));
+            }
         }
 
-        state.item = Some(inner);
-        state.done = true;
+        self.state.done.store(true, SeqCst);
 
-        self.cv.notify_all();
+        // the kernel is done with it now
+        drop(orphaned);
+
+        if let Some(waker) = to_wake {
+            waker.wake();
+        }
     }
 }
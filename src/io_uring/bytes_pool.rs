@@ -0,0 +1,81 @@
+use std::sync::Weak;
+
+use bytes::Bytes;
+
+use super::*;
+
+/// How many buffers a `BytesPool` keeps around
+/// for reuse, beyond which returned ones are
+/// freed instead.
+const MAX_POOLED: usize = 64;
+
+/// The buffers that `Uring::read_at_bytes` reads
+/// into. Each one is handed out inside of a
+/// `Bytes`, and comes back here once the last
+/// clone or slice of that `Bytes` is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct BytesPool {
+    bufs: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BytesPool {
+    /// A buffer of at least `len` bytes, which is
+    /// reused if there's one big enough, in which
+    /// case it still holds whatever was read into
+    /// it the last time.
+    pub(crate) fn take(&self, len: usize) -> Vec<u8> {
+        let mut bufs = self.bufs.lock().unwrap();
+        bufs.iter()
+            .position(|buf| buf.len() >= len)
+            .map_or_else(
+                || vec![0; len],
+                |i| bufs.swap_remove(i),
+            )
+    }
+
+    fn put(&self, buf: Vec<u8>) {
+        let mut bufs = self.bufs.lock().unwrap();
+        if bufs.len() < MAX_POOLED {
+            bufs.push(buf);
+        }
+    }
+
+    /// Hands out the first `len` bytes of `buf` as
+    /// a `Bytes`, which gives `buf` back to this
+    /// pool when it's dropped.
+    pub(crate) fn freeze(
+        self: &Arc<Self>,
+        buf: Vec<u8>,
+        len: usize,
+    ) -> Bytes {
+        Bytes::from_owner(Pooled {
+            buf,
+            len,
+            pool: Arc::downgrade(self),
+        })
+    }
+}
+
+/// A pooled buffer, owned by the `Bytes` that it
+/// was handed out in.
+struct Pooled {
+    buf: Vec<u8>,
+    len: usize,
+    pool: Weak<BytesPool>,
+}
+
+impl AsRef<[u8]> for Pooled {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl Drop for Pooled {
+    fn drop(&mut self) {
+        // the ring may be gone by now, in which
+        // case there's nothing to give it back to
+        if let Some(pool) = self.pool.upgrade() {
+            pool.put(std::mem::take(&mut self.buf));
+        }
+    }
+}
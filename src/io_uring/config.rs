@@ -3,9 +3,10 @@ use std::sync::Arc;
 use super::*;
 
 /// Configuration for the underlying `io_uring` system.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone)]
 pub struct Config {
     /// The number of entries in the submission queue.
+    /// The kernel rounds this up to a power of two.
     /// The completion queue size may be specified by
     /// using `raw_params` instead. By default, the
     /// kernel will choose a completion queue that is 2x
@@ -22,57 +23,550 @@ pub struct Config {
     /// Specify a particular CPU to pin the
     /// `SQPOLL` thread onto.
     pub sq_poll_affinity: u32,
+    /// How many milliseconds the `SQPOLL` thread
+    /// may go without seeing a submission before
+    /// it goes to sleep, after which it must be
+    /// woken up again by the next submission.
+    /// 0 leaves it up to the kernel, which
+    /// currently picks one second.
+    pub sq_poll_idle: u32,
     /// Specify that the user will directly
     /// poll the hardware for operation completion
     /// rather than using the completion queue.
     ///
     /// CURRENTLY UNSUPPORTED
     pub io_poll: bool,
+    /// Set `IORING_SETUP_COOP_TASKRUN`, so that the
+    /// kernel doesn't interrupt whichever thread
+    /// submitted an operation to finish it off,
+    /// and waits for that thread to next enter the
+    /// kernel instead, which saves an interrupt
+    /// per completion under load. `start` fails
+    /// with `InvalidInput` on kernels before 5.19.
+    pub coop_taskrun: bool,
+    /// Set `IORING_SETUP_SINGLE_ISSUER`, promising
+    /// the kernel that only the thread that calls
+    /// `start` will ever submit to this ring, which
+    /// lets it skip some synchronization. Without
+    /// the `single_thread` feature, the reaper
+    /// thread also submits, to retry operations,
+    /// so `start` fails with `InvalidInput` unless
+    /// it's enabled. Submitting, or dropping the
+    /// ring, from any other thread then panics.
+    /// `start` also fails with `InvalidInput` on
+    /// kernels before 6.0.
+    pub single_issuer: bool,
+    /// Retry reads and positional writes that fail
+    /// with errors that can go away on their own,
+    /// like `ENOSPC` on a filesystem that is being
+    /// cleaned up, with a backoff between attempts.
+    /// Sends, and anything else that isn't safe to
+    /// repeat, are never retried. `None`, the
+    /// default, disables this.
+    pub transient_retry: Option<TransientRetry>,
     /// Print a profile table on drop, showing where
     /// time was spent.
     pub print_profile_on_drop: bool,
+    /// The number of times a read, write or nop
+    /// that completes with `EINTR` will be
+    /// transparently resubmitted before the error
+    /// is returned to the caller. Since the kernel
+    /// has not transferred any data when an operation
+    /// is interrupted, its buffer is still valid and
+    /// it is safe to resubmit as-is. Operations with
+    /// `Ordering::Link` are never retried, because
+    /// the rest of their chain has already been
+    /// canceled by the time the `EINTR` is reaped.
+    /// Setting this to 0 disables retries.
+    pub eintr_retries: u32,
+    /// When a `Completion` is dropped before its
+    /// operation has completed, submit a cancellation
+    /// for it before blocking. The drop still waits
+    /// for the kernel to be done with any borrowed
+    /// buffers, but only for as long as it takes
+    /// to cancel the operation, rather than for
+    /// something like a `recv` that may never
+    /// complete. A `Completion` that has been
+    /// polled as a future is always canceled
+    /// this way, whether or not this is set.
+    pub cancel_on_drop: bool,
+    /// Called with the error of any `Completion`
+    /// that is dropped without being waited on,
+    /// and resolves to an error. Without this,
+    /// such errors are silently discarded, which
+    /// makes failures in fire-and-forget operations
+    /// invisible.
+    pub drop_error_handler:
+        Option<Arc<dyn Fn(io::Error) + Send + Sync>>,
+    /// Let an `OwnedCompletion` whose buffers it
+    /// owns outright, like the one returned by
+    /// `Uring::recv_into_vec`, be dropped before
+    /// its operation has completed without
+    /// blocking. Its buffers are handed to the
+    /// reaper instead, which frees them once the
+    /// kernel is done with them. Dropping the
+    /// ring cancels the ones still in flight, so
+    /// a recv that would never complete doesn't
+    /// keep that waiting forever. Operations on
+    /// borrowed buffers still block when dropped,
+    /// whether or not this is set, because those
+    /// buffers may be freed as soon as the drop
+    /// returns. The errors of operations that are
+    /// dropped this way are not passed to the
+    /// `drop_error_handler`.
+    pub detach_owned_on_drop: bool,
+    /// Print a warning to stderr whenever more
+    /// than 1000 operations with `Ordering::Drain`
+    /// are submitted in a single second. Each of
+    /// them waits for every operation submitted
+    /// before it, so this many almost always
+    /// means one is being used in a hot loop by
+    /// mistake. See `Uring::drain_warnings`.
+    pub warn_on_drain: bool,
+    /// The most completions that the reaper will
+    /// process before handing their tickets back
+    /// to threads waiting to submit more work.
+    /// Lower values get tickets back sooner under
+    /// bursts of completions, at some cost to
+    /// throughput. 0 means no limit, so tickets
+    /// are handed back once per batch of ready
+    /// completions.
+    pub reap_batch_max: usize,
+    /// Have the reaper hold on to the tickets of
+    /// reaped completions until it has at least
+    /// this many, or has held them for
+    /// `ticket_return_delay_us`, before handing
+    /// them back all at once. Under heavy churn,
+    /// this wakes threads waiting to submit, and
+    /// takes the locks that they take tickets
+    /// from, much less often, at the cost of a
+    /// little submission latency. Tickets are
+    /// always handed back before the reaper
+    /// blocks for more completions, so this never
+    /// holds up a submission for longer than
+    /// that. 0, the default, hands them back
+    /// after every batch of ready completions.
+    pub ticket_return_batch: usize,
+    /// The longest that `ticket_return_batch`
+    /// holds on to reaped tickets while more
+    /// completions keep coming in. Defaults to 50.
+    pub ticket_return_delay_us: u32,
+    /// Have `Uring::submit_all` leave fewer than
+    /// this many SQEs in the submission queue,
+    /// rather than entering the kernel for them
+    /// right away, so that they are submitted
+    /// together with the ones that come after
+    /// them, in fewer syscalls. They are still
+    /// submitted as soon as anything waits on one
+    /// of them, or by the first `submit_all` after
+    /// they've been held back for
+    /// `submit_batch_timeout_us`. 0, the default,
+    /// always submits right away.
+    pub submit_batch_min: usize,
+    /// The longest that `submit_batch_min` holds
+    /// back SQEs from `Uring::submit_all`.
+    /// Defaults to 100.
+    pub submit_batch_timeout_us: u32,
+    /// Have the reaper busy-poll the completion
+    /// queue for up to this many microseconds
+    /// before blocking in the kernel for the next
+    /// completion. When completions tend to arrive
+    /// within that window, this saves the syscall
+    /// and the wakeup that blocking costs, cutting
+    /// latency, but the reaper burns a whole core
+    /// while it spins, even if nothing arrives.
+    /// `None`, the default, always blocks right
+    /// away. With the `single_thread` feature, it's
+    /// the waiting threads that spin instead.
+    pub cq_spin_us: Option<u32>,
+    /// What to do when an accept fails with
+    /// `EAGAIN`, which some kernels do right away
+    /// for a non-blocking listener that has no
+    /// pending connections, rather than waiting
+    /// for one like newer ones do. By default,
+    /// that's passed on as an error of kind
+    /// `WouldBlock`. When this is set, the accept
+    /// is resubmitted instead,
+    /// behind a poll that waits for the listener
+    /// to become readable, so it completes once a
+    /// connection arrives, just like it would
+    /// have on a blocking listener. Leaving
+    /// listeners in blocking mode is simpler, and
+    /// cheaper, since rio never blocks a thread
+    /// on them either way.
+    pub retry_accept_eagain: bool,
+    /// Reorder each batch of submissions so that
+    /// operations on the same file descriptor are
+    /// handed to the kernel next to each other,
+    /// which can make IO on a mix of files more
+    /// sequential. Operations on the same fd keep
+    /// their order, linked chains are moved as a
+    /// whole, and nothing is moved past an
+    /// `Ordering::Drain`. This has no effect with
+    /// `sq_poll`, where the kernel may be reading
+    /// the submission queue while it's filled in.
+    pub group_by_fd: bool,
+    /// The longest body that `Uring::recv_framed`
+    /// will accept, so that a peer can't make us
+    /// allocate however much it likes just by
+    /// sending a huge length prefix. Longer frames
+    /// fail with `InvalidData` before anything is
+    /// allocated for them. Defaults to 16 MiB.
+    pub max_frame_len: usize,
+    /// Caps the number of kernel worker threads
+    /// that may be spawned to run this ring's
+    /// operations in the background, as
+    /// `(bounded, unbounded)`, by calling
+    /// `Uring::set_iowq_max_workers` as soon as
+    /// the ring is started. `start` fails if that
+    /// does. `None`, the default, leaves it up to
+    /// the kernel.
+    pub iowq_max_workers: Option<(u32, u32)>,
+    /// Also record this ring's metrics into a
+    /// process-wide aggregate, which is shared
+    /// with every other ring that sets this.
+    /// Each ring's own metrics are always
+    /// available from `Uring::metrics_snapshot`.
+    pub aggregate_metrics: bool,
+    /// Install a panic hook that, whenever rio
+    /// panics, prints the state of every ring that
+    /// sets this: its fd, how many operations are
+    /// in-flight, how many tickets are free, and
+    /// its metrics. The hook is installed once, the
+    /// first time a ring that sets this is started,
+    /// and runs whatever hook was there before
+    /// first.
+    pub panic_diagnostics: bool,
     /// setting `raw_params` overrides everything else
     pub raw_params: Option<io_uring_params>,
 }
 
+/// Which errors `Config::transient_retry` retries
+/// operations on, and how often.
+///
+/// An operation is resubmitted after waiting
+/// `initial_backoff`, then twice that, and so on,
+/// up to `max_backoff` between attempts, until it
+/// either succeeds or has been retried
+/// `max_attempts` times, after which its error is
+/// returned. Retries after `EINTR` count towards
+/// the same attempts.
+///
+/// # Warning
+///
+/// Waiting between attempts needs
+/// `IOSQE_IO_HARDLINK`, which came in linux 5.5.
+/// On older kernels, an operation that is retried
+/// after a nonzero backoff fails with `ECANCELED`.
+#[derive(Debug, Clone)]
+pub struct TransientRetry {
+    /// The `errno` values to retry on. By default,
+    /// `ENOSPC` and `EIO`.
+    pub errnos: Vec<i32>,
+    /// The most times that an operation is
+    /// retried before its error is returned.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest to wait before any retry.
+    pub max_backoff: Duration,
+}
+
+impl Default for TransientRetry {
+    fn default() -> TransientRetry {
+        TransientRetry {
+            errnos: vec![libc::ENOSPC, libc::EIO],
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl TransientRetry {
+    /// How long to wait before retrying after
+    /// `attempts` earlier retries.
+    pub(crate) fn backoff(
+        &self,
+        attempts: u32,
+    ) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2_u32.saturating_pow(attempts))
+            .min(self.max_backoff)
+    }
+}
+
 impl Default for Config {
     fn default() -> Config {
         Config {
             depth: 256,
             sq_poll: false,
             io_poll: false,
+            coop_taskrun: false,
+            single_issuer: false,
             sq_poll_affinity: 0,
+            sq_poll_idle: 0,
             raw_params: None,
             print_profile_on_drop: false,
+            eintr_retries: 3,
+            transient_retry: None,
+            cancel_on_drop: false,
+            drop_error_handler: None,
+            detach_owned_on_drop: false,
+            warn_on_drain: false,
+            reap_batch_max: 0,
+            ticket_return_batch: 0,
+            ticket_return_delay_us: 50,
+            submit_batch_min: 0,
+            submit_batch_timeout_us: 100,
+            cq_spin_us: None,
+            retry_accept_eagain: false,
+            group_by_fd: false,
+            max_frame_len: 16 * 1024 * 1024,
+            iowq_max_workers: None,
+            aggregate_metrics: false,
+            panic_diagnostics: false,
         }
     }
 }
 
+impl std::fmt::Debug for Config {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field("depth", &self.depth)
+            .field("sq_poll", &self.sq_poll)
+            .field(
+                "sq_poll_affinity",
+                &self.sq_poll_affinity,
+            )
+            .field("sq_poll_idle", &self.sq_poll_idle)
+            .field("io_poll", &self.io_poll)
+            .field("coop_taskrun", &self.coop_taskrun)
+            .field("single_issuer", &self.single_issuer)
+            .field(
+                "print_profile_on_drop",
+                &self.print_profile_on_drop,
+            )
+            .field("eintr_retries", &self.eintr_retries)
+            .field("transient_retry", &self.transient_retry)
+            .field("cancel_on_drop", &self.cancel_on_drop)
+            .field(
+                "drop_error_handler",
+                &self
+                    .drop_error_handler
+                    .as_ref()
+                    .map(|_| ".."),
+            )
+            .field(
+                "detach_owned_on_drop",
+                &self.detach_owned_on_drop,
+            )
+            .field("warn_on_drain", &self.warn_on_drain)
+            .field("reap_batch_max", &self.reap_batch_max)
+            .field(
+                "ticket_return_batch",
+                &self.ticket_return_batch,
+            )
+            .field(
+                "ticket_return_delay_us",
+                &self.ticket_return_delay_us,
+            )
+            .field(
+                "submit_batch_min",
+                &self.submit_batch_min,
+            )
+            .field(
+                "submit_batch_timeout_us",
+                &self.submit_batch_timeout_us,
+            )
+            .field("cq_spin_us", &self.cq_spin_us)
+            .field(
+                "retry_accept_eagain",
+                &self.retry_accept_eagain,
+            )
+            .field("group_by_fd", &self.group_by_fd)
+            .field("max_frame_len", &self.max_frame_len)
+            .field(
+                "iowq_max_workers",
+                &self.iowq_max_workers,
+            )
+            .field(
+                "aggregate_metrics",
+                &self.aggregate_metrics,
+            )
+            .field(
+                "panic_diagnostics",
+                &self.panic_diagnostics,
+            )
+            .field("raw_params", &self.raw_params)
+            .finish()
+    }
+}
+
 impl Config {
+    /// Suggests a `depth` for a workload that keeps
+    /// about `expected_concurrency` operations
+    /// in-flight at once, by rounding it up to the
+    /// next power of two, and then into the range
+    /// of 16 to 4096. Very small values get
+    /// 16, which doesn't cost much, and leaves some
+    /// room for bursts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let config = rio::Config {
+    ///     depth: rio::Config::suggest_depth(200),
+    ///     ..rio::Config::default()
+    /// };
+    /// assert_eq!(config.depth, 256);
+    /// ```
+    pub const fn suggest_depth(
+        expected_concurrency: usize,
+    ) -> usize {
+        const MIN_DEPTH: usize = 16;
+        const MAX_DEPTH: usize = 4096;
+
+        if expected_concurrency <= MIN_DEPTH {
+            MIN_DEPTH
+        } else if expected_concurrency >= MAX_DEPTH {
+            MAX_DEPTH
+        } else {
+            expected_concurrency.next_power_of_two()
+        }
+    }
+
+    /// Create a `Config` from the following
+    /// environment variables, falling back to
+    /// the default for any that are unset:
+    ///
+    /// * `RIO_DEPTH`: `depth`
+    /// * `RIO_SQ_POLL`: `sq_poll`
+    /// * `RIO_SQ_POLL_AFFINITY`: `sq_poll_affinity`
+    /// * `RIO_SQ_POLL_IDLE`: `sq_poll_idle`
+    /// * `RIO_PRINT_PROFILE`: `print_profile_on_drop`
+    ///
+    /// Boolean variables accept `1`, `0`, `true`
+    /// or `false`. Returns an error of kind
+    /// `io::ErrorKind::InvalidInput` if any
+    /// variable fails to parse, or if the
+    /// resulting `Config` is invalid.
+    pub fn from_env() -> io::Result<Config> {
+        let mut config = Config::default();
+
+        if let Some(depth) = env_var("RIO_DEPTH")? {
+            config.depth = depth;
+        }
+        if let Some(sq_poll) = env_bool("RIO_SQ_POLL")? {
+            config.sq_poll = sq_poll;
+        }
+        if let Some(affinity) =
+            env_var("RIO_SQ_POLL_AFFINITY")?
+        {
+            config.sq_poll_affinity = affinity;
+        }
+        if let Some(idle) = env_var("RIO_SQ_POLL_IDLE")? {
+            config.sq_poll_idle = idle;
+        }
+        if let Some(print) = env_bool("RIO_PRINT_PROFILE")?
+        {
+            config.print_profile_on_drop = print;
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        if self.single_issuer
+            && !cfg!(feature = "single_thread")
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Config::single_issuer needs the \
+                 single_thread feature, because the \
+                 reaper thread submits retries",
+            ));
+        }
+
+        // with `raw_params`, its flags may have the
+        // kernel clamp the depth, so it's left to
+        // the kernel to reject
+        if self.raw_params.is_none()
+            && !(1..=IORING_MAX_ENTRIES)
+                .contains(&self.depth)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Config::depth must be between 1 and \
+                     {} (inclusive)",
+                    IORING_MAX_ENTRIES
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Start the `Rio` system.
-    pub fn start(mut self) -> io::Result<Rio> {
-        let mut params =
-            if let Some(params) = self.raw_params.take() {
-                params
-            } else {
-                let mut params = io_uring_params::default();
-
-                if self.sq_poll {
-                    // set SQPOLL mode to avoid needing wakeup
-                    params.flags = IORING_SETUP_SQPOLL;
-                    params.sq_thread_cpu =
-                        self.sq_poll_affinity;
-                }
-
-                params
-            };
+    ///
+    /// This spawns a thread that reaps completions
+    /// in the background, unless the `single_thread`
+    /// feature is enabled, in which case they are
+    /// reaped by whichever thread is waiting on one.
+    ///
+    /// Returns an error of kind
+    /// `io::ErrorKind::InvalidInput` if `depth`
+    /// is not between 1 and 32768 (inclusive),
+    /// unless `raw_params` is set, or if
+    /// `single_issuer` is set without the
+    /// `single_thread` feature.
+    pub fn start(self) -> io::Result<Rio> {
+        self.validate()?;
+
+        // raw_params is copied rather than taken,
+        // so that `Rio::post_fork_child` can start
+        // an identical ring from the stored config
+        let mut params = if let Some(params) =
+            self.raw_params
+        {
+            params
+        } else {
+            let mut params = io_uring_params::default();
+
+            if self.coop_taskrun {
+                params.flags |= IORING_SETUP_COOP_TASKRUN;
+            }
+            if self.single_issuer {
+                params.flags |= IORING_SETUP_SINGLE_ISSUER;
+            }
+
+            if self.sq_poll {
+                // set SQPOLL mode to avoid needing wakeup
+                params.flags |= IORING_SETUP_SQPOLL;
+                params.sq_thread_cpu =
+                    self.sq_poll_affinity;
+                params.sq_thread_idle = self.sq_poll_idle;
+            }
+
+            params
+        };
 
         let params_ptr: *mut io_uring_params = &mut params;
 
-        let ring_fd = setup(
-            u32::try_from(self.depth).unwrap(),
-            params_ptr,
-        )?;
+        let depth =
+            u32::try_from(self.depth).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Config::depth is too big",
+                )
+            })?;
+        let ring_fd = setup(depth, params_ptr)?;
 
         if ring_fd < 0 {
             let mut err = io::Error::last_os_error();
@@ -87,34 +581,125 @@ impl Config {
             return Err(err);
         }
 
+        let metrics = Arc::new(Metrics {
+            aggregate: self.aggregate_metrics,
+            ..Metrics::default()
+        });
+
         let in_flight = Arc::new(InFlight::new(
             params.cq_entries as usize,
         ));
 
         let ticket_queue = Arc::new(TicketQueue::new(
             params.cq_entries as usize,
+            metrics.clone(),
         ));
 
-        let sq = Sq::new(&params, ring_fd)?;
+        if self.panic_diagnostics {
+            diagnostics::register(
+                ring_fd,
+                &ticket_queue,
+                &metrics,
+            );
+        }
+
+        let sq = Sq::new(
+            &params,
+            ring_fd,
+            self.group_by_fd,
+            metrics.clone(),
+        )?;
+        let submitter = Arc::new(Submitter::new(
+            sq,
+            params.flags,
+            ring_fd,
+            self.submit_batch_min,
+            Duration::from_micros(u64::from(
+                self.submit_batch_timeout_us,
+            )),
+            metrics.clone(),
+        ));
+        let shared = RingShared {
+            submitter,
+            in_flight,
+            ticket_queue,
+            wakeup: Arc::new(Wakeup::default()),
+            metrics,
+            messages: Arc::new(Mutex::new(VecDeque::new())),
+        };
         let cq = Cq::new(
             &params,
             ring_fd,
-            in_flight.clone(),
-            ticket_queue.clone(),
+            self.clone(),
+            &shared,
         )?;
 
-        std::thread::spawn(move || {
-            let mut cq = cq;
-            cq.reaper(ring_fd)
-        });
+        let cq_peek = cq.peek();
+
+        #[cfg(not(feature = "single_thread"))]
+        let _ = std::thread::Builder::new()
+            .name("rio-reaper".into())
+            .spawn(move || {
+                let mut reaper = cq;
+                reaper.reaper(ring_fd)
+            })?;
 
-        Ok(Rio(Arc::new(Uring::new(
+        let iowq_max_workers = self.iowq_max_workers;
+
+        let rio = Rio(Arc::new(Uring::new(
             self,
-            params.flags,
             ring_fd,
-            sq,
-            in_flight,
-            ticket_queue,
-        ))))
+            &params,
+            shared,
+            cq_peek,
+            #[cfg(feature = "single_thread")]
+            cq,
+        )));
+
+        if let Some((bounded, unbounded)) = iowq_max_workers
+        {
+            rio.set_iowq_max_workers(bounded, unbounded)?;
+        }
+
+        Ok(rio)
+    }
+}
+
+fn env_var<T: std::str::FromStr>(
+    name: &str,
+) -> io::Result<Option<T>> {
+    match std::env::var(name) {
+        Ok(value) => {
+            value.trim().parse().map(Some).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "failed to parse {}={:?}",
+                        name, value
+                    ),
+                )
+            })
+        }
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            e,
+        )),
+    }
+}
+
+fn env_bool(name: &str) -> io::Result<Option<bool>> {
+    match env_var::<String>(name)?.as_deref() {
+        None => Ok(None),
+        Some("1") | Some("true") => Ok(Some(true)),
+        Some("0") | Some("false") => Ok(Some(false)),
+        Some(value) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "failed to parse {}={:?}, expected \
+                 one of 1, 0, true or false",
+                name, value
+            ),
+        )),
     }
 }
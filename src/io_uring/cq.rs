@@ -4,6 +4,20 @@ use std::slice::from_raw_parts_mut;
 
 use super::*;
 
+/// The `user_data` of the timeouts and polls that
+/// wait in front of a delayed retry. Its ticket bits are
+/// all set, which is never a real ticket, and its
+/// top bit is clear, so it isn't mistaken for the
+/// poison pill either.
+const RETRY_DELAY: u64 = u64::MAX >> 1;
+
+/// The CQE flags that `Uring::msg_ring` has the
+/// kernel post its messages with, which no real
+/// completion ever has, since without
+/// `IORING_CQE_F_BUFFER` set, the kernel never
+/// sets any of the upper bits.
+pub(crate) const RING_MESSAGE: u32 = 1 << 31;
+
 /// Consumes uring completions.
 #[derive(Debug)]
 pub struct Cq {
@@ -14,14 +28,38 @@ pub struct Cq {
     cqes: *mut [io_uring_cqe],
     ticket_queue: Arc<TicketQueue>,
     in_flight: Arc<InFlight>,
+    submitter: Arc<Submitter>,
+    wakeup: Arc<Wakeup>,
+    metrics: Arc<Metrics>,
+    config: Config,
+    // reused by every reap, to avoid allocating
+    tickets_to_push: Vec<usize>,
+    // when the oldest of `tickets_to_push` was
+    // reaped, if they are being held for a batch
+    held_since: Option<Instant>,
+    mapping: Arc<CqMapping>,
+    messages: Arc<Mutex<VecDeque<RingMessage>>>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for Cq {}
+
+/// The completion queue's memory, which is shared
+/// by the `Cq` and any `CqPeek`s, and unmapped
+/// once they are all gone.
+#[derive(Debug)]
+struct CqMapping {
     ring_ptr: *const libc::c_void,
     ring_mmap_sz: usize,
 }
 
 #[allow(unsafe_code)]
-unsafe impl Send for Cq {}
+unsafe impl Send for CqMapping {}
+
+#[allow(unsafe_code)]
+unsafe impl Sync for CqMapping {}
 
-impl Drop for Cq {
+impl Drop for CqMapping {
     fn drop(&mut self) {
         #[allow(unsafe_code)]
         unsafe {
@@ -33,12 +71,73 @@ impl Drop for Cq {
     }
 }
 
+/// A read-only view of the completion queue,
+/// for checking whether a completion has been
+/// posted by the kernel before the reaper has
+/// gotten around to it.
+#[derive(Debug)]
+pub(crate) struct CqPeek {
+    khead: *const AtomicU32,
+    ktail: *const AtomicU32,
+    kring_mask: *const u32,
+    koverflow: *const AtomicU32,
+    cqes: *const io_uring_cqe,
+    _mapping: Arc<CqMapping>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for CqPeek {}
+
+#[allow(unsafe_code)]
+unsafe impl Sync for CqPeek {}
+
+impl CqPeek {
+    /// Fills in the completion queue's side of
+    /// `state`.
+    pub(crate) fn dump_state(&self, state: &mut RingState) {
+        state.cq_head =
+            unsafe { &*self.khead }.load(Acquire);
+        state.cq_tail =
+            unsafe { &*self.ktail }.load(Acquire);
+        state.cq_mask = unsafe { *self.kring_mask };
+        state.cq_overflow =
+            unsafe { &*self.koverflow }.load(Acquire);
+    }
+
+    /// Whether the completion for `user_data` is
+    /// sitting in the completion queue, waiting
+    /// to be reaped.
+    pub(crate) fn is_posted(&self, user_data: u64) -> bool {
+        let head = unsafe { &*self.khead }.load(Acquire);
+        let tail = unsafe { &*self.ktail }.load(Acquire);
+        let mask = unsafe { *self.kring_mask };
+
+        let mut i = head;
+        while i != tail {
+            // the reaper may be done with this slot
+            // and the kernel reusing it by now, so
+            // we may see a torn cqe, which is
+            // fine for a best-effort check.
+            let cqe = unsafe {
+                std::ptr::read_volatile(
+                    self.cqes.add((i & mask) as usize),
+                )
+            };
+            if cqe.user_data == user_data {
+                return true;
+            }
+            i = i.wrapping_add(1);
+        }
+        false
+    }
+}
+
 impl Cq {
     pub(crate) fn new(
         params: &io_uring_params,
         ring_fd: i32,
-        in_flight: Arc<InFlight>,
-        ticket_queue: Arc<TicketQueue>,
+        config: Config,
+        shared: &RingShared,
     ) -> io::Result<Cq> {
         // TODO IORING_FEAT_SINGLE_MMAP for cq
         let cq_ring_mmap_sz = params.cq_off.cqes as usize
@@ -54,8 +153,10 @@ impl Cq {
         #[allow(unsafe_code)]
         Ok(unsafe {
             Cq {
-                ring_ptr: cq_ring_ptr,
-                ring_mmap_sz: cq_ring_mmap_sz,
+                mapping: Arc::new(CqMapping {
+                    ring_ptr: cq_ring_ptr,
+                    ring_mmap_sz: cq_ring_mmap_sz,
+                }),
                 khead: cq_ring_ptr
                     .add(params.cq_off.head as usize)
                     as *mut AtomicU32,
@@ -74,45 +175,152 @@ impl Cq {
                         as _,
                     params.cq_entries as usize,
                 ),
-                in_flight: in_flight.clone(),
-                ticket_queue: ticket_queue.clone(),
+                in_flight: shared.in_flight.clone(),
+                ticket_queue: shared.ticket_queue.clone(),
+                submitter: shared.submitter.clone(),
+                wakeup: shared.wakeup.clone(),
+                metrics: shared.metrics.clone(),
+                config,
+                tickets_to_push: Vec::with_capacity(
+                    params.cq_entries as usize,
+                ),
+                held_since: None,
+                messages: shared.messages.clone(),
             }
         })
     }
 
+    pub(crate) fn peek(&self) -> CqPeek {
+        CqPeek {
+            khead: self.khead,
+            ktail: self.ktail,
+            kring_mask: self.kring_mask,
+            koverflow: self.koverflow,
+            cqes: self.cqes.cast(),
+            _mapping: self.mapping.clone(),
+        }
+    }
+
+    #[cfg(not(feature = "single_thread"))]
     pub(crate) fn reaper(&mut self, ring_fd: i32) {
-        fn block_for_cqe(ring_fd: i32) -> io::Result<()> {
+        while self.reap(ring_fd, 1).is_some() {}
+        // poison pill detected, time to shut down
+    }
+
+    /// Blocks until at least `min_complete`
+    /// completions are ready, and then reaps all
+    /// of the ready ones. Returns `None` once the
+    /// poison pill has been reaped.
+    pub(crate) fn reap(
+        &mut self,
+        ring_fd: i32,
+        min_complete: u32,
+    ) -> Option<usize> {
+        fn block_for_cqe(
+            ring_fd: i32,
+            wait: u32,
+        ) -> io::Result<()> {
             let flags = IORING_ENTER_GETEVENTS;
             let submit = 0;
-            let wait = 1;
             let sigset = std::ptr::null_mut();
 
-            let _ = Measure::new(&M.enter_cqe);
             enter(ring_fd, submit, wait, flags, sigset)?;
 
             Ok(())
         }
 
-        loop {
-            if let Err(e) = block_for_cqe(ring_fd) {
+        // tickets that are held for a bigger batch
+        // are handed back instead of blocking with
+        // them, since the completion we'd block for
+        // may be waiting on a submission that needs
+        // one of them.
+        if !self.tickets_to_push.is_empty()
+            && !self.has_ready_cqe()
+        {
+            self.return_tickets(true);
+            return Some(0);
+        }
+
+        let spun = min_complete > 0 && self.spin_for_cqe();
+        if !spun {
+            let _ = self.metrics.measure(|m| &m.enter_cqe);
+            if let Err(e) =
+                block_for_cqe(ring_fd, min_complete)
+            {
                 panic!("error in cqe reaper: {:?}", e);
-            } else {
-                assert_eq!(
-                    unsafe {
-                        (*self.koverflow).load(Relaxed)
-                    },
-                    0
-                );
-                if self.reap_ready_cqes().is_none() {
-                    // poison pill detected, time to shut down
-                    return;
-                }
             }
         }
+
+        assert_eq!(
+            unsafe { (*self.koverflow).load(Relaxed) },
+            0
+        );
+
+        self.reap_ready_cqes()
+    }
+
+    /// Busy-polls the completion queue for up to
+    /// `Config::cq_spin_us`, returning whether a
+    /// completion showed up before then.
+    fn spin_for_cqe(&self) -> bool {
+        let spin_us = match self.config.cq_spin_us {
+            Some(spin_us) => spin_us,
+            None => return false,
+        };
+        let deadline = Instant::now()
+            + Duration::from_micros(u64::from(spin_us));
+
+        loop {
+            if self.has_ready_cqe() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Whether the kernel has posted completions
+    /// that haven't been reaped yet.
+    fn has_ready_cqe(&self) -> bool {
+        let head = unsafe { &*self.khead }.load(Acquire);
+        let tail = unsafe { &*self.ktail }.load(Acquire);
+        head != tail
+    }
+
+    /// Hands the tickets of reaped completions
+    /// back to submitters, unless
+    /// `Config::ticket_return_batch` has them held
+    /// for a bigger batch and `force` isn't set.
+    fn return_tickets(&mut self, force: bool) {
+        if self.tickets_to_push.is_empty() {
+            return;
+        }
+
+        let batch = self.config.ticket_return_batch;
+        if !force
+            && batch != 0
+            && self.tickets_to_push.len() < batch
+        {
+            let held_since = *self
+                .held_since
+                .get_or_insert_with(Instant::now);
+            let delay = Duration::from_micros(u64::from(
+                self.config.ticket_return_delay_us,
+            ));
+            if held_since.elapsed() < delay {
+                return;
+            }
+        }
+
+        self.ticket_queue
+            .push_multi(&mut self.tickets_to_push);
+        self.held_since = None;
     }
 
     fn reap_ready_cqes(&mut self) -> Option<usize> {
-        let _ = Measure::new(&M.reap_ready);
+        let _ = self.metrics.measure(|m| &m.reap_ready);
         let mut head =
             unsafe { &*self.khead }.load(Acquire);
         let tail = unsafe { &*self.ktail }.load(Acquire);
@@ -122,55 +330,493 @@ impl Cq {
         // limitation as of rust 1.40
         let mut cq_opt = Some(self);
 
-        let mut to_push =
-            Vec::with_capacity(count as usize);
+        let mut to_retry = vec![];
+        let mut to_delay = vec![];
+        let mut to_poll = vec![];
 
         while head != tail {
             let cq = cq_opt.take().unwrap();
             let index = head & unsafe { *cq.kring_mask };
             let cqe = &unsafe { &*cq.cqes }[index as usize];
 
+            if cqe.flags == RING_MESSAGE {
+                // posted by `msg_ring`, so there's no
+                // ticket, just a message to hand over
+                cq.messages.lock().unwrap().push_back(
+                    RingMessage {
+                        data: cqe.user_data,
+                        len: u32::from_ne_bytes(
+                            cqe.res.to_ne_bytes(),
+                        ),
+                    },
+                );
+                unsafe { &*cq.khead }.fetch_add(1, Release);
+                head += 1;
+                cq_opt = Some(cq);
+                continue;
+            }
+
+            if cqe.user_data == RETRY_DELAY {
+                // nothing waits on these, the retry
+                // that it was linked to goes ahead
+                // whatever its result.
+                unsafe { &*cq.khead }.fetch_add(1, Release);
+                head += 1;
+                cq_opt = Some(cq);
+                continue;
+            }
+
             // we detect a poison pill by seeing if
             // the user_data is really big, which it
             // will tend not to be. if it's not a
             // poison pill, it will be up to as large
             // as the completion queue length.
-            let (ticket, poisoned) =
+            let (user_data, poisoned) =
                 if cqe.user_data > u64::max_value() / 2 {
                     (cqe.user_data ^ u64::max_value(), true)
                 } else {
                     (cqe.user_data, false)
                 };
+            let ticket =
+                usize::try_from(user_data & TICKET_MASK)
+                    .unwrap();
 
             let res = cqe.res;
 
-            let completion_filler =
-                cq.in_flight.take_filler(ticket as usize);
-            to_push.push(ticket as usize);
+            let send_all = !poisoned
+                && cq.in_flight.is_send_all(ticket);
+
+            let tag = if poisoned {
+                None
+            } else {
+                cq.in_flight.tag(ticket)
+            };
+
+            let (frame_rest, frame_err) = if poisoned {
+                (None, None)
+            } else {
+                match cq.frame_step(ticket, res) {
+                    Ok(rest) => (rest, None),
+                    Err(e) => (None, Some(e)),
+                }
+            };
 
             let result = if res < 0 {
                 Err(io::Error::from_raw_os_error(res.neg()))
+            } else if send_all && res == 0 {
+                Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "send_all failed to send any bytes",
+                ))
+            } else if let Some(e) = frame_err {
+                Err(e)
+            } else if let Some(caller_tag) = tag {
+                // raw operations submitted with a tag
+                // hand it back in place of our own
+                // user_data, which is of no use to them.
+                Ok(io_uring_cqe {
+                    user_data: caller_tag,
+                    ..*cqe
+                })
             } else {
                 Ok(*cqe)
             };
 
-            completion_filler.fill(result);
+            let nowait_fallback = if poisoned {
+                None
+            } else {
+                cq.nowait_fallback(ticket, res)
+            };
+
+            let transient_backoff = if poisoned {
+                None
+            } else {
+                cq.transient_backoff(ticket, res)
+            };
+
+            let accept_retry = if poisoned {
+                None
+            } else {
+                cq.accept_retry(ticket, res)
+            };
+
+            if !poisoned
+                && res == -libc::EINTR
+                && cq.should_retry(ticket)
+            {
+                // the filler and ticket stay in place,
+                // and the operation is resubmitted
+                // below as if nothing happened.
+                to_retry.push(cq.in_flight.sqe(ticket));
+            } else if let Some(backoff) = transient_backoff
+            {
+                // the same as for `EINTR`, except that
+                // it's delayed by a timeout linked in
+                // front of it, unless there's no wait.
+                let sqe = cq.in_flight.sqe(ticket);
+                if backoff == Duration::ZERO {
+                    to_retry.push(sqe);
+                } else {
+                    let delay = cq
+                        .in_flight
+                        .set_delay(ticket, backoff);
+                    to_delay.push((delay, sqe));
+                }
+            } else if let Some(accept) = accept_retry {
+                // the listener is non-blocking, so this
+                // waits for it to be readable first.
+                to_poll.push(accept);
+            } else if let Some(blocking) = nowait_fallback {
+                // the buffer is owned by the completion,
+                // so it's safe to try again, this time
+                // letting the write block.
+                cq.in_flight.set_sqe(ticket, blocking);
+                to_retry.push(blocking);
+            } else if let Some(rest) = frame_rest {
+                // the next part of a `recv_framed`,
+                // which may be the start of its body.
+                cq.in_flight.set_sqe(ticket, rest);
+                to_retry.push(rest);
+            } else if let Some(rest) =
+                cq.unsent_tail(ticket, res)
+            {
+                // like a retry, but for the part of
+                // the buffer that wasn't sent yet.
+                cq.in_flight.set_sqe(ticket, rest);
+                to_retry.push(rest);
+            } else if let Some(multi_filler) =
+                cq.in_flight.multi_filler(ticket)
+            {
+                // multishot tickets are handed back
+                // when their `Multishot` is dropped.
+                let more =
+                    cqe.flags & IORING_CQE_F_MORE != 0;
+                multi_filler.fill(result, more);
+            } else {
+                let completion_filler =
+                    cq.in_flight.take_filler(ticket);
+                cq.tickets_to_push.push(ticket);
+
+                completion_filler.fill(result);
+            }
 
             unsafe { &*cq.khead }.fetch_add(1, Release);
-            cq_opt = Some(cq);
             head += 1;
 
+            let batch_max = cq.config.reap_batch_max;
+            if batch_max != 0
+                && cq.tickets_to_push.len() >= batch_max
+            {
+                cq.return_tickets(true);
+                cq.wakeup.notify();
+            }
+
             if poisoned {
+                cq.wakeup.notify();
                 return None;
             }
+
+            cq_opt = Some(cq);
         }
 
-        cq_opt
-            .take()
-            .unwrap()
-            .ticket_queue
-            .push_multi(to_push);
+        let cq = cq_opt.take().unwrap();
+
+        if !to_retry.is_empty()
+            || !to_delay.is_empty()
+            || !to_poll.is_empty()
+        {
+            for sqe in to_retry {
+                let pushed =
+                    cq.submitter.push(|s| *s = sqe);
+                if let Err(e) = pushed {
+                    cq.fail_resubmit(&sqe, e);
+                }
+            }
+            for (delay, sqe) in to_delay {
+                let pushed = cq.submitter.push_pair(
+                    |s| {
+                        *s = io_uring_sqe::default();
+                        s.prep_rw(
+                            IORING_OP_TIMEOUT,
+                            -1,
+                            1,
+                            0,
+                            Ordering::None,
+                        );
+                        s.addr = delay as u64;
+                        // unlike a soft link, this isn't
+                        // broken by the timeout's ETIME
+                        s.flags |= IOSQE_IO_HARDLINK;
+                        s.user_data = RETRY_DELAY;
+                    },
+                    |s| *s = sqe,
+                );
+                if let Err(e) = pushed {
+                    cq.fail_resubmit(&sqe, e);
+                }
+            }
+            for sqe in to_poll {
+                let pushed = cq.submitter.push_pair(
+                    |s| {
+                        *s = io_uring_sqe::default();
+                        s.prep_rw(
+                            IORING_OP_POLL_ADD,
+                            sqe.fd,
+                            0,
+                            0,
+                            Ordering::None,
+                        );
+                        s.__bindgen_anon_1.poll_events =
+                            PollFlags::IN.bits();
+                        s.flags |= IOSQE_IO_HARDLINK
+                            | (sqe.flags
+                                & IOSQE_FIXED_FILE);
+                        s.user_data = RETRY_DELAY;
+                    },
+                    |s| *s = sqe,
+                );
+                if let Err(e) = pushed {
+                    cq.fail_resubmit(&sqe, e);
+                }
+            }
+            cq.submitter.submit_pending();
+        }
+
+        cq.return_tickets(false);
+
+        // wake everything that was waiting on any of
+        // the completions we just filled at once.
+        cq.wakeup.notify();
 
         Some(count as usize)
     }
+
+    /// Fails the operation that `sqe` would have
+    /// resubmitted, since pushing it failed.
+    fn fail_resubmit(
+        &mut self,
+        sqe: &io_uring_sqe,
+        e: io::Error,
+    ) {
+        let ticket =
+            usize::try_from(sqe.user_data & TICKET_MASK)
+                .unwrap();
+        if self.in_flight.fail(ticket, e) {
+            self.tickets_to_push.push(ticket);
+        }
+    }
+
+    /// For a `send_all` that only sent part of its
+    /// buffer, returns an SQE that sends the rest.
+    fn unsent_tail(
+        &self,
+        ticket: usize,
+        res: i32,
+    ) -> Option<io_uring_sqe> {
+        if !self.in_flight.is_send_all(ticket) || res <= 0 {
+            return None;
+        }
+
+        let mut sqe = self.in_flight.sqe(ticket);
+        let sent = u32::try_from(res).unwrap();
+        if sent >= sqe.len {
+            return None;
+        }
+
+        sqe.addr += u64::from(sent);
+        sqe.len -= sent;
+
+        Some(sqe)
+    }
+
+    /// For a `recv_framed` that received `res`
+    /// bytes, returns an SQE that receives the
+    /// rest of its prefix or body, or `None` once
+    /// the whole frame has been received. Fails if
+    /// the peer hangs up partway through, or sends
+    /// a length over `Config::max_frame_len`.
+    fn frame_step(
+        &self,
+        ticket: usize,
+        res: i32,
+    ) -> io::Result<Option<io_uring_sqe>> {
+        #[allow(unsafe_code)]
+        let frame = match unsafe {
+            self.in_flight.frame(ticket).as_mut()
+        } {
+            Some(frame) if res >= 0 => frame,
+            _ => return Ok(None),
+        };
+
+        if res == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "EOF partway through a recv_framed frame",
+            ));
+        }
+
+        let mut sqe = self.in_flight.sqe(ticket);
+        let received = u32::try_from(res).unwrap();
+        frame.received += usize::try_from(res).unwrap();
+        sqe.addr += u64::from(received);
+        sqe.len -= received;
+        if sqe.len > 0 {
+            return Ok(Some(sqe));
+        }
+        if frame.body.is_some() {
+            return Ok(None);
+        }
+
+        let len = frame.prefix[..frame.prefix_len]
+            .iter()
+            .fold(0_u64, |len, b| len << 8 | u64::from(*b));
+        let body_len = match u32::try_from(len) {
+            Ok(body_len)
+                if usize::try_from(body_len).unwrap()
+                    <= frame.max_len =>
+            {
+                body_len
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                    "recv_framed got a frame of {} bytes, \
+                         over the max_frame_len of {}",
+                    len, frame.max_len,
+                ),
+                ))
+            }
+        };
+
+        let mut body = Vec::with_capacity(
+            usize::try_from(body_len).unwrap(),
+        );
+        frame.received = 0;
+        sqe.addr = body.as_mut_ptr() as u64;
+        sqe.len = body_len;
+        frame.body = Some(body);
+
+        Ok(if body_len == 0 { None } else { Some(sqe) })
+    }
+
+    /// For a `write_at_nowait` that would have
+    /// blocked, returns an SQE that performs the
+    /// same write without `RWF_NOWAIT`. Other
+    /// writes that ask for `RWF_NOWAIT` get their
+    /// `EAGAIN` back.
+    fn nowait_fallback(
+        &self,
+        ticket: usize,
+        res: i32,
+    ) -> Option<io_uring_sqe> {
+        if res != -libc::EAGAIN
+            || !self.in_flight.is_nowait_fallback(ticket)
+        {
+            return None;
+        }
+
+        let mut sqe = self.in_flight.sqe(ticket);
+        let rw_flags =
+            unsafe { sqe.__bindgen_anon_1.rw_flags };
+
+        sqe.__bindgen_anon_1.rw_flags =
+            rw_flags & !libc::RWF_NOWAIT;
+
+        Some(sqe)
+    }
+
+    /// For an accept that failed with `EAGAIN`,
+    /// when `Config::retry_accept_eagain` is set,
+    /// returns the SQE to submit again once the
+    /// listener is readable.
+    fn accept_retry(
+        &self,
+        ticket: usize,
+        res: i32,
+    ) -> Option<io_uring_sqe> {
+        if res != -libc::EAGAIN
+            || !self.config.retry_accept_eagain
+        {
+            return None;
+        }
+
+        let sqe = self.in_flight.sqe(ticket);
+        if sqe.opcode == IORING_OP_ACCEPT {
+            Some(sqe)
+        } else {
+            None
+        }
+    }
+
+    /// For a read or positional write that failed
+    /// with one of `Config::transient_retry`'s
+    /// errors, and hasn't run out of attempts,
+    /// returns how long to wait before retrying it.
+    fn transient_backoff(
+        &self,
+        ticket: usize,
+        res: i32,
+    ) -> Option<Duration> {
+        let policy =
+            self.config.transient_retry.as_ref()?;
+        if res >= 0 || !policy.errnos.contains(&res.neg()) {
+            return None;
+        }
+
+        let sqe = self.in_flight.sqe(ticket);
+        let rw_flags =
+            unsafe { sqe.__bindgen_anon_1.rw_flags };
+
+        // writes at the current position, or to the
+        // end of the file, would land somewhere else
+        // the second time around.
+        let positional = matches!(
+            sqe.opcode,
+            IORING_OP_READV
+                | IORING_OP_WRITEV
+                | IORING_OP_READ_FIXED
+                | IORING_OP_WRITE_FIXED
+                | IORING_OP_READ
+                | IORING_OP_WRITE
+        ) && sqe.off != u64::MAX
+            && rw_flags & libc::RWF_APPEND == 0;
+
+        if !positional || sqe.flags & IOSQE_IO_LINK != 0 {
+            return None;
+        }
+
+        let attempts = self.in_flight.bump_attempts(ticket);
+        if attempts < policy.max_attempts {
+            Some(policy.backoff(attempts))
+        } else {
+            None
+        }
+    }
+
+    /// Operations that were interrupted are retried
+    /// up to `Config::eintr_retries` times, as long
+    /// as they are safe to resubmit as-is. Linked
+    /// operations are never retried, because the
+    /// rest of their chain has already been canceled
+    /// with `ECANCELED` by the time we see this.
+    fn should_retry(&self, ticket: usize) -> bool {
+        let sqe = self.in_flight.sqe(ticket);
+
+        let idempotent = matches!(
+            sqe.opcode,
+            IORING_OP_NOP
+                | IORING_OP_READV
+                | IORING_OP_WRITEV
+                | IORING_OP_READ_FIXED
+                | IORING_OP_WRITE_FIXED
+                | IORING_OP_READ
+                | IORING_OP_WRITE
+        );
+
+        idempotent
+            && sqe.flags & IOSQE_IO_LINK == 0
+            && self.in_flight.bump_attempts(ticket)
+                < self.config.eintr_retries
+    }
 }
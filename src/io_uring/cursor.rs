@@ -0,0 +1,89 @@
+use super::*;
+
+/// A `File` paired with an offset that advances
+/// by the number of bytes transferred by each
+/// `read` or `write`, like the implicit cursor of
+/// a `std::fs::File`. Created by `Uring::cursor`.
+///
+/// The offset only advances when an operation's
+/// completion is waited on, or polled until it
+/// resolves. A completion that is dropped before
+/// then leaves the offset where it was, even if
+/// the operation went through.
+#[derive(Debug)]
+pub struct Cursor<'a> {
+    uring: &'a Uring,
+    file: &'a File,
+    offset: u64,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(
+        uring: &'a Uring,
+        file: &'a File,
+        offset: u64,
+    ) -> Cursor<'a> {
+        Cursor {
+            uring,
+            file,
+            offset,
+        }
+    }
+
+    /// The offset that the next operation will
+    /// be performed at.
+    pub const fn position(&self) -> u64 {
+        self.offset
+    }
+
+    /// Move the offset that the next operation
+    /// will be performed at.
+    pub fn set_position(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// Reads from the current offset into the
+    /// given buffer, advancing the offset by the
+    /// number of bytes read when the completion
+    /// is waited on.
+    pub fn read<'b, B>(
+        &'b mut self,
+        iov: &'b B,
+    ) -> OwnedCompletion<'b, usize>
+    where
+        B: AsIoVec + AsIoVecMut,
+    {
+        let completion =
+            self.uring.read_at(self.file, iov, self.offset);
+        advance_on(&mut self.offset, completion)
+    }
+
+    /// Writes the given buffer at the current
+    /// offset, advancing the offset by the number
+    /// of bytes written when the completion is
+    /// waited on.
+    pub fn write<'b, B>(
+        &'b mut self,
+        iov: &'b B,
+    ) -> OwnedCompletion<'b, usize>
+    where
+        B: ?Sized + AsIoVec,
+    {
+        let completion = self.uring.write_at(
+            self.file,
+            iov,
+            self.offset,
+        );
+        advance_on(&mut self.offset, completion)
+    }
+}
+
+fn advance_on<'b>(
+    offset: &'b mut u64,
+    completion: Completion<'b, usize>,
+) -> OwnedCompletion<'b, usize> {
+    OwnedCompletion::new(completion, move |len| {
+        *offset += u64::try_from(len).unwrap();
+        Ok(len)
+    })
+}
@@ -0,0 +1,110 @@
+use std::{
+    panic::{self, PanicHookInfo},
+    sync::{Once, Weak},
+};
+
+use super::*;
+
+/// What the panic hook needs to describe a
+/// ring, without keeping any of it alive.
+struct RegisteredRing {
+    ring_fd: i32,
+    ticket_queue: Weak<TicketQueue>,
+    metrics: Weak<Metrics>,
+}
+
+static RINGS: Mutex<Vec<RegisteredRing>> =
+    Mutex::new(vec![]);
+static INSTALL_HOOK: Once = Once::new();
+
+/// Includes a ring in what is printed when rio
+/// panics, installing the panic hook that does
+/// so the first time this is called.
+pub(crate) fn register(
+    ring_fd: i32,
+    ticket_queue: &Arc<TicketQueue>,
+    metrics: &Arc<Metrics>,
+) {
+    INSTALL_HOOK.call_once(install_hook);
+
+    let mut rings = RINGS.lock().unwrap();
+    rings.retain(|ring| {
+        ring.ticket_queue.strong_count() > 0
+    });
+    rings.push(RegisteredRing {
+        ring_fd,
+        ticket_queue: Arc::downgrade(ticket_queue),
+        metrics: Arc::downgrade(metrics),
+    });
+}
+
+fn install_hook() {
+    // keep whatever was there before, which
+    // prints the panic message itself
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if from_rio(info) {
+            print_rings();
+        }
+    }));
+}
+
+fn from_rio(info: &PanicHookInfo<'_>) -> bool {
+    // `file!()` names source files the same way
+    // that panic locations do, so the part before
+    // this file's path within the crate is where
+    // all of rio's sources are.
+    let src =
+        file!().strip_suffix("io_uring/diagnostics.rs");
+    match (src, info.location()) {
+        (Some(prefix), Some(location)) => {
+            location.file().starts_with(prefix)
+        }
+        _ => false,
+    }
+}
+
+fn print_rings() {
+    // the panic may have happened while holding
+    // any of these locks, in which case they'd
+    // deadlock if we waited for them here.
+    if let Ok(rings) = RINGS.try_lock() {
+        rings.iter().for_each(print_ring);
+    } else {
+        eprintln!("rio: ring state unavailable");
+    }
+}
+
+fn print_ring(ring: &RegisteredRing) {
+    let (ticket_queue, metrics) = match (
+        ring.ticket_queue.upgrade(),
+        ring.metrics.upgrade(),
+    ) {
+        (Some(ticket_queue), Some(metrics)) => {
+            (ticket_queue, metrics)
+        }
+        _ => return,
+    };
+
+    let tickets = ticket_queue.try_in_flight().map_or(
+        "tickets unavailable".to_string(),
+        |in_flight| {
+            format!(
+                "{} ops in-flight, {} of {} tickets free",
+                in_flight,
+                ticket_queue
+                    .size()
+                    .saturating_sub(in_flight),
+                ticket_queue.size(),
+            )
+        },
+    );
+
+    eprintln!(
+        "rio ring fd {}: {}, {:?}",
+        ring.ring_fd,
+        tickets,
+        metrics.snapshot(),
+    );
+}
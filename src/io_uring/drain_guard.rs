@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// More `Drain` operations than this in a single
+/// second will trigger a warning when
+/// `Config::warn_on_drain` is set.
+pub(crate) const DRAIN_WARN_THRESHOLD: u64 = 1000;
+
+/// Counts how many `Drain` operations are
+/// submitted per second, and warns once a second
+/// for as long as that exceeds
+/// `DRAIN_WARN_THRESHOLD`.
+#[derive(Debug)]
+pub(crate) struct DrainGuard {
+    window: Mutex<(Instant, u64)>,
+    warnings: AtomicU64,
+}
+
+impl DrainGuard {
+    pub(crate) fn new() -> DrainGuard {
+        DrainGuard {
+            window: Mutex::new((Instant::now(), 0)),
+            warnings: 0.into(),
+        }
+    }
+
+    /// Records the submission of a `Drain`
+    /// operation.
+    pub(crate) fn note(&self) {
+        let mut window = self.window.lock().unwrap();
+        let (start, count) = &mut *window;
+
+        if start.elapsed() >= Duration::from_secs(1) {
+            *start = Instant::now();
+            *count = 0;
+        }
+
+        *count += 1;
+
+        if *count == DRAIN_WARN_THRESHOLD + 1 {
+            self.warnings.fetch_add(1, Relaxed);
+            eprintln!(
+                "rio: more than {} operations with \
+                 Ordering::Drain were submitted in \
+                 the last second. Each one waits for \
+                 everything submitted before it, \
+                 serializing the whole ring, which is \
+                 almost always a mistake outside of \
+                 explicit barriers.",
+                DRAIN_WARN_THRESHOLD,
+            );
+        }
+    }
+
+    pub(crate) fn warnings(&self) -> u64 {
+        self.warnings.load(Relaxed)
+    }
+}
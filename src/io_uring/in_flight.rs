@@ -2,10 +2,64 @@ use std::ptr::null_mut;
 
 use super::*;
 
+/// Socket address storage that a `msghdr`'s
+/// `msg_name` should point to. This is owned
+/// by the operation's `OwnedCompletion`, and
+/// must outlive the operation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MsgName {
+    pub(crate) ptr: *mut libc::sockaddr_storage,
+    pub(crate) len: libc::socklen_t,
+}
+
+/// Ancillary data that a `msghdr`'s
+/// `msg_control` should point to, like the
+/// `SCM_RIGHTS` of `Uring::send_fds`. Like a
+/// `MsgName`, it must outlive the operation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MsgControl {
+    pub(crate) ptr: *mut libc::c_void,
+    pub(crate) len: usize,
+}
+
+/// The state of a `Uring::recv_framed`, which
+/// is owned by its `OwnedCompletion` and
+/// advanced by the reaper as each of the
+/// receives that make up the frame completes.
+#[derive(Debug)]
+pub(crate) struct Frame {
+    /// The big-endian length prefix, of which
+    /// the first `prefix_len` bytes are used.
+    pub(crate) prefix: [u8; 8],
+    pub(crate) prefix_len: usize,
+    pub(crate) max_len: usize,
+    /// Bytes received so far into `body`, or into
+    /// `prefix` while `body` is still `None`.
+    pub(crate) received: usize,
+    pub(crate) body: Option<Vec<u8>>,
+}
+
+/// The bits of an SQE's `user_data` that hold
+/// its ticket. The bits above hold a generation
+/// that is bumped every time the ticket is
+/// reused, so that an operation can be canceled
+/// by its `user_data` without any risk of
+/// hitting a later operation on the same ticket.
+pub(crate) const TICKET_MASK: u64 = 0xFFFF_FFFF;
+
 pub(crate) struct InFlight {
     iovecs: UnsafeCell<Vec<libc::iovec>>,
     msghdrs: UnsafeCell<Vec<libc::msghdr>>,
     fillers: UnsafeCell<Vec<Option<Filler>>>,
+    multi_fillers: UnsafeCell<Vec<Option<MultiFiller>>>,
+    sqes: UnsafeCell<Vec<io_uring_sqe>>,
+    attempts: UnsafeCell<Vec<u32>>,
+    send_alls: UnsafeCell<Vec<bool>>,
+    nowait_fallbacks: UnsafeCell<Vec<bool>>,
+    generations: UnsafeCell<Vec<u32>>,
+    tags: UnsafeCell<Vec<Option<u64>>>,
+    delays: UnsafeCell<Vec<__kernel_timespec>>,
+    frames: UnsafeCell<Vec<*mut Frame>>,
 }
 
 impl std::fmt::Debug for InFlight {
@@ -37,10 +91,37 @@ impl InFlight {
             filler_vec.push(None);
         }
         let fillers = UnsafeCell::new(filler_vec);
+        let multi_fillers =
+            UnsafeCell::new((0..size).map(|_| None).collect());
+        let sqes = UnsafeCell::new(vec![
+            io_uring_sqe::default();
+            size
+        ]);
+        let attempts = UnsafeCell::new(vec![0; size]);
+        let send_alls = UnsafeCell::new(vec![false; size]);
+        let nowait_fallbacks =
+            UnsafeCell::new(vec![false; size]);
+        let generations = UnsafeCell::new(vec![0; size]);
+        let tags = UnsafeCell::new(vec![None; size]);
+        let delays = UnsafeCell::new(vec![
+            __kernel_timespec::default();
+            size
+        ]);
+        let frames =
+            UnsafeCell::new(vec![null_mut(); size]);
         InFlight {
             iovecs,
             msghdrs,
             fillers,
+            multi_fillers,
+            sqes,
+            attempts,
+            send_alls,
+            nowait_fallbacks,
+            generations,
+            tags,
+            delays,
+            frames,
         }
     }
 
@@ -49,6 +130,7 @@ impl InFlight {
         ticket: usize,
         iovec: Option<libc::iovec>,
         msghdr: bool,
+        msg_name: Option<MsgName>,
         filler: Filler,
     ) -> u64 {
         #[allow(unsafe_code)]
@@ -64,9 +146,34 @@ impl InFlight {
                             .as_mut_ptr()
                             .add(ticket);
                     (*msghdr_ptr)[ticket].msg_iovlen = 1;
+
+                    // always reset the name, so that a
+                    // previous op's address is never reused
+                    let (name, namelen) = msg_name
+                        .map_or((null_mut(), 0), |n| {
+                            (n.ptr.cast(), n.len)
+                        });
+                    (&mut *msghdr_ptr)[ticket].msg_name =
+                        name;
+                    (&mut *msghdr_ptr)[ticket].msg_namelen =
+                        namelen;
+
+                    // same for ancillary data, which
+                    // is only set by `set_msg_control`
+                    (&mut *msghdr_ptr)[ticket].msg_control =
+                        null_mut();
+                    (&mut *msghdr_ptr)[ticket].msg_controllen =
+                        0;
                 }
             }
             (*self.fillers.get())[ticket] = Some(filler);
+            (&mut *self.multi_fillers.get())[ticket] = None;
+            (&mut *self.attempts.get())[ticket] = 0;
+            (&mut *self.send_alls.get())[ticket] = false;
+            (&mut *self.nowait_fallbacks.get())[ticket] =
+                false;
+            (&mut *self.tags.get())[ticket] = None;
+            (&mut *self.frames.get())[ticket] = null_mut();
             if iovec.is_some() {
                 if msghdr {
                     (*msghdr_ptr).as_mut_ptr().add(ticket)
@@ -81,6 +188,94 @@ impl InFlight {
         }
     }
 
+    /// Writes to every slot of a ticket that is
+    /// not in use, so that its memory is faulted
+    /// in now rather than by its first operation.
+    pub(crate) fn prewarm(&self, ticket: usize) {
+        #[allow(unsafe_code)]
+        unsafe {
+            std::ptr::write_volatile(
+                (*self.iovecs.get())
+                    .as_mut_ptr()
+                    .add(ticket),
+                libc::iovec {
+                    iov_base: null_mut(),
+                    iov_len: 0,
+                },
+            );
+            std::ptr::write_volatile(
+                (*self.msghdrs.get())
+                    .as_mut_ptr()
+                    .add(ticket),
+                MaybeUninit::<libc::msghdr>::zeroed()
+                    .assume_init(),
+            );
+            std::ptr::write_volatile(
+                (*self.sqes.get()).as_mut_ptr().add(ticket),
+                io_uring_sqe::default(),
+            );
+            std::ptr::write_volatile(
+                (*self.attempts.get())
+                    .as_mut_ptr()
+                    .add(ticket),
+                0,
+            );
+            (&mut *self.fillers.get())[ticket] = None;
+            (&mut *self.multi_fillers.get())[ticket] = None;
+        }
+    }
+
+    /// Returns the `user_data` for the next
+    /// operation that is submitted with this
+    /// ticket.
+    pub(crate) fn next_user_data(
+        &self,
+        ticket: usize,
+    ) -> u64 {
+        #[allow(unsafe_code)]
+        unsafe {
+            let generation =
+                &mut (&mut *self.generations.get())[ticket];
+            // keep the top bit clear, since that is
+            // how the reaper detects the poison pill
+            *generation =
+                generation.wrapping_add(1) & (u32::MAX >> 1);
+            (u64::from(*generation) << 32)
+                | u64::try_from(ticket).unwrap()
+        }
+    }
+
+    /// Like `insert`, but for a multishot operation
+    /// that may be filled many times.
+    pub(crate) fn insert_multi(
+        &self,
+        ticket: usize,
+        filler: MultiFiller,
+    ) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.fillers.get())[ticket] = None;
+            (&mut *self.multi_fillers.get())[ticket] =
+                Some(filler);
+            (&mut *self.attempts.get())[ticket] = 0;
+            (&mut *self.send_alls.get())[ticket] = false;
+            (&mut *self.tags.get())[ticket] = None;
+            (&mut *self.frames.get())[ticket] = null_mut();
+            (&mut *self.nowait_fallbacks.get())[ticket] =
+                false;
+        }
+    }
+
+    pub(crate) fn multi_filler(
+        &self,
+        ticket: usize,
+    ) -> Option<&MultiFiller> {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.multi_fillers.get())[ticket].as_ref()
+        }
+    }
+
     pub(crate) fn take_filler(
         &self,
         ticket: usize,
@@ -90,4 +285,189 @@ impl InFlight {
             (*self.fillers.get())[ticket].take().unwrap()
         }
     }
+
+    /// Fails the operation on `ticket` with `e`,
+    /// for when its SQE never made it into the
+    /// submission queue. Returns whether the
+    /// ticket may be handed back, which for a
+    /// multishot operation is left to its
+    /// `Multishot`.
+    pub(crate) fn fail(
+        &self,
+        ticket: usize,
+        e: io::Error,
+    ) -> bool {
+        if let Some(multi_filler) =
+            self.multi_filler(ticket)
+        {
+            multi_filler.fill(Err(e), false);
+            false
+        } else {
+            self.take_filler(ticket).fill(Err(e));
+            true
+        }
+    }
+
+    /// Stores a copy of the SQE that was submitted
+    /// for this ticket, so that it may be
+    /// resubmitted if it needs to be retried.
+    pub(crate) fn set_sqe(
+        &self,
+        ticket: usize,
+        sqe: io_uring_sqe,
+    ) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.sqes.get())[ticket] = sqe;
+        }
+    }
+
+    pub(crate) fn sqe(
+        &self,
+        ticket: usize,
+    ) -> io_uring_sqe {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.sqes.get())[ticket]
+        }
+    }
+
+    /// Points this ticket's `msghdr` at ancillary
+    /// data, after `insert` has set up the rest.
+    pub(crate) fn set_msg_control(
+        &self,
+        ticket: usize,
+        control: MsgControl,
+    ) {
+        #[allow(unsafe_code)]
+        unsafe {
+            let msghdr =
+                &mut (&mut *self.msghdrs.get())[ticket];
+            msghdr.msg_control = control.ptr;
+            msghdr.msg_controllen = control.len;
+        }
+    }
+
+    /// Marks this ticket's operation as a send
+    /// that should be resubmitted until its whole
+    /// buffer has been sent.
+    pub(crate) fn set_send_all(&self, ticket: usize) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.send_alls.get())[ticket] = true;
+        }
+    }
+
+    pub(crate) fn is_send_all(
+        &self,
+        ticket: usize,
+    ) -> bool {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.send_alls.get())[ticket]
+        }
+    }
+
+    /// Marks this ticket's operation as a
+    /// `write_at_nowait`, which should be
+    /// resubmitted without `RWF_NOWAIT` if it
+    /// fails with `EAGAIN`.
+    pub(crate) fn set_nowait_fallback(
+        &self,
+        ticket: usize,
+    ) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.nowait_fallbacks.get())[ticket] =
+                true;
+        }
+    }
+
+    pub(crate) fn is_nowait_fallback(
+        &self,
+        ticket: usize,
+    ) -> bool {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.nowait_fallbacks.get())[ticket]
+        }
+    }
+
+    /// Marks this ticket's operation as a receive
+    /// of the prefix of `frame`, which must stay
+    /// put until the operation completes.
+    pub(crate) fn set_frame(
+        &self,
+        ticket: usize,
+        frame: *mut Frame,
+    ) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.frames.get())[ticket] = frame;
+        }
+    }
+
+    /// The frame set by `set_frame`, or null.
+    pub(crate) fn frame(
+        &self,
+        ticket: usize,
+    ) -> *mut Frame {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.frames.get())[ticket]
+        }
+    }
+
+    /// Sets the caller's tag for a raw operation,
+    /// which replaces the `user_data` of the CQE
+    /// that it resolves to.
+    pub(crate) fn set_tag(&self, ticket: usize, tag: u64) {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&mut *self.tags.get())[ticket] = Some(tag);
+        }
+    }
+
+    pub(crate) fn tag(&self, ticket: usize) -> Option<u64> {
+        #[allow(unsafe_code)]
+        unsafe {
+            (&*self.tags.get())[ticket]
+        }
+    }
+
+    /// Sets how long to wait before this ticket's
+    /// operation is retried, returning a pointer
+    /// for the timeout that does the waiting,
+    /// which stays valid until the ticket is
+    /// reused.
+    pub(crate) fn set_delay(
+        &self,
+        ticket: usize,
+        delay: Duration,
+    ) -> *const __kernel_timespec {
+        #[allow(unsafe_code)]
+        unsafe {
+            let slot =
+                &mut (&mut *self.delays.get())[ticket];
+            *slot = __kernel_timespec::from(delay);
+            slot
+        }
+    }
+
+    /// Bumps the number of times this ticket's
+    /// operation has been retried, returning the
+    /// previous count.
+    pub(crate) fn bump_attempts(
+        &self,
+        ticket: usize,
+    ) -> u32 {
+        #[allow(unsafe_code)]
+        unsafe {
+            let attempts =
+                &mut (&mut *self.attempts.get())[ticket];
+            let old = *attempts;
+            *attempts += 1;
+            old
+        }
+    }
 }
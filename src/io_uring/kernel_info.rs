@@ -0,0 +1,246 @@
+use std::{ffi::CStr, fmt};
+
+use super::*;
+
+const FEATURE_NAMES: [(u32, &str); 13] = [
+    (IORING_FEAT_SINGLE_MMAP, "SINGLE_MMAP"),
+    (IORING_FEAT_NODROP, "NODROP"),
+    (IORING_FEAT_SUBMIT_STABLE, "SUBMIT_STABLE"),
+    (IORING_FEAT_RW_CUR_POS, "RW_CUR_POS"),
+    (IORING_FEAT_CUR_PERSONALITY, "CUR_PERSONALITY"),
+    (IORING_FEAT_FAST_POLL, "FAST_POLL"),
+    (IORING_FEAT_POLL_32BITS, "POLL_32BITS"),
+    (IORING_FEAT_SQPOLL_NONFIXED, "SQPOLL_NONFIXED"),
+    (IORING_FEAT_EXT_ARG, "EXT_ARG"),
+    (IORING_FEAT_NATIVE_WORKERS, "NATIVE_WORKERS"),
+    (IORING_FEAT_RSRC_TAGS, "RSRC_TAGS"),
+    (IORING_FEAT_CQE_SKIP, "CQE_SKIP"),
+    (IORING_FEAT_LINKED_FILE, "LINKED_FILE"),
+];
+
+/// The oldest linux release that has each of the
+/// `io_uring_register` opcodes that rio uses.
+const REGISTER_SINCE: [(u32, &str, (u32, u32)); 8] = [
+    (
+        IORING_REGISTER_BUFFERS,
+        "IORING_REGISTER_BUFFERS",
+        (5, 1),
+    ),
+    (
+        IORING_UNREGISTER_BUFFERS,
+        "IORING_UNREGISTER_BUFFERS",
+        (5, 1),
+    ),
+    (
+        IORING_REGISTER_FILES,
+        "IORING_REGISTER_FILES",
+        (5, 1),
+    ),
+    (
+        IORING_UNREGISTER_FILES,
+        "IORING_UNREGISTER_FILES",
+        (5, 1),
+    ),
+    (
+        IORING_REGISTER_FILES_UPDATE,
+        "IORING_REGISTER_FILES_UPDATE",
+        (5, 5),
+    ),
+    (
+        IORING_REGISTER_PERSONALITY,
+        "IORING_REGISTER_PERSONALITY",
+        (5, 6),
+    ),
+    (
+        IORING_UNREGISTER_PERSONALITY,
+        "IORING_UNREGISTER_PERSONALITY",
+        (5, 6),
+    ),
+    (
+        IORING_REGISTER_IOWQ_MAX_WORKERS,
+        "IORING_REGISTER_IOWQ_MAX_WORKERS",
+        (5, 15),
+    ),
+];
+
+/// Turns the error that `io_uring_register`
+/// returned for `opcode` into one of kind
+/// `io::ErrorKind::Unsupported` if it's because
+/// the kernel is too old to know about it,
+/// saying which version is needed. The kernel
+/// uses the same `EINVAL` for that as it does
+/// for bad arguments, which are left as-is.
+pub(crate) fn unsupported_register(
+    opcode: u32,
+    err: io::Error,
+) -> io::Error {
+    match err.raw_os_error() {
+        Some(libc::ENOSYS) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            "io_uring_register is not supported by \
+             this kernel",
+        ),
+        Some(libc::EINVAL) => {
+            let since = REGISTER_SINCE
+                .iter()
+                .find(|(op, _, _)| *op == opcode);
+            let running =
+                uname_release().ok().and_then(|release| {
+                    parse_release(&release)
+                });
+
+            match (since, running) {
+                (
+                    Some((_, name, (major, minor))),
+                    Some((running_major, running_minor, _)),
+                ) if (running_major, running_minor)
+                    < (*major, *minor) =>
+                {
+                    io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!(
+                            "{} needs linux {}.{} or \
+                             newer, but this is {}.{}",
+                            name,
+                            major,
+                            minor,
+                            running_major,
+                            running_minor
+                        ),
+                    )
+                }
+                _ => err,
+            }
+        }
+        _ => err,
+    }
+}
+
+/// The running kernel's version and the
+/// `io_uring` features that it supports,
+/// returned by `rio::kernel_info`.
+///
+/// Its `Display` implementation is meant for
+/// logging, like
+/// `kernel 5.15.0, SINGLE_MMAP+NODROP+FAST_POLL`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelInfo {
+    /// The major version, like the 5 in 5.15.0.
+    pub major: u32,
+    /// The minor version, like the 15 in 5.15.0.
+    pub minor: u32,
+    /// The patch version, like the 0 in 5.15.0.
+    pub patch: u32,
+    /// The full release string from `uname`,
+    /// which may have a distribution-specific
+    /// suffix after the version.
+    pub release: String,
+    /// The `IORING_FEAT_*` bitmask that the kernel
+    /// reports when a ring is set up.
+    pub features: u32,
+}
+
+impl KernelInfo {
+    /// Whether the kernel is at least the given
+    /// version.
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+
+    /// The names of the features in `features`
+    /// that rio knows about, without the
+    /// `IORING_FEAT_` prefix.
+    pub fn feature_names(&self) -> Vec<&'static str> {
+        FEATURE_NAMES
+            .iter()
+            .filter(|(bit, _)| self.features & bit != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl fmt::Display for KernelInfo {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "kernel {}.{}.{}, {}",
+            self.major,
+            self.minor,
+            self.patch,
+            self.feature_names().join("+")
+        )
+    }
+}
+
+/// Report the running kernel's version, parsed
+/// from `uname`, along with the `io_uring`
+/// features it supports, which are determined
+/// by setting up a throwaway ring.
+pub fn kernel_info() -> io::Result<KernelInfo> {
+    let release = uname_release()?;
+    let (major, minor, patch) = parse_release(&release)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "failed to parse kernel release {:?}",
+                    release
+                ),
+            )
+        })?;
+
+    let mut params = io_uring_params::default();
+    let ring_fd = setup(1, &mut params)?;
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::close(ring_fd);
+    }
+
+    Ok(KernelInfo {
+        major,
+        minor,
+        patch,
+        release,
+        features: params.features,
+    })
+}
+
+fn uname_release() -> io::Result<String> {
+    #[allow(unsafe_code)]
+    let utsname = unsafe {
+        let mut utsname =
+            MaybeUninit::<libc::utsname>::zeroed();
+        if libc::uname(utsname.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        utsname.assume_init()
+    };
+
+    #[allow(unsafe_code)]
+    let release =
+        unsafe { CStr::from_ptr(utsname.release.as_ptr()) };
+
+    Ok(release.to_string_lossy().into_owned())
+}
+
+/// Parses the leading `major.minor[.patch]` of
+/// a release like `5.15.0-91-generic`.
+fn parse_release(release: &str) -> Option<(u32, u32, u32)> {
+    let mut numbers = release.split('.').map(|part| {
+        let digits: String = part
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse::<u32>()
+    });
+
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next()?.ok()?;
+    let patch =
+        numbers.next().and_then(Result::ok).unwrap_or(0);
+
+    Some((major, minor, patch))
+}
@@ -7,11 +7,17 @@ use super::*;
 ///         `include/uapi/linux/io_uring.h`
 use std::fmt;
 
+/// A completion queue entry, as posted by
+/// the kernel.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct io_uring_cqe {
+    /// The `user_data` of the SQE that this
+    /// completes, which is managed by rio.
     pub user_data: u64,
+    /// The result of the operation.
     pub res: i32,
+    /// Opcode-specific `IORING_CQE_F_*` flags.
     pub flags: u32,
 }
 
@@ -23,25 +29,69 @@ pub struct io_uring_params {
     pub flags: u32,
     pub sq_thread_cpu: u32,
     pub sq_thread_idle: u32,
-    pub resv: [u32; 5_usize],
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3_usize],
     pub sq_off: io_sqring_offsets,
     pub cq_off: io_cqring_offsets,
 }
 
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct __kernel_timespec {
+    pub tv_sec: i64,
+    pub tv_nsec: i64,
+}
+
+impl From<Duration> for __kernel_timespec {
+    fn from(dur: Duration) -> __kernel_timespec {
+        __kernel_timespec {
+            tv_sec: i64::try_from(dur.as_secs())
+                .unwrap_or(i64::MAX),
+            tv_nsec: i64::from(dur.subsec_nanos()),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone)]
+pub struct io_uring_files_update {
+    pub offset: u32,
+    pub resv: u32,
+    pub fds: u64,
+}
+
 pub type __kernel_rwf_t = ::std::os::raw::c_int;
 
+/// A submission queue entry, which describes
+/// an operation for the kernel to perform.
+/// The fields mirror `struct io_uring_sqe` in
+/// `include/uapi/linux/io_uring.h`.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default)]
 pub struct io_uring_sqe {
+    /// The `IORING_OP_*` to perform.
     pub opcode: u8,
+    /// `IOSQE_*` flags.
     pub flags: u8,
+    /// The IO priority, or opcode-specific flags.
     pub ioprio: u16,
+    /// The file descriptor to operate on.
     pub fd: i32,
+    /// The offset into the file, or `addr2`.
     pub off: u64,
+    /// A pointer to a buffer or iovecs.
     pub addr: u64,
+    /// The length of the buffer, or the number
+    /// of iovecs.
     pub len: u32,
+    /// Opcode-specific flags.
     pub __bindgen_anon_1: io_uring_sqe__bindgen_ty_1,
+    /// Passed back in the completion. This is
+    /// managed by rio, and must not be changed.
     pub user_data: u64,
+    /// The buffer index or group, personality
+    /// and padding.
     pub __bindgen_anon_2: io_uring_sqe__bindgen_ty_2,
 }
 
@@ -70,6 +120,31 @@ impl io_uring_sqe {
         self.apply_order(ordering);
     }
 
+    /// Splices `len` bytes from `fd_in` at `off_in`
+    /// to `fd_out`, at its current position. Pipes
+    /// don't have offsets, so `off_in` must be
+    /// `u64::MAX` when `fd_in` is one.
+    pub(crate) fn prep_splice(
+        &mut self,
+        fd_in: i32,
+        off_in: u64,
+        fd_out: i32,
+        len: usize,
+    ) {
+        self.prep_rw(
+            IORING_OP_SPLICE,
+            fd_out,
+            len,
+            u64::MAX,
+            Ordering::None,
+        );
+        // `addr` doubles as `splice_off_in`
+        self.addr = off_in;
+        self.__bindgen_anon_2
+            .__bindgen_anon_1
+            .splice_fd_in = fd_in;
+    }
+
     fn apply_order(&mut self, ordering: Ordering) {
         match ordering {
             Ordering::None => {}
@@ -91,6 +166,10 @@ pub union io_uring_sqe__bindgen_ty_1 {
     pub poll_events: u16,
     pub sync_range_flags: u32,
     pub msg_flags: u32,
+    pub fadvise_advice: u32,
+    pub cancel_flags: u32,
+    pub msg_ring_flags: u32,
+    pub timeout_flags: u32,
     _bindgen_union_align: u32,
 }
 
@@ -116,10 +195,20 @@ impl fmt::Debug for io_uring_sqe__bindgen_ty_1 {
 #[derive(Copy, Clone)]
 pub union io_uring_sqe__bindgen_ty_2 {
     pub buf_index: u16,
+    pub buf_group: u16,
+    pub __bindgen_anon_1: io_uring_sqe__bindgen_ty_2__bindgen_ty_1,
     pub __pad2: [u64; 3_usize],
     _bindgen_union_align: [u64; 3_usize],
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct io_uring_sqe__bindgen_ty_2__bindgen_ty_1 {
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+}
+
 impl fmt::Debug for io_uring_sqe__bindgen_ty_2 {
     fn fmt(
         &self,
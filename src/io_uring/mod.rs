@@ -1,53 +1,164 @@
 use std::{
-    cell::UnsafeCell,
+    cell::{Cell, UnsafeCell},
+    collections::{HashMap, VecDeque},
     convert::TryFrom,
     fs::File,
     io,
-    net::{TcpListener, TcpStream},
+    net::{
+        Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4,
+        SocketAddrV6, TcpListener, TcpStream,
+    },
     ops::Neg,
-    os::unix::io::{AsRawFd, FromRawFd},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
     sync::{
         atomic::{
-            AtomicU32, AtomicU64,
-            Ordering::{Acquire, Relaxed, Release},
+            AtomicBool, AtomicU32, AtomicU64,
+            Ordering::{
+                AcqRel, Acquire, Relaxed, Release, SeqCst,
+            },
         },
         Arc, Condvar, Mutex,
     },
+    time::{Duration, Instant},
     mem::MaybeUninit
 };
 
 use super::{
     pair, AsIoVec, AsIoVecMut, Completion, Filler, FromCqe,
-    Measure, M,
+    KeepAlive, Metrics, MetricsSnapshot, OwnedCompletion,
+    Wakeup,
 };
 
+#[cfg(feature = "testing")]
+use super::Injector;
+
+#[cfg(feature = "testing")]
+pub use uring::SqFault;
+
+#[cfg(feature = "bytes")]
+use bytes_pool::BytesPool;
+
+#[cfg(feature = "bytes")]
+mod bytes_pool;
 mod config;
 mod constants;
 mod cq;
+mod cursor;
+mod diagnostics;
+mod drain_guard;
 mod in_flight;
+mod kernel_info;
 mod kernel_types;
+mod multishot;
+mod pool;
+mod prepped;
+mod registration;
+mod ring_buf;
 mod sq;
+mod stream;
+mod submitter;
 mod syscall;
 mod ticket_queue;
 mod uring;
 
 pub(crate) use {
     constants::*,
-    cq::Cq,
-    in_flight::InFlight,
+    cq::{Cq, CqPeek, RING_MESSAGE},
+    drain_guard::DrainGuard,
+    in_flight::{
+        Frame, InFlight, MsgControl, MsgName, TICKET_MASK,
+    },
+    kernel_info::unsupported_register,
     kernel_types::{
-        io_uring_cqe, io_uring_params, io_uring_sqe,
+        __kernel_timespec, io_uring_files_update,
+        io_uring_params,
     },
+    multishot::{MultiFiller, Multishot},
+    pool::BufferGroup,
     sq::Sq,
-    syscall::{enter, setup},
+    uring::{checked_end, RingShared},
+    submitter::Submitter,
+    syscall::{enter, register, setup},
     ticket_queue::TicketQueue,
 };
 
 pub use {
-    config::Config,
-    uring::{Rio, Uring},
+    config::{Config, TransientRetry},
+    cursor::Cursor,
+    kernel_info::{kernel_info, KernelInfo},
+    kernel_types::{io_uring_cqe, io_uring_sqe},
+    multishot::{PollMultishot, RecvMultishot},
+    pool::PoolBuf,
+    prepped::PreppedOp,
+    registration::{
+        BuffersRegistration, FilesRegistration, RegisteredFd,
+    },
+    ring_buf::RingBuf,
+    stream::RioStream,
+    uring::{
+        BenchResult, CacheHint, Expired, FilledBuf,
+        ResolvedAddr, RingState, Rio, SocketAddrStorage,
+        SubmitStats, TimeoutToken, Uring, VectoredStop,
+    },
 };
 
+/// The `io_uring` opcodes and flags that can be
+/// used to fill in or inspect an `io_uring_sqe`
+/// or `io_uring_cqe`, named as they are in the
+/// kernel's `io_uring.h`. Constants that only
+/// matter to rio's own management of the ring,
+/// like mmap offsets and `io_uring_enter` flags,
+/// are left out.
+///
+/// # Examples
+///
+/// ```
+/// use rio::consts::{IORING_OP_NOP, IOSQE_IO_LINK};
+///
+/// let mut sqe = rio::io_uring_sqe::default();
+/// sqe.opcode = IORING_OP_NOP;
+/// sqe.flags |= IOSQE_IO_LINK;
+/// assert_eq!(sqe.opcode, 0);
+/// ```
+pub mod consts {
+    pub use super::constants::{
+        IORING_OP_NOP, IORING_OP_READV, IORING_OP_WRITEV,
+        IORING_OP_FSYNC, IORING_OP_READ_FIXED,
+        IORING_OP_WRITE_FIXED, IORING_OP_POLL_ADD,
+        IORING_OP_POLL_REMOVE, IORING_OP_SYNC_FILE_RANGE,
+        IORING_OP_SENDMSG, IORING_OP_RECVMSG,
+        IORING_OP_TIMEOUT, IORING_OP_TIMEOUT_REMOVE,
+        IORING_OP_ACCEPT, IORING_OP_ASYNC_CANCEL,
+        IORING_OP_LINK_TIMEOUT, IORING_OP_CONNECT,
+        IORING_OP_FALLOCATE, IORING_OP_OPENAT,
+        IORING_OP_CLOSE, IORING_OP_FILES_UPDATE,
+        IORING_OP_STATX, IORING_OP_READ, IORING_OP_WRITE,
+        IORING_OP_FADVISE, IORING_OP_MADVISE,
+        IORING_OP_SEND, IORING_OP_RECV, IORING_OP_OPENAT2,
+        IORING_OP_EPOLL_CTL, IORING_OP_SPLICE,
+        IORING_OP_PROVIDE_BUFFERS, IORING_OP_REMOVE_BUFFERS,
+        IORING_OP_SHUTDOWN, IORING_OP_MSG_RING,
+        IOSQE_FIXED_FILE, IOSQE_IO_DRAIN, IOSQE_IO_LINK,
+        IOSQE_IO_HARDLINK, IOSQE_ASYNC, IOSQE_BUFFER_SELECT,
+        IORING_SETUP_IOPOLL, IORING_SETUP_SQPOLL,
+        IORING_SETUP_SQ_AFF, IORING_SETUP_CQSIZE,
+        IORING_SETUP_CLAMP, IORING_FSYNC_DATASYNC,
+        IORING_TIMEOUT_ABS, IORING_ASYNC_CANCEL_ALL,
+        IORING_ASYNC_CANCEL_FD, IORING_ASYNC_CANCEL_ANY,
+        IORING_NOP_INJECT_RESULT, IORING_RECV_MULTISHOT,
+        IORING_POLL_ADD_MULTI, IORING_TIMEOUT_UPDATE,
+        IORING_CQE_F_BUFFER, IORING_CQE_F_MORE,
+        IORING_CQE_F_SOCK_NONEMPTY, IORING_CQE_BUFFER_SHIFT,
+        IORING_FEAT_SINGLE_MMAP, IORING_FEAT_NODROP,
+        IORING_FEAT_SUBMIT_STABLE, IORING_FEAT_RW_CUR_POS,
+        IORING_FEAT_CUR_PERSONALITY, IORING_FEAT_FAST_POLL,
+        IORING_FEAT_POLL_32BITS,
+        IORING_FEAT_SQPOLL_NONFIXED, IORING_FEAT_EXT_ARG,
+        IORING_FEAT_NATIVE_WORKERS, IORING_FEAT_RSRC_TAGS,
+        IORING_FEAT_CQE_SKIP, IORING_FEAT_LINKED_FILE,
+    };
+}
+
 /// Specify whether `io_uring` should
 /// run operations in a specific order.
 /// By default, it will run independent
@@ -74,6 +185,289 @@ pub enum Ordering {
     Drain,
 }
 
+/// A message posted to this ring by
+/// `Uring::msg_ring`, from another ring or from
+/// this one, and received with `Uring::recv_msg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingMessage {
+    /// The `data` that it was sent with.
+    pub data: u64,
+    /// The `len` that it was sent with.
+    pub len: u32,
+}
+
+/// Which phases of `sync_file_range(2)` a call
+/// to `Uring::sync_file_range_with_flags` should
+/// go through, for a range of a file. See the
+/// man page for what each of them does.
+///
+/// Starts out with none of them, and each is
+/// added by its setter:
+///
+/// ```
+/// let full_sync = rio::SyncFileRangeFlags::new()
+///     .wait_before()
+///     .write()
+///     .wait_after();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SyncFileRangeFlags(u32);
+
+impl SyncFileRangeFlags {
+    /// No phases at all, which does nothing.
+    pub const fn new() -> SyncFileRangeFlags {
+        SyncFileRangeFlags(0)
+    }
+
+    /// `SYNC_FILE_RANGE_WRITE |
+    /// SYNC_FILE_RANGE_WAIT_AFTER`, which is what
+    /// `Uring::sync_file_range` uses: start
+    /// writeback of any dirty pages in the range,
+    /// and wait for it to finish.
+    pub const fn pessimistic() -> SyncFileRangeFlags {
+        SyncFileRangeFlags::new().write().wait_after()
+    }
+
+    /// Adds `SYNC_FILE_RANGE_WAIT_BEFORE`, to first
+    /// wait for any writeback that is already in
+    /// progress on the range.
+    ///
+    /// Earlier versions of rio left this out of
+    /// `sync_file_range`, because it caused `EBADF`.
+    /// That was because the flags were put in the
+    /// SQE's own flags, where this one happens to
+    /// mean `IOSQE_FIXED_FILE`, rather than where
+    /// the kernel looks for them. They are passed
+    /// correctly now, so this works on every
+    /// kernel that supports `sync_file_range`
+    /// through `io_uring`, which is linux 5.2 and
+    /// up. Older ones fail it with `EINVAL`, with
+    /// or without this flag.
+    pub const fn wait_before(self) -> SyncFileRangeFlags {
+        SyncFileRangeFlags(
+            self.0 | libc::SYNC_FILE_RANGE_WAIT_BEFORE,
+        )
+    }
+
+    /// Adds `SYNC_FILE_RANGE_WRITE`, to start
+    /// writeback of any dirty pages in the range.
+    pub const fn write(self) -> SyncFileRangeFlags {
+        SyncFileRangeFlags(
+            self.0 | libc::SYNC_FILE_RANGE_WRITE,
+        )
+    }
+
+    /// Adds `SYNC_FILE_RANGE_WAIT_AFTER`, to wait
+    /// for writeback of the range to finish.
+    pub const fn wait_after(self) -> SyncFileRangeFlags {
+        SyncFileRangeFlags(
+            self.0 | libc::SYNC_FILE_RANGE_WAIT_AFTER,
+        )
+    }
+
+    pub(crate) const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+/// The result of `Uring::recv_hinted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvResult {
+    /// The number of bytes that were received.
+    pub bytes: usize,
+    /// Whether the socket had more data
+    /// available to receive immediately after
+    /// this receive completed.
+    pub more_available: bool,
+}
+
+impl FromCqe for RecvResult {
+    fn from_cqe(cqe: io_uring_cqe) -> RecvResult {
+        RecvResult {
+            bytes: usize::try_from(cqe.res).unwrap(),
+            more_available: cqe.flags
+                & IORING_CQE_F_SOCK_NONEMPTY
+                != 0,
+        }
+    }
+}
+
+/// A set of `poll(2)` events, which is what
+/// `Uring::poll_add` waits for, and what it
+/// resolves to once some of them are ready.
+///
+/// Flags are combined with `|`:
+///
+/// ```
+/// use rio::PollFlags;
+///
+/// let either = PollFlags::IN | PollFlags::OUT;
+/// assert!(either.contains(PollFlags::IN));
+/// assert!(!either.contains(PollFlags::ERR));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PollFlags(u16);
+
+impl PollFlags {
+    /// There is data to read.
+    pub const IN: PollFlags =
+        PollFlags(libc::POLLIN as u16);
+    /// There is urgent data to read, like TCP
+    /// out-of-band data.
+    pub const PRI: PollFlags =
+        PollFlags(libc::POLLPRI as u16);
+    /// Writing is now possible.
+    pub const OUT: PollFlags =
+        PollFlags(libc::POLLOUT as u16);
+    /// An error condition. Always reported, even
+    /// when it isn't asked for.
+    pub const ERR: PollFlags =
+        PollFlags(libc::POLLERR as u16);
+    /// The other end hung up. Always reported,
+    /// even when it isn't asked for.
+    pub const HUP: PollFlags =
+        PollFlags(libc::POLLHUP as u16);
+    /// The peer of a stream socket shut down
+    /// its writing half.
+    pub const RDHUP: PollFlags =
+        PollFlags(libc::POLLRDHUP as u16);
+    /// The file descriptor is not open. Always
+    /// reported, even when it isn't asked for.
+    pub const NVAL: PollFlags =
+        PollFlags(libc::POLLNVAL as u16);
+
+    /// No events at all.
+    pub const fn empty() -> PollFlags {
+        PollFlags(0)
+    }
+
+    /// Whether there are no events in the set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of the events in `other` are
+    /// also in this set.
+    pub const fn contains(self, other: PollFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The raw `poll(2)` event bits.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Builds a set from raw `poll(2)` event bits,
+    /// which are kept as-is, even if they don't
+    /// have a constant here.
+    pub const fn from_bits(bits: u16) -> PollFlags {
+        PollFlags(bits)
+    }
+}
+
+impl std::ops::BitOr for PollFlags {
+    type Output = PollFlags;
+
+    fn bitor(self, other: PollFlags) -> PollFlags {
+        PollFlags(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for PollFlags {
+    fn bitor_assign(&mut self, other: PollFlags) {
+        self.0 |= other.0;
+    }
+}
+
+/// Per-operation flags for reads and writes,
+/// the same ones that `preadv2(2)` and
+/// `pwritev2(2)` take, for use with
+/// `Uring::read_at_with_flags` and
+/// `Uring::write_at_with_flags`.
+///
+/// Flags are combined with `|`:
+///
+/// ```
+/// use rio::RwFlags;
+///
+/// let flags = RwFlags::APPEND | RwFlags::DSYNC;
+/// assert!(flags.contains(RwFlags::APPEND));
+/// assert!(!flags.contains(RwFlags::SYNC));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RwFlags(i32);
+
+impl RwFlags {
+    /// Poll for completion of high priority IO,
+    /// which needs a file opened with `O_DIRECT`
+    /// on a device that supports polling.
+    pub const HIPRI: RwFlags = RwFlags(libc::RWF_HIPRI);
+    /// Make a write durable before completing it,
+    /// like `O_DSYNC`, but just for this write.
+    pub const DSYNC: RwFlags = RwFlags(libc::RWF_DSYNC);
+    /// Make a write and the file's metadata
+    /// durable before completing it, like
+    /// `O_SYNC`, but just for this write.
+    pub const SYNC: RwFlags = RwFlags(libc::RWF_SYNC);
+    /// Fail with `EAGAIN` instead of blocking,
+    /// like when the data isn't cached yet.
+    pub const NOWAIT: RwFlags = RwFlags(libc::RWF_NOWAIT);
+    /// Write to the end of the file, ignoring the
+    /// offset, like `O_APPEND`, but just for this
+    /// write.
+    pub const APPEND: RwFlags = RwFlags(libc::RWF_APPEND);
+
+    /// No flags at all.
+    pub const fn empty() -> RwFlags {
+        RwFlags(0)
+    }
+
+    /// Whether there are no flags in the set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of the flags in `other` are
+    /// also in this set.
+    pub const fn contains(self, other: RwFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The raw `RWF_*` bits.
+    pub const fn bits(self) -> i32 {
+        self.0
+    }
+
+    /// Builds a set from raw `RWF_*` bits, which
+    /// are kept as-is, even if they don't have a
+    /// constant here.
+    pub const fn from_bits(bits: i32) -> RwFlags {
+        RwFlags(bits)
+    }
+}
+
+impl std::ops::BitOr for RwFlags {
+    type Output = RwFlags;
+
+    fn bitor(self, other: RwFlags) -> RwFlags {
+        RwFlags(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for RwFlags {
+    fn bitor_assign(&mut self, other: RwFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl FromCqe for PollFlags {
+    fn from_cqe(cqe: io_uring_cqe) -> PollFlags {
+        // the kernel hands back the ready events
+        // as `res`, which only has poll bits set.
+        PollFlags(u16::try_from(cqe.res).unwrap())
+    }
+}
+
 fn uring_mmap(
     size: usize,
     ring_fd: i32,
@@ -94,12 +488,7 @@ fn uring_mmap(
     if ptr.is_null() || ptr == libc::MAP_FAILED {
         let mut err = io::Error::last_os_error();
         if let Some(12) = err.raw_os_error() {
-            err = io::Error::new(
-                io::ErrorKind::Other,
-                "Not enough lockable memory. You probably \
-                 need to raise the memlock rlimit, which \
-                 often defaults to a pretty low number.",
-            );
+            err = not_enough_memlock();
         }
         return Err(err);
     }
@@ -107,6 +496,15 @@ fn uring_mmap(
     Ok(ptr)
 }
 
+fn not_enough_memlock() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Not enough lockable memory. You probably \
+         need to raise the memlock rlimit, which \
+         often defaults to a pretty low number.",
+    )
+}
+
 impl FromCqe for TcpStream {
     fn from_cqe(cqe: io_uring_cqe) -> TcpStream {
         #[allow(unsafe_code)]
@@ -115,3 +513,27 @@ impl FromCqe for TcpStream {
         }
     }
 }
+
+/// For operations like `openat` that create a new
+/// file descriptor, which the `File` takes
+/// ownership of.
+impl FromCqe for File {
+    fn from_cqe(cqe: io_uring_cqe) -> File {
+        #[allow(unsafe_code)]
+        unsafe {
+            File::from_raw_fd(cqe.res)
+        }
+    }
+}
+
+/// For operations that create a new file
+/// descriptor of any kind, which the `OwnedFd`
+/// takes ownership of.
+impl FromCqe for OwnedFd {
+    fn from_cqe(cqe: io_uring_cqe) -> OwnedFd {
+        #[allow(unsafe_code)]
+        unsafe {
+            OwnedFd::from_raw_fd(cqe.res)
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+
+use super::*;
+
+#[derive(Debug, Default)]
+struct MultishotState {
+    items: VecDeque<io::Result<io_uring_cqe>>,
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// The reaper's side of a multishot operation,
+/// which is filled once for every CQE that the
+/// kernel posts for it.
+#[derive(Debug)]
+pub(crate) struct MultiFiller {
+    mu: Arc<Mutex<MultishotState>>,
+}
+
+impl MultiFiller {
+    /// Queue up a completion. `more` is false for
+    /// the last completion the kernel will post
+    /// for this operation. Like `Filler::fill`,
+    /// blocked waiters are woken up by the reaper
+    /// once it's done with its current batch.
+    pub(crate) fn fill(
+        &self,
+        item: io::Result<io_uring_cqe>,
+        more: bool,
+    ) {
+        let mut state = self.mu.lock().unwrap();
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+
+        state.items.push_back(item);
+        state.done = !more;
+    }
+}
+
+/// The completions of a single multishot SQE.
+///
+/// Unlike a `Completion`, the ticket of a
+/// multishot operation is not handed back by the
+/// reaper, but by this when it is dropped, after
+/// the kernel has posted its last completion.
+#[derive(Debug)]
+pub(crate) struct Multishot<'a> {
+    mu: Arc<Mutex<MultishotState>>,
+    uring: &'a Uring,
+    // taken by `take_ticket` to arm another
+    // operation on, once this one is finished
+    ticket: Option<usize>,
+    pub(crate) user_data: u64,
+    pub(crate) sqe_id: u64,
+    // polls are stopped by removing them,
+    // rather than by canceling them
+    pub(crate) is_poll: bool,
+}
+
+impl<'a> Multishot<'a> {
+    pub(crate) fn new(
+        uring: &'a Uring,
+        ticket: usize,
+    ) -> (Multishot<'a>, MultiFiller) {
+        let mu =
+            Arc::new(Mutex::new(MultishotState::default()));
+        let multishot = Multishot {
+            mu: mu.clone(),
+            uring,
+            ticket: Some(ticket),
+            user_data: 0,
+            sqe_id: 0,
+            is_poll: false,
+        };
+
+        (multishot, MultiFiller { mu })
+    }
+
+    /// Block until the next completion arrives,
+    /// returning `None` after the last one.
+    pub(crate) fn next_cqe(
+        &self,
+    ) -> Option<io::Result<io_uring_cqe>> {
+        if let Err(e) =
+            self.uring.ensure_submitted(self.sqe_id)
+        {
+            // see `Completion::wait`
+            return Some(Err(e));
+        }
+
+        // buffers that were handed back by dropping
+        // their `PoolBuf`s wait to be submitted, and
+        // we may need them for what we wait for
+        self.uring.submit_pending();
+
+        let _ = self.uring.metrics().measure(|m| &m.wait);
+
+        self.uring.wait_until(|| {
+            let state = self.mu.lock().unwrap();
+            !state.items.is_empty() || state.done
+        });
+
+        self.mu.lock().unwrap().items.pop_front()
+    }
+
+    pub(crate) fn poll_next_cqe(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<io_uring_cqe>>> {
+        if let Err(e) =
+            self.uring.ensure_submitted(self.sqe_id)
+        {
+            return Poll::Ready(Some(Err(e)));
+        }
+
+        // see `next_cqe`
+        self.uring.submit_pending();
+
+        #[cfg(feature = "single_thread")]
+        self.uring.reap_unless(|| {
+            let state = self.mu.lock().unwrap();
+            !state.items.is_empty() || state.done
+        });
+
+        let mut state = self.mu.lock().unwrap();
+        if let Some(item) = state.items.pop_front() {
+            Poll::Ready(Some(item))
+        } else if state.done {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+
+            // see `Completion::poll`
+            #[cfg(feature = "single_thread")]
+            cx.waker().wake_by_ref();
+
+            Poll::Pending
+        }
+    }
+
+    /// Whether the kernel has posted its last
+    /// completion, and every completion has been
+    /// consumed.
+    pub(crate) fn is_finished(&self) -> bool {
+        let state = self.mu.lock().unwrap();
+        state.done && state.items.is_empty()
+    }
+
+    /// Takes the ticket of a finished operation,
+    /// so that another one can be armed on it
+    /// without taking a new one while this still
+    /// holds its own. It's no longer released
+    /// when this is dropped.
+    pub(crate) fn take_ticket(&mut self) -> usize {
+        self.ticket.take().unwrap()
+    }
+
+    /// Cancel the operation if the kernel has not
+    /// finished it yet, and block until it has
+    /// posted its last completion. Completions
+    /// that were never consumed are returned.
+    pub(crate) fn cancel(
+        &self,
+    ) -> Vec<io::Result<io_uring_cqe>> {
+        self.uring.ensure_submitted_for_drop(self.sqe_id);
+
+        let done = self.mu.lock().unwrap().done;
+        if !done {
+            // this fails with ENOENT if the operation
+            // finished in the mean time, which is fine.
+            let _ = if self.is_poll {
+                self.uring.poll_remove(self.user_data)
+            } else {
+                self.uring.cancel(self.user_data)
+            };
+        }
+
+        self.uring
+            .wait_until(|| self.mu.lock().unwrap().done);
+
+        let mut state = self.mu.lock().unwrap();
+        state.items.drain(..).collect()
+    }
+}
+
+impl<'a> Drop for Multishot<'a> {
+    fn drop(&mut self) {
+        drop(self.cancel());
+        if let Some(ticket) = self.ticket {
+            self.uring.release_ticket(ticket);
+        }
+    }
+}
+
+/// A stream of buffers received by
+/// `Uring::recv_multishot`.
+///
+/// This can be used either as a blocking
+/// `Iterator` or as an async `Stream`. It ends
+/// when the peer shuts down the connection, or
+/// after yielding an error, such as `ENOBUFS`
+/// when the buffer group has run dry.
+///
+/// Dropping it cancels the receive, blocking
+/// until the kernel is done with it.
+#[derive(Debug)]
+pub struct RecvMultishot<'a> {
+    multishot: Multishot<'a>,
+    group: Option<Arc<BufferGroup>>,
+    uring: &'a Uring,
+}
+
+impl<'a> RecvMultishot<'a> {
+    pub(crate) const fn new(
+        uring: &'a Uring,
+        multishot: Multishot<'a>,
+        group: Option<Arc<BufferGroup>>,
+    ) -> RecvMultishot<'a> {
+        RecvMultishot {
+            multishot,
+            group,
+            uring,
+        }
+    }
+
+    /// Returns `None` when the stream is over.
+    fn to_buf(
+        &self,
+        item: io::Result<io_uring_cqe>,
+    ) -> Option<io::Result<PoolBuf<'a>>> {
+        let cqe = match item {
+            Ok(cqe) => cqe,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if cqe.flags & IORING_CQE_F_BUFFER == 0 {
+            // only EOF completes without a buffer
+            return None;
+        }
+
+        let group = self.group.clone().expect(
+            "kernel selected a buffer from a group \
+             that was never provided",
+        );
+        let bid = u16::try_from(
+            cqe.flags >> IORING_CQE_BUFFER_SHIFT,
+        )
+        .unwrap();
+        let len = usize::try_from(cqe.res).unwrap();
+
+        // an empty buffer is still handed back to
+        // the kernel when it's dropped here.
+        let buf = PoolBuf::new(self.uring, group, bid, len);
+        if len == 0 {
+            None
+        } else {
+            Some(Ok(buf))
+        }
+    }
+}
+
+impl<'a> Iterator for RecvMultishot<'a> {
+    type Item = io::Result<PoolBuf<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.multishot.next_cqe()?;
+        self.to_buf(item)
+    }
+}
+
+impl<'a> Stream for RecvMultishot<'a> {
+    type Item = io::Result<PoolBuf<'a>>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        match self.multishot.poll_next_cqe(cx) {
+            Poll::Ready(Some(item)) => {
+                Poll::Ready(self.to_buf(item))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a> Drop for RecvMultishot<'a> {
+    fn drop(&mut self) {
+        // hand any buffers that were received but
+        // never consumed back to the kernel.
+        for item in self.multishot.cancel() {
+            drop(self.to_buf(item));
+        }
+    }
+}
+
+/// A stream of readiness events from
+/// `Uring::poll_add_multishot`.
+///
+/// This can be used either as a blocking
+/// `Iterator` or as an async `Stream`. If the
+/// kernel stops posting events on its own, the
+/// poll is transparently armed again. It ends
+/// after yielding an error.
+///
+/// Dropping it removes the poll with
+/// `IORING_OP_POLL_REMOVE`, blocking until the
+/// kernel is done with it.
+#[derive(Debug)]
+pub struct PollMultishot<'a> {
+    multishot: Multishot<'a>,
+    uring: &'a Uring,
+    fd: RawFd,
+    events: PollFlags,
+}
+
+impl<'a> PollMultishot<'a> {
+    pub(crate) fn new(
+        uring: &'a Uring,
+        fd: RawFd,
+        events: PollFlags,
+    ) -> PollMultishot<'a> {
+        PollMultishot {
+            multishot: uring
+                .poll_multishot_sqe(fd, events, None),
+            uring,
+            fd,
+            events,
+        }
+    }
+
+    fn ready_events(
+        &mut self,
+        item: io::Result<io_uring_cqe>,
+    ) -> io::Result<PollFlags> {
+        let events = item.map(PollFlags::from_cqe)?;
+
+        // the kernel can end a multishot poll
+        // without an error, like when its CQ
+        // overflows, in which case it needs to be
+        // armed again to keep the events coming.
+        if self.multishot.is_finished() {
+            let ticket = self.multishot.take_ticket();
+            self.multishot = self.uring.poll_multishot_sqe(
+                self.fd,
+                self.events,
+                Some(ticket),
+            );
+        }
+
+        Ok(events)
+    }
+}
+
+impl<'a> Iterator for PollMultishot<'a> {
+    type Item = io::Result<PollFlags>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.multishot.next_cqe()?;
+        Some(self.ready_events(item))
+    }
+}
+
+impl<'a> Stream for PollMultishot<'a> {
+    type Item = io::Result<PollFlags>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.multishot.poll_next_cqe(cx) {
+            Poll::Ready(Some(item)) => {
+                Poll::Ready(Some(this.ready_events(item)))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
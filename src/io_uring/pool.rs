@@ -0,0 +1,170 @@
+use std::ops::Deref;
+
+use super::*;
+
+/// The memory backing a group of buffers that
+/// has been handed to the kernel with
+/// `Uring::provide_buffers`.
+#[derive(Debug)]
+pub(crate) struct BufferGroup {
+    ptr: *mut u8,
+    buf_size: usize,
+    count: u16,
+    pub(crate) bgid: u16,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for BufferGroup {}
+
+#[allow(unsafe_code)]
+unsafe impl Sync for BufferGroup {}
+
+impl Drop for BufferGroup {
+    fn drop(&mut self) {
+        let len = self.buf_size * usize::from(self.count);
+        #[allow(unsafe_code)]
+        unsafe {
+            drop(Box::from_raw(
+                std::ptr::slice_from_raw_parts_mut(
+                    self.ptr, len,
+                ),
+            ));
+        }
+    }
+}
+
+impl BufferGroup {
+    pub(crate) fn new(
+        bgid: u16,
+        count: u16,
+        buf_size: usize,
+    ) -> io::Result<BufferGroup> {
+        let len = buf_size
+            .checked_mul(usize::from(count))
+            .filter(|len| *len > 0)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "buffer pool size must be nonzero \
+                     and fit in memory",
+                )
+            })?;
+
+        if u32::try_from(buf_size).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffers in a pool must be smaller \
+                 than 4gb",
+            ));
+        }
+
+        let memory = vec![0_u8; len].into_boxed_slice();
+        let ptr = Box::into_raw(memory).cast::<u8>();
+
+        Ok(BufferGroup {
+            ptr,
+            buf_size,
+            count,
+            bgid,
+        })
+    }
+
+    pub(crate) const fn count(&self) -> u16 {
+        self.count
+    }
+
+    pub(crate) const fn buf_size(&self) -> usize {
+        self.buf_size
+    }
+
+    pub(crate) fn buf_ptr(&self, bid: u16) -> *mut u8 {
+        assert!(bid < self.count);
+        #[allow(unsafe_code)]
+        unsafe {
+            self.ptr.add(usize::from(bid) * self.buf_size)
+        }
+    }
+}
+
+/// A buffer from a group registered with
+/// `Uring::provide_buffers`, which the kernel
+/// selected and filled for an operation like
+/// `Uring::recv_multishot`.
+///
+/// Dropping it hands the buffer back to the
+/// kernel so that it may be used again. That
+/// doesn't wait on the kernel: the buffer goes
+/// back with the next submission, or before
+/// the next receive that has to wait.
+pub struct PoolBuf<'a> {
+    uring: &'a Uring,
+    group: Arc<BufferGroup>,
+    bid: u16,
+    len: usize,
+}
+
+impl<'a> std::fmt::Debug for PoolBuf<'a> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("PoolBuf")
+            .field("bgid", &self.group.bgid)
+            .field("bid", &self.bid)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<'a> PoolBuf<'a> {
+    pub(crate) fn new(
+        uring: &'a Uring,
+        group: Arc<BufferGroup>,
+        bid: u16,
+        len: usize,
+    ) -> PoolBuf<'a> {
+        assert!(len <= group.buf_size());
+        PoolBuf {
+            uring,
+            group,
+            bid,
+            len,
+        }
+    }
+
+    /// The id of this buffer within its group.
+    pub const fn buffer_id(&self) -> u16 {
+        self.bid
+    }
+}
+
+impl<'a> Deref for PoolBuf<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // the kernel will not touch this buffer
+        // again until we provide it back on drop.
+        #[allow(unsafe_code)]
+        unsafe {
+            std::slice::from_raw_parts(
+                self.group.buf_ptr(self.bid),
+                self.len,
+            )
+        }
+    }
+}
+
+impl<'a> AsRef<[u8]> for PoolBuf<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<'a> Drop for PoolBuf<'a> {
+    fn drop(&mut self) {
+        // if this fails the buffer is just lost
+        // to the pool, which the kernel reports
+        // with ENOBUFS once the group runs dry.
+        self.uring.provide_detached(&self.group, self.bid);
+    }
+}
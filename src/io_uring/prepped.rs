@@ -0,0 +1,75 @@
+use std::marker::PhantomData;
+
+use super::*;
+
+/// A read or write that has been described but
+/// not yet submitted, for passing to
+/// `Uring::linked_pair`. It borrows its file and
+/// buffer for as long as the `Completion` that
+/// it is eventually submitted as.
+#[derive(Clone, Copy)]
+pub struct PreppedOp<'a> {
+    pub(crate) opcode: u8,
+    pub(crate) fd: RawFd,
+    pub(crate) iovec: libc::iovec,
+    pub(crate) at: u64,
+    borrows: PhantomData<&'a ()>,
+}
+
+impl<'a> PreppedOp<'a> {
+    /// Reads into the provided buffer from the
+    /// given file at the given offset, like
+    /// `Uring::read_at`.
+    pub fn read_at<F, B>(
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> PreppedOp<'a>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        PreppedOp {
+            opcode: IORING_OP_READV,
+            fd: file.as_raw_fd(),
+            iovec: iov.into_new_iovec(),
+            at,
+            borrows: PhantomData,
+        }
+    }
+
+    /// Writes the provided buffer to the given
+    /// file at the given offset, like
+    /// `Uring::write_at`.
+    pub fn write_at<F, B>(
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> PreppedOp<'a>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        PreppedOp {
+            opcode: IORING_OP_WRITEV,
+            fd: file.as_raw_fd(),
+            iovec: iov.into_new_iovec(),
+            at,
+            borrows: PhantomData,
+        }
+    }
+}
+
+impl std::fmt::Debug for PreppedOp<'_> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("PreppedOp")
+            .field("opcode", &self.opcode)
+            .field("fd", &self.fd)
+            .field("len", &self.iovec.iov_len)
+            .field("at", &self.at)
+            .finish()
+    }
+}
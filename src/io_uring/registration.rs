@@ -0,0 +1,592 @@
+use super::*;
+
+/// A set of files registered with a `Uring` by
+/// `Uring::register_files`, which stays
+/// registered until this is dropped.
+///
+/// Operations on registered files are performed
+/// through this, on the `RegisteredFd`s that it
+/// hands out for them with `fd` and `fds`, so
+/// they borrow it and can't outlive the
+/// registration.
+///
+/// Slots can be given other files with `update`,
+/// `remove` and `accept_fixed`, after which, in
+/// debug builds, operations on a `RegisteredFd`
+/// from before then fail with
+/// `io::ErrorKind::InvalidInput`, instead of
+/// quietly using the new file.
+#[derive(Debug)]
+pub struct FilesRegistration<'a> {
+    uring: &'a Uring,
+    len: usize,
+    generations: Vec<AtomicU64>,
+}
+
+/// A file registered with a `FilesRegistration`,
+/// which carries its index in the registered
+/// table and, in debug builds, which file was
+/// in that slot when this was handed out, so
+/// that it isn't used with another one by
+/// mistake.
+///
+/// Operations that take one always set
+/// `IOSQE_FIXED_FILE`, and since it isn't a file
+/// descriptor, it can't be given to the normal
+/// operations on `Uring`:
+///
+/// ```compile_fail
+/// let ring = rio::new().unwrap();
+/// let files = [std::fs::File::open("a").unwrap()];
+/// let registered =
+///     ring.register_files(&files).unwrap();
+/// let fd = registered.fd(0);
+///
+/// let buf = vec![0; 5];
+/// ring.read_at(&fd, &buf, 0).wait().unwrap();
+/// ```
+///
+/// nor can a plain file be given to the
+/// operations on registered ones:
+///
+/// ```compile_fail
+/// let ring = rio::new().unwrap();
+/// let files = [std::fs::File::open("a").unwrap()];
+/// let registered =
+///     ring.register_files(&files).unwrap();
+///
+/// let buf = vec![0; 5];
+/// registered
+///     .read_at(&files[0], &buf, 0)
+///     .wait()
+///     .unwrap();
+/// ```
+///
+/// # Examples
+///
+/// ```no_run
+/// let ring = rio::new().unwrap();
+/// let files = [std::fs::File::open("a").unwrap()];
+/// let registered =
+///     ring.register_files(&files).unwrap();
+///
+/// let fd = registered.fd(0);
+/// let other = std::fs::File::open("b").unwrap();
+/// let other_fd = registered.update(0, &other).unwrap();
+///
+/// // the slot was given another file since
+/// let buf = vec![0; 5];
+/// assert!(registered
+///     .read_at(&fd, &buf, 0)
+///     .wait()
+///     .is_err());
+/// registered.read_at(&other_fd, &buf, 0).wait().unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisteredFd {
+    index: usize,
+    generation: Option<u64>,
+}
+
+impl RegisteredFd {
+    const fn new(
+        index: usize,
+        generation: u64,
+    ) -> RegisteredFd {
+        RegisteredFd {
+            index,
+            generation: if cfg!(debug_assertions) {
+                Some(generation)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The index of the file in the registered
+    /// table.
+    pub const fn index(self) -> usize {
+        self.index
+    }
+}
+
+impl<'a> Drop for FilesRegistration<'a> {
+    fn drop(&mut self) {
+        let _ = register(
+            self.uring.as_raw_fd(),
+            IORING_UNREGISTER_FILES,
+            std::ptr::null(),
+            0,
+        );
+    }
+}
+
+impl<'a> FilesRegistration<'a> {
+    pub(crate) fn new(
+        uring: &'a Uring,
+        len: usize,
+    ) -> FilesRegistration<'a> {
+        FilesRegistration {
+            uring,
+            len,
+            generations: (0..len)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    /// The number of registered files.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no files are registered.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The file in the slot at `index`, which, in
+    /// debug builds, operations fail on if the
+    /// slot is given another file after this was
+    /// called.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn fd(&self, index: usize) -> RegisteredFd {
+        RegisteredFd::new(
+            index,
+            self.generations[index].load(Acquire),
+        )
+    }
+
+    /// The files in every slot, in the order that
+    /// they were registered.
+    pub fn fds(&self) -> Vec<RegisteredFd> {
+        (0..self.len).map(|index| self.fd(index)).collect()
+    }
+
+    /// Replaces the file in the slot at `index`
+    /// with `file`, returning the `RegisteredFd`
+    /// that operations on it use from then on.
+    /// Operations that are already in flight keep
+    /// using the file that was there when they
+    /// started.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.5 and up.
+    pub fn update<F>(
+        &self,
+        index: usize,
+        file: &'a F,
+    ) -> io::Result<RegisteredFd>
+    where
+        F: AsRawFd,
+    {
+        let generation =
+            self.update_fd(index, file.as_raw_fd())?;
+        Ok(RegisteredFd::new(index, generation))
+    }
+
+    /// Empties the slot at `index`, so operations
+    /// on it fail with `EBADF` until it is given
+    /// another file.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.5 and up.
+    pub fn remove(&self, index: usize) -> io::Result<()> {
+        let _ = self.update_fd(index, -1)?;
+        Ok(())
+    }
+
+    /// Puts `fd` in the slot at `index`, returning
+    /// the slot's new generation.
+    fn update_fd(
+        &self,
+        index: usize,
+        fd: RawFd,
+    ) -> io::Result<u64> {
+        checked_index(index, self.len)?;
+
+        let update = io_uring_files_update {
+            offset: u32::try_from(index).unwrap(),
+            resv: 0,
+            fds: std::ptr::addr_of!(fd) as u64,
+        };
+        let _ = register(
+            self.uring.as_raw_fd(),
+            IORING_REGISTER_FILES_UPDATE,
+            std::ptr::addr_of!(update).cast(),
+            1,
+        )
+        .map_err(|e| {
+            unsupported_register(
+                IORING_REGISTER_FILES_UPDATE,
+                e,
+            )
+        })?;
+
+        Ok(self.bump(index))
+    }
+
+    /// Records that the slot at `index` was given
+    /// another file, so that `RegisteredFd`s from
+    /// before then no longer match it, returning
+    /// its new generation.
+    fn bump(&self, index: usize) -> u64 {
+        self.generations[index].fetch_add(1, AcqRel) + 1
+    }
+
+    /// Checks that `fd` is in bounds and, in debug
+    /// builds, that its slot still has the same
+    /// file in it, returning its index.
+    fn checked_fd(
+        &self,
+        fd: &RegisteredFd,
+    ) -> io::Result<usize> {
+        checked_index(fd.index, self.len)?;
+        match fd.generation {
+            Some(generation)
+                if generation
+                    != self.generations[fd.index]
+                        .load(Acquire) =>
+            {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "registered file slot {} was \
+                         given another file after this \
+                         RegisteredFd was obtained",
+                        fd.index
+                    ),
+                ))
+            }
+            _ => Ok(fd.index),
+        }
+    }
+
+    /// Reads into the provided buffer from the
+    /// registered file `fd`, at the given
+    /// offset, like `Uring::read_at`.
+    pub fn read_at<'b, B>(
+        &'b self,
+        fd: &RegisteredFd,
+        iov: &'b B,
+        at: u64,
+    ) -> Completion<'b, usize>
+    where
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.rw_at(
+            IORING_OP_READV,
+            fd,
+            iov,
+            at,
+            RwFlags::empty(),
+        )
+    }
+
+    /// Writes the provided buffer to the
+    /// registered file `fd`, at the given
+    /// offset, like `Uring::write_at`.
+    pub fn write_at<'b, B>(
+        &'b self,
+        fd: &RegisteredFd,
+        iov: &'b B,
+        at: u64,
+    ) -> Completion<'b, usize>
+    where
+        B: ?Sized + AsIoVec,
+    {
+        self.rw_at(
+            IORING_OP_WRITEV,
+            fd,
+            iov,
+            at,
+            RwFlags::empty(),
+        )
+    }
+
+    /// Appends the provided buffer to the end of
+    /// the registered file `fd`, using
+    /// `RWF_APPEND`, as if it had been opened with
+    /// `O_APPEND`, so each one lands in full after
+    /// everything that was appended before it.
+    ///
+    /// Like any other operations, appends that are
+    /// in flight at the same time may be performed
+    /// in any order, so if the order of records
+    /// matters, wait on each one before starting
+    /// the next.
+    pub fn append<'b, B>(
+        &'b self,
+        fd: &RegisteredFd,
+        iov: &'b B,
+    ) -> Completion<'b, usize>
+    where
+        B: ?Sized + AsIoVec,
+    {
+        // the offset is ignored with RWF_APPEND
+        self.rw_at(
+            IORING_OP_WRITEV,
+            fd,
+            iov,
+            0,
+            RwFlags::APPEND,
+        )
+    }
+
+    /// Accepts a connection from `listener`
+    /// straight into the registered file slot at
+    /// `index`, replacing whatever was there,
+    /// without it ever getting a normal file
+    /// descriptor. Resolves to the `RegisteredFd`
+    /// that it was installed as, through which it
+    /// can then be used.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.19 and up.
+    pub fn accept_fixed<'b>(
+        &'b self,
+        listener: &'b TcpListener,
+        index: usize,
+    ) -> OwnedCompletion<'b, RegisteredFd> {
+        let (completion, generation) = if let Err(e) =
+            checked_index(index, self.len)
+        {
+            (Completion::failed(self.uring, e), 0)
+        } else {
+            // whatever is in the slot now is on its
+            // way out, so its `RegisteredFd`s are too
+            let generation = self.bump(index);
+            let completion =
+                self.uring.with_sqe(None, false, |sqe| {
+                    sqe.prep_rw(
+                        IORING_OP_ACCEPT,
+                        listener.as_raw_fd(),
+                        0,
+                        0,
+                        Ordering::None,
+                    );
+                    // 0 means a normal descriptor, so
+                    // slots are counted from 1 here.
+                    sqe.__bindgen_anon_2
+                        .__bindgen_anon_1
+                        .splice_fd_in =
+                        i32::try_from(index + 1).unwrap();
+                });
+            (completion, generation)
+        };
+
+        let fd = RegisteredFd::new(index, generation);
+        OwnedCompletion::new(completion, move |_| Ok(fd))
+    }
+
+    /// Receives data from the registered socket
+    /// `fd` into the provided buffer, like
+    /// `Uring::recv`.
+    pub fn recv<'b, B>(
+        &'b self,
+        fd: &RegisteredFd,
+        iov: &'b B,
+    ) -> Completion<'b, usize>
+    where
+        B: AsIoVec + AsIoVecMut,
+    {
+        let iovec = iov.into_new_iovec();
+        let index = match self.checked_fd(fd) {
+            Ok(index) => index,
+            Err(e) => {
+                return Completion::failed(self.uring, e)
+            }
+        };
+
+        self.uring.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_RECV,
+                i32::try_from(index).unwrap(),
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.flags |= IOSQE_FIXED_FILE;
+            sqe.addr = iovec.iov_base as u64;
+            sqe.len = u32::try_from(iovec.iov_len).unwrap();
+        })
+    }
+
+    fn rw_at<'b, B>(
+        &'b self,
+        opcode: u8,
+        fd: &RegisteredFd,
+        iov: &'b B,
+        at: u64,
+        flags: RwFlags,
+    ) -> Completion<'b, usize>
+    where
+        B: ?Sized + AsIoVec,
+    {
+        let iovec = iov.into_new_iovec();
+        let index =
+            match self.checked_fd(fd).and_then(|index| {
+                checked_end(at, iovec.iov_len)?;
+                Ok(index)
+            }) {
+                Ok(index) => index,
+                Err(e) => {
+                    return Completion::failed(
+                        self.uring, e,
+                    )
+                }
+            };
+
+        self.uring.with_sqe(Some(iovec), false, |sqe| {
+            sqe.prep_rw(
+                opcode,
+                i32::try_from(index).unwrap(),
+                1,
+                at,
+                Ordering::None,
+            );
+            sqe.flags |= IOSQE_FIXED_FILE;
+            sqe.__bindgen_anon_1.rw_flags = flags.bits();
+        })
+    }
+}
+
+/// A set of buffers registered with a `Uring` by
+/// `Uring::register_buffers`, which stays
+/// registered, with their memory pinned, until
+/// this is dropped.
+///
+/// Operations on registered buffers are performed
+/// through this, by their index in the slice that
+/// was registered, so they borrow it and can't
+/// outlive the registration.
+#[derive(Debug)]
+pub struct BuffersRegistration<'a> {
+    uring: &'a Uring,
+    iovecs: Vec<libc::iovec>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl<'a> Send for BuffersRegistration<'a> {}
+
+#[allow(unsafe_code)]
+unsafe impl<'a> Sync for BuffersRegistration<'a> {}
+
+impl<'a> Drop for BuffersRegistration<'a> {
+    fn drop(&mut self) {
+        let _ = register(
+            self.uring.as_raw_fd(),
+            IORING_UNREGISTER_BUFFERS,
+            std::ptr::null(),
+            0,
+        );
+    }
+}
+
+impl<'a> BuffersRegistration<'a> {
+    pub(crate) const fn new(
+        uring: &'a Uring,
+        iovecs: Vec<libc::iovec>,
+    ) -> BuffersRegistration<'a> {
+        BuffersRegistration { uring, iovecs }
+    }
+
+    /// The number of registered buffers.
+    pub const fn len(&self) -> usize {
+        self.iovecs.len()
+    }
+
+    /// Whether no buffers are registered.
+    pub const fn is_empty(&self) -> bool {
+        self.iovecs.is_empty()
+    }
+
+    /// Reads from the given file, at the given
+    /// offset, into the whole of the registered
+    /// buffer at `index`.
+    pub fn read_at<'b, F>(
+        &'b self,
+        file: &'b F,
+        index: usize,
+        at: u64,
+    ) -> Completion<'b, usize>
+    where
+        F: AsRawFd,
+    {
+        self.rw_at(IORING_OP_READ_FIXED, file, index, at)
+    }
+
+    /// Writes the whole of the registered buffer
+    /// at `index` to the given file, at the given
+    /// offset.
+    pub fn write_at<'b, F>(
+        &'b self,
+        file: &'b F,
+        index: usize,
+        at: u64,
+    ) -> Completion<'b, usize>
+    where
+        F: AsRawFd,
+    {
+        self.rw_at(IORING_OP_WRITE_FIXED, file, index, at)
+    }
+
+    fn rw_at<'b, F>(
+        &'b self,
+        opcode: u8,
+        file: &'b F,
+        index: usize,
+        at: u64,
+    ) -> Completion<'b, usize>
+    where
+        F: AsRawFd,
+    {
+        let iovec = match checked_index(index, self.len())
+            .map(|_| self.iovecs[index])
+            .and_then(|iovec| {
+                checked_end(at, iovec.iov_len)
+                    .map(|_| iovec)
+            }) {
+            Ok(iovec) => iovec,
+            Err(e) => {
+                return Completion::failed(self.uring, e)
+            }
+        };
+
+        self.uring.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                opcode,
+                file.as_raw_fd(),
+                iovec.iov_len,
+                at,
+                Ordering::None,
+            );
+            sqe.addr = iovec.iov_base as u64;
+            sqe.__bindgen_anon_2.buf_index =
+                u16::try_from(index).unwrap();
+        })
+    }
+}
+
+fn checked_index(
+    index: usize,
+    len: usize,
+) -> io::Result<()> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "registration index out of bounds",
+        ))
+    }
+}
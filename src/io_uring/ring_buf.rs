@@ -0,0 +1,143 @@
+/// A fixed-size circular buffer for streaming
+/// data in from a file or socket with
+/// `Uring::read_into_ring`, which fills in its
+/// free space after whatever has been read so
+/// far, wrapping around to the start of the
+/// buffer when it reaches the end.
+///
+/// # Examples
+///
+/// ```no_run
+/// let ring = rio::new().unwrap();
+/// let file = std::fs::File::open("file").unwrap();
+/// let mut buf = rio::RingBuf::with_capacity(4096);
+///
+/// let mut at = 0;
+/// loop {
+///     let read = ring
+///         .read_into_ring(&file, &mut buf, at)
+///         .wait()
+///         .unwrap();
+///     if read == 0 {
+///         break;
+///     }
+///     at += read as u64;
+///
+///     let (front, back) = buf.as_slices();
+///     // process front, then back
+///     let consumed = front.len() + back.len();
+///     buf.consume(consumed);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingBuf {
+    buf: Box<[u8]>,
+    head: usize,
+    len: usize,
+}
+
+impl RingBuf {
+    /// Creates an empty ring buffer that holds up
+    /// to `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> RingBuf {
+        RingBuf {
+            buf: vec![0; capacity].into_boxed_slice(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// How many bytes this can hold at once.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// How many bytes have been read into this,
+    /// but not consumed yet.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there is nothing to consume.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many more bytes can be read into this
+    /// before it's full.
+    pub fn free(&self) -> usize {
+        self.capacity() - self.len
+    }
+
+    /// The bytes that haven't been consumed yet,
+    /// in order. The second slice is only
+    /// non-empty when they wrap around the end
+    /// of the buffer.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let first =
+            self.len.min(self.capacity() - self.head);
+        (
+            &self.buf[self.head..self.head + first],
+            &self.buf[..self.len - first],
+        )
+    }
+
+    /// Drops the first `n` bytes that haven't been
+    /// consumed yet, freeing up their space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is more than `len()`.
+    pub fn consume(&mut self, n: usize) {
+        assert!(
+            n <= self.len,
+            "consumed {} bytes, but only {} are there",
+            n,
+            self.len
+        );
+        self.len -= n;
+        self.head = if self.len == 0 {
+            // start over at the front, so that the
+            // next read is less likely to wrap
+            0
+        } else {
+            (self.head + n) % self.capacity()
+        };
+    }
+
+    /// The free space after the unconsumed bytes,
+    /// as up to two iovecs: from there to the end
+    /// of the buffer, and then from its start.
+    pub(crate) fn free_iovecs(
+        &mut self,
+    ) -> Vec<libc::iovec> {
+        let capacity = self.capacity();
+        if self.len == capacity {
+            return vec![];
+        }
+
+        let tail = (self.head + self.len) % capacity;
+        let first = self.free().min(capacity - tail);
+        let second = self.free() - first;
+        let base = self.buf.as_mut_ptr();
+
+        let mut iovecs = vec![libc::iovec {
+            iov_base: base.wrapping_add(tail).cast(),
+            iov_len: first,
+        }];
+        if second > 0 {
+            iovecs.push(libc::iovec {
+                iov_base: base.cast(),
+                iov_len: second,
+            });
+        }
+        iovecs
+    }
+
+    /// Marks the `n` bytes after the unconsumed
+    /// ones as filled in.
+    pub(crate) fn fill(&mut self, n: usize) {
+        assert!(n <= self.free());
+        self.len += n;
+    }
+}
@@ -1,8 +1,11 @@
 #![allow(unsafe_code)]
 
-use std::slice::from_raw_parts_mut;
+use std::{
+    ops::Range, slice::from_raw_parts_mut,
+    sync::atomic::fence,
+};
 
-use super::*;
+use super::{uring::SqFault, *};
 
 /// Sprays uring submissions.
 #[derive(Debug)]
@@ -12,13 +15,27 @@ pub(crate) struct Sq {
     kring_mask: *mut u32,
     kflags: *mut AtomicU32,
     kdropped: *mut AtomicU32,
+    /// The kernel's count of dropped SQEs, as of
+    /// the last submission, since it only ever
+    /// grows.
+    dropped_seen: u32,
+    /// SQEs that were published, but that the
+    /// kernel wasn't told about because `enter`
+    /// failed. They go along with the next
+    /// submission.
+    unentered: u32,
     array: &'static mut [AtomicU32],
     sqes: &'static mut [io_uring_sqe],
     sqe_head: u32,
     sqe_tail: u32,
+    sq_poll_wakeups: u64,
+    group_by_fd: bool,
+    #[cfg(feature = "testing")]
+    fault: Option<SqFault>,
     ring_ptr: *const libc::c_void,
     ring_mmap_sz: usize,
     sqes_mmap_sz: usize,
+    metrics: Arc<Metrics>,
 }
 
 impl Drop for Sq {
@@ -43,6 +60,8 @@ impl Sq {
     pub(crate) fn new(
         params: &io_uring_params,
         ring_fd: i32,
+        group_by_fd: bool,
+        metrics: Arc<Metrics>,
     ) -> io::Result<Sq> {
         let sq_ring_mmap_sz = params.sq_off.array as usize
             + (params.sq_entries as usize
@@ -70,9 +89,21 @@ impl Sq {
             Sq {
                 sqe_head: 0,
                 sqe_tail: 0,
+                sq_poll_wakeups: 0,
+                unentered: 0,
+                // with SQPOLL, the kernel consumes
+                // SQEs as soon as they're in the
+                // array, so `head` only covers the
+                // ones that were flushed in order
+                group_by_fd: group_by_fd
+                    && params.flags & IORING_SETUP_SQPOLL
+                        == 0,
+                #[cfg(feature = "testing")]
+                fault: None,
                 ring_ptr: sq_ring_ptr,
                 ring_mmap_sz: sq_ring_mmap_sz,
                 sqes_mmap_sz,
+                metrics,
                 khead: sq_ring_ptr
                     .add(params.sq_off.head as usize)
                     as *mut AtomicU32,
@@ -88,6 +119,7 @@ impl Sq {
                 kdropped: sq_ring_ptr
                     .add(params.sq_off.dropped as usize)
                     as *mut AtomicU32,
+                dropped_seen: 0,
                 array: from_raw_parts_mut(
                     sq_ring_ptr
                         .add(params.sq_off.array as usize)
@@ -102,20 +134,60 @@ impl Sq {
         })
     }
 
+    pub(crate) const fn capacity(&self) -> usize {
+        self.sqes.len()
+    }
+
+    /// The number of times that `submit_all` has
+    /// had to wake up the SQPOLL kernel thread
+    /// after it went idle.
+    pub(crate) const fn sq_poll_wakeups(&self) -> u64 {
+        self.sq_poll_wakeups
+    }
+
+    /// Fills in the submission queue's side of
+    /// `state`.
+    pub(crate) fn dump_state(&self, state: &mut RingState) {
+        state.sq_head =
+            unsafe { &*self.khead }.load(Acquire);
+        state.sq_tail =
+            unsafe { &*self.ktail }.load(Acquire);
+        state.sq_mask = unsafe { *self.kring_mask };
+        state.sqe_head = self.sqe_head;
+        state.sqe_tail = self.sqe_tail;
+        state.sq_dropped =
+            unsafe { &*self.kdropped }.load(Acquire);
+    }
+
+    fn head(&self, ring_flags: u32) -> u32 {
+        if (ring_flags & IORING_SETUP_SQPOLL) == 0 {
+            // non-polling mode, where the kernel
+            // hasn't consumed what `enter` failed on
+            self.sqe_head.wrapping_sub(self.unentered)
+        } else {
+            // polling mode
+            unsafe { &*self.khead }.load(Acquire)
+        }
+    }
+
+    /// The number of SQEs that may be acquired
+    /// with `try_get_sqe` before the submission
+    /// queue is full.
+    pub(crate) fn space_left(
+        &self,
+        ring_flags: u32,
+    ) -> usize {
+        let used = self.sqe_tail - self.head(ring_flags);
+        self.capacity() - used as usize
+    }
+
     pub(crate) fn try_get_sqe(
         &mut self,
         ring_flags: u32,
     ) -> Option<&mut io_uring_sqe> {
         let next = self.sqe_tail + 1;
 
-        let head =
-            if (ring_flags & IORING_SETUP_SQPOLL) == 0 {
-                // non-polling mode
-                self.sqe_head
-            } else {
-                // polling mode
-                unsafe { &*self.khead }.load(Acquire)
-            };
+        let head = self.head(ring_flags);
 
         if next - head <= self.sqes.len() as u32 {
             let idx =
@@ -123,97 +195,294 @@ impl Sq {
             let sqe = &mut self.sqes[idx as usize];
             self.sqe_tail = next;
 
+            // the slot still holds whatever used it
+            // last, and the kernel rejects some ops,
+            // like fsync, if fields they don't use,
+            // like addr, aren't zero
+            *sqe = io_uring_sqe::default();
+
             Some(sqe)
         } else {
             None
         }
     }
 
-    // sets sq.array to point to current sq.sqe_head
-    fn flush(&mut self) -> u32 {
+    // sets sq.array to point to current sq.sqe_head.
+    // the SQEs are published even if this fails, so
+    // the count is returned either way.
+    fn flush(&mut self) -> (u32, io::Result<()>) {
         let mask: u32 = unsafe { *self.kring_mask };
         let to_submit = self.sqe_tail - self.sqe_head;
 
         let mut ktail =
             unsafe { &*self.ktail }.load(Acquire);
 
-        for _ in 0..to_submit {
-            let index = ktail & mask;
-            self.array[index as usize]
-                .store(self.sqe_head & mask, Release);
-            ktail += 1;
-            self.sqe_head += 1;
+        if self.group_by_fd && to_submit > 1 {
+            for pos in self.grouped(to_submit) {
+                let index = ktail & mask;
+                self.array[index as usize]
+                    .store(pos & mask, Release);
+                ktail += 1;
+            }
+            self.sqe_head += to_submit;
+        } else {
+            for _ in 0..to_submit {
+                let index = ktail & mask;
+                self.array[index as usize]
+                    .store(self.sqe_head & mask, Release);
+                ktail += 1;
+                self.sqe_head += 1;
+            }
         }
 
         let swapped =
             unsafe { &*self.ktail }.swap(ktail, Release);
+        let expected = ktail.wrapping_sub(to_submit);
 
-        assert_eq!(swapped, ktail - to_submit);
+        let injected = self.take_fault(SqFault::TailMoved);
+        debug_assert!(
+            injected || swapped == expected,
+            "submission queue tail was moved from {} \
+             to {} while rio was submitting",
+            expected,
+            swapped,
+        );
+        if swapped != expected || injected {
+            return (
+                to_submit,
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "submission queue tail was moved \
+                         from {} to {} by someone else \
+                         while rio was submitting",
+                        expected, swapped,
+                    ),
+                )),
+            );
+        }
 
-        to_submit
+        (to_submit, Ok(()))
     }
 
+    /// The positions of the next `count` SQEs to
+    /// submit, reordered so that ones on the same
+    /// fd are next to each other, for
+    /// `Config::group_by_fd`. The sort is stable,
+    /// linked chains are moved as a whole, and
+    /// nothing is moved past a drain, or a chain
+    /// at the end that isn't finished yet, since
+    /// the kernel links it to whatever is
+    /// submitted after it.
+    fn grouped(&self, count: u32) -> Vec<u32> {
+        let mask: u32 = unsafe { *self.kring_mask };
+        let sqe =
+            |pos: u32| &self.sqes[(pos & mask) as usize];
+        let linked = |pos: u32| {
+            sqe(pos).flags
+                & (IOSQE_IO_LINK | IOSQE_IO_HARDLINK)
+                != 0
+        };
+
+        let mut order = Vec::with_capacity(count as usize);
+        let mut units = vec![];
+        let end = self.sqe_head + count;
+        let mut pos = self.sqe_head;
+        while pos < end {
+            let start = pos;
+            while linked(pos) && pos + 1 < end {
+                pos += 1;
+            }
+            pos += 1;
+
+            let first = sqe(start);
+            if first.flags & IOSQE_IO_DRAIN != 0
+                || linked(pos - 1)
+            {
+                sort_units(&mut units, &mut order);
+                order.extend(start..pos);
+            } else {
+                let fixed = first.flags & IOSQE_FIXED_FILE;
+                units.push(((fixed, first.fd), start..pos));
+            }
+        }
+        sort_units(&mut units, &mut order);
+
+        order
+    }
+
+    /// Makes the next submission run into `fault`.
+    #[cfg(feature = "testing")]
+    pub(crate) fn inject_fault(&mut self, fault: SqFault) {
+        self.fault = Some(fault);
+    }
+
+    /// Whether `fault` was injected, clearing it
+    /// if so, so that it only happens once.
+    #[cfg(feature = "testing")]
+    fn take_fault(&mut self, fault: SqFault) -> bool {
+        if self.fault == Some(fault) {
+            self.fault = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    #[cfg(not(feature = "testing"))]
+    #[allow(clippy::unused_self)]
+    const fn take_fault(&self, _fault: SqFault) -> bool {
+        false
+    }
+
+    /// Submits everything that has been filled in,
+    /// failing instead of panicking if the queue
+    /// turns out to be inconsistent, because that
+    /// is caused by something outside of rio's
+    /// control, like a kernel quirk, or another
+    /// user of the ring's memory.
+    ///
+    /// Returns how many SQEs were handed to the
+    /// kernel alongside the result, since they
+    /// were, even when it's an error.
     pub(crate) fn submit_all(
         &mut self,
         ring_flags: u32,
         ring_fd: i32,
-    ) -> u64 {
-        let submitted = if ring_flags & IORING_SETUP_SQPOLL
+    ) -> (u64, io::Result<()>) {
+        let (submitted, flush_res) = if ring_flags
+            & IORING_SETUP_SQPOLL
             == 0
         {
             // non-SQPOLL mode, we need to use
             // `enter` to submit our SQEs.
 
-            // TODO for polling, keep flags at 0
-
+            // with a background reaper thread, we don't
+            // set IORING_ENTER_GETEVENTS here, because
+            // it's already blocking in `enter` to reap
+            // completions, and asking the kernel to
+            // also do so here just causes contention.
+            // Under `single_thread` nothing else is in
+            // `enter`, so completions are flushed to
+            // the queue as we submit.
+            #[cfg(not(feature = "single_thread"))]
+            let flags = 0;
+            #[cfg(feature = "single_thread")]
             let flags = IORING_ENTER_GETEVENTS;
-            let flushed = self.flush();
-            let mut to_submit = flushed;
+            let (flushed, flush_res) = self.flush();
+            let mut to_submit = flushed + self.unentered;
+            let mut enter_res = Ok(());
             while to_submit > 0 {
-                let _ = Measure::new(&M.enter_sqe);
-                let ret = enter(
+                let _ =
+                    self.metrics.measure(|m| &m.enter_sqe);
+                match enter(
                     ring_fd,
                     to_submit,
                     0,
                     flags,
                     std::ptr::null_mut(),
-                )
-                .expect(
-                    "Failed to submit items to kernel via \
-                     io_uring. This should never fail.",
-                );
-                to_submit -= u32::try_from(ret).unwrap();
+                ) {
+                    Ok(ret) => {
+                        to_submit -=
+                            u32::try_from(ret).unwrap();
+                    }
+                    Err(e) => {
+                        enter_res = Err(e);
+                        break;
+                    }
+                }
             }
-            flushed
-        } else if unsafe { &*self.kflags }.load(Acquire)
-            & IORING_SQ_NEED_WAKEUP
-            != 0
-        {
-            // the kernel has signalled to us that the
-            // SQPOLL thread that checks the submission
-            // queue has terminated due to inactivity,
-            // and needs to be restarted.
-            let to_submit = self.sqe_tail - self.sqe_head;
-            let _ = Measure::new(&M.enter_sqe);
-            enter(
-                ring_fd,
-                to_submit,
-                0,
-                IORING_ENTER_SQ_WAKEUP,
-                std::ptr::null_mut(),
-            )
-            .expect(
-                "Failed to wake up SQPOLL io_uring \
-                 kernel thread. This should never fail.",
-            );
-            0
+            let entered =
+                flushed + self.unentered - to_submit;
+            self.unentered = to_submit;
+            (entered, flush_res.and(enter_res))
         } else {
-            0
+            // SQPOLL mode, the kernel thread picks up
+            // our SQEs as soon as we publish them.
+            let (flushed, flush_res) = self.flush();
+            let mut entered = flushed + self.unentered;
+            let mut enter_res = Ok(());
+
+            // the tail must be visible before we check
+            // whether the kernel thread went to sleep,
+            // or it could miss our SQEs.
+            fence(SeqCst);
+
+            if unsafe { &*self.kflags }.load(Relaxed)
+                & IORING_SQ_NEED_WAKEUP
+                != 0
+            {
+                // the kernel has signalled to us that the
+                // SQPOLL thread that checks the submission
+                // queue has terminated due to inactivity,
+                // and needs to be restarted.
+                self.sq_poll_wakeups += 1;
+                self.metrics.add_sq_poll_wakeup();
+
+                let _ =
+                    self.metrics.measure(|m| &m.enter_sqe);
+                if let Err(e) = enter(
+                    ring_fd,
+                    entered,
+                    0,
+                    IORING_ENTER_SQ_WAKEUP,
+                    std::ptr::null_mut(),
+                ) {
+                    // nothing is handed over until the
+                    // thread is woken up by a later try
+                    enter_res = Err(e);
+                    entered = 0;
+                }
+            }
+            self.unentered =
+                flushed + self.unentered - entered;
+            (entered, flush_res.and(enter_res))
         };
-        assert_eq!(
-            unsafe { &*self.kdropped }.load(Relaxed),
-            0
+
+        let injected = self.take_fault(SqFault::Dropped);
+        if injected {
+            // as if the kernel dropped one more
+            self.dropped_seen =
+                self.dropped_seen.wrapping_sub(1);
+        }
+        let dropped =
+            unsafe { &*self.kdropped }.load(Relaxed);
+        let newly_dropped =
+            dropped.wrapping_sub(self.dropped_seen);
+        self.dropped_seen = dropped;
+        debug_assert!(
+            injected || newly_dropped == 0,
+            "the kernel dropped {} invalid SQEs from \
+             the submission queue",
+            newly_dropped,
         );
-        u64::from(submitted)
+        let res = flush_res.and_then(|()| {
+            if newly_dropped != 0 {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "the kernel dropped {} invalid \
+                         SQEs from the submission queue",
+                        newly_dropped,
+                    ),
+                ))
+            } else {
+                Ok(())
+            }
+        });
+
+        (u64::from(submitted), res)
+    }
+}
+
+/// Appends the positions of `units` to `order`,
+/// sorted by their keys, leaving `units` empty.
+fn sort_units(
+    units: &mut Vec<((u8, i32), Range<u32>)>,
+    order: &mut Vec<u32>,
+) {
+    units.sort_by_key(|(key, _)| *key);
+    for (_, unit) in units.drain(..) {
+        order.extend(unit);
     }
 }
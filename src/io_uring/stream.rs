@@ -0,0 +1,86 @@
+use std::os::unix::io::IntoRawFd;
+
+use super::*;
+
+/// A `TcpStream` that is shut down and closed
+/// through the ring when it's dropped, which keeps
+/// connection teardown off of the thread that
+/// drops it. Created by `Rio::stream`, and derefs
+/// to the `TcpStream` for everything else.
+///
+/// The teardown is best-effort: it's submitted
+/// on drop, but nothing waits for it or reports
+/// its errors. Use `into_inner` to get the
+/// `TcpStream` back and close it normally.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::TcpListener;
+///
+/// let ring = rio::new().unwrap();
+/// let listener =
+///     TcpListener::bind("127.0.0.1:6666").unwrap();
+///
+/// let stream = ring.accept(&listener).wait().unwrap();
+/// let stream = ring.stream(stream);
+/// ring.send(&*stream, b"bye").wait().unwrap();
+///
+/// // shut down and closed by the ring
+/// drop(stream);
+/// ```
+#[derive(Debug)]
+pub struct RioStream {
+    rio: Rio,
+    // only `None` once it's been dropped or
+    // taken back by `into_inner`
+    stream: Option<TcpStream>,
+}
+
+impl RioStream {
+    pub(crate) const fn new(
+        rio: Rio,
+        stream: TcpStream,
+    ) -> RioStream {
+        RioStream {
+            rio,
+            stream: Some(stream),
+        }
+    }
+
+    /// Returns the wrapped `TcpStream`, which is
+    /// then no longer closed through the ring.
+    pub fn into_inner(mut self) -> TcpStream {
+        self.stream.take().unwrap()
+    }
+}
+
+impl std::ops::Deref for RioStream {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for RioStream {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl AsRawFd for RioStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_ref().unwrap().as_raw_fd()
+    }
+}
+
+impl Drop for RioStream {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            self.rio.shutdown_close_detached(
+                stream.into_raw_fd(),
+            );
+        }
+    }
+}
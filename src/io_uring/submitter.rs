@@ -0,0 +1,368 @@
+use super::*;
+
+/// How many times to spin while waiting for the
+/// SQPOLL thread to make room, before yielding.
+const SQ_POLL_SPINS: u32 = 64;
+
+/// How often to check whether the SQPOLL thread
+/// needs to be woken up again while waiting for
+/// it to make room, just in case.
+const SQ_POLL_RECHECK: u32 = 1024;
+
+/// Owns the submission queue, and tracks how many
+/// SQEs have been loaded into it and how many of
+/// those have been submitted to the kernel.
+///
+/// This is shared between the `Uring`, which
+/// submits new operations, and the `Cq` reaper,
+/// which resubmits operations that are retried.
+#[derive(Debug)]
+pub(crate) struct Submitter {
+    sq: Mutex<Sq>,
+    flags: u32,
+    ring_fd: i32,
+    loaded: AtomicU64,
+    submitted: AtomicU64,
+    // see `Config::submit_batch_min`
+    batch_min: u64,
+    batch_timeout: Duration,
+    // when `submit_batched` first held back what
+    // is in the queue now
+    deferred_since: Mutex<Option<Instant>>,
+    metrics: Arc<Metrics>,
+    // the only thread that may submit, with
+    // `Config::single_issuer`
+    issuer: Option<std::thread::ThreadId>,
+}
+
+#[allow(unsafe_code)]
+unsafe impl Send for Submitter {}
+
+#[allow(unsafe_code)]
+unsafe impl Sync for Submitter {}
+
+impl Submitter {
+    pub(crate) fn new(
+        sq: Sq,
+        flags: u32,
+        ring_fd: i32,
+        batch_min: usize,
+        batch_timeout: Duration,
+        metrics: Arc<Metrics>,
+    ) -> Submitter {
+        Submitter {
+            sq: Mutex::new(sq),
+            flags,
+            ring_fd,
+            loaded: 0.into(),
+            submitted: 0.into(),
+            batch_min: u64::try_from(batch_min).unwrap(),
+            batch_timeout,
+            deferred_since: Mutex::new(None),
+            metrics,
+            issuer: if flags & IORING_SETUP_SINGLE_ISSUER
+                != 0
+            {
+                Some(std::thread::current().id())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.sq.lock().unwrap().capacity()
+    }
+
+    pub(crate) fn sq_poll_wakeups(&self) -> u64 {
+        self.sq.lock().unwrap().sq_poll_wakeups()
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn inject_fault(&self, fault: SqFault) {
+        self.sq.lock().unwrap().inject_fault(fault);
+    }
+
+    pub(crate) fn dump_state(&self, state: &mut RingState) {
+        self.sq.lock().unwrap().dump_state(state);
+    }
+
+    /// Acquires an SQE, fills it in using `f`, and
+    /// returns the id that must be passed to
+    /// `ensure_submitted` to ensure that it has been
+    /// submitted to the kernel. Fails, without
+    /// calling `f`, if the submission queue is full
+    /// and submitting to make room fails.
+    pub(crate) fn push<F>(&self, f: F) -> io::Result<u64>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+    {
+        let mut sq = {
+            let _get_sq_mu =
+                self.metrics.measure(|m| &m.sq_mu_wait);
+            self.sq.lock().unwrap()
+        };
+        let _hold_sq_mu =
+            self.metrics.measure(|m| &m.sq_mu_hold);
+
+        {
+            let _get_sqe =
+                self.metrics.measure(|m| &m.get_sqe);
+            self.make_room(&mut sq, 1)?;
+        }
+
+        let sqe_id = self.loaded.fetch_add(1, Release) + 1;
+        self.metrics.add_ops(1);
+
+        f(sq.try_get_sqe(self.flags).unwrap());
+
+        Ok(sqe_id)
+    }
+
+    /// Like `push`, but fills in two adjacent SQEs,
+    /// so that the first may be linked to the
+    /// second without another thread's SQE ending
+    /// up in between them. Returns the id of the
+    /// second SQE.
+    pub(crate) fn push_pair<F, G>(
+        &self,
+        f: F,
+        g: G,
+    ) -> io::Result<u64>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+        G: FnOnce(&mut io_uring_sqe),
+    {
+        let mut sq = {
+            let _get_sq_mu =
+                self.metrics.measure(|m| &m.sq_mu_wait);
+            self.sq.lock().unwrap()
+        };
+        let _hold_sq_mu =
+            self.metrics.measure(|m| &m.sq_mu_hold);
+
+        {
+            let _get_sqe =
+                self.metrics.measure(|m| &m.get_sqe);
+            self.make_room(&mut sq, 2)?;
+        }
+
+        let sqe_id = self.loaded.fetch_add(2, Release) + 2;
+        self.metrics.add_ops(2);
+
+        f(sq.try_get_sqe(self.flags).unwrap());
+        g(sq.try_get_sqe(self.flags).unwrap());
+
+        Ok(sqe_id)
+    }
+
+    /// Like `push_pair`, but fills in `count`
+    /// adjacent SQEs, calling `f` with the index
+    /// of each one in the chain. Returns the id of
+    /// the last SQE.
+    pub(crate) fn push_chain<F>(
+        &self,
+        count: usize,
+        mut f: F,
+    ) -> io::Result<u64>
+    where
+        F: FnMut(usize, &mut io_uring_sqe),
+    {
+        let mut sq = {
+            let _get_sq_mu =
+                self.metrics.measure(|m| &m.sq_mu_wait);
+            self.sq.lock().unwrap()
+        };
+        let _hold_sq_mu =
+            self.metrics.measure(|m| &m.sq_mu_hold);
+
+        {
+            let _get_sqe =
+                self.metrics.measure(|m| &m.get_sqe);
+            self.make_room(&mut sq, count)?;
+        }
+
+        let count64 = u64::try_from(count).unwrap();
+        let sqe_id =
+            self.loaded.fetch_add(count64, Release)
+                + count64;
+        self.metrics.add_ops(count64);
+
+        for i in 0..count {
+            f(i, sq.try_get_sqe(self.flags).unwrap());
+        }
+
+        Ok(sqe_id)
+    }
+
+    /// Submits everything in `sq`, which must be
+    /// locked, returning how many SQEs that was.
+    /// Whatever was handed to the kernel is counted
+    /// as submitted even if this fails, so that
+    /// waiting on it doesn't try to submit it again.
+    fn submit_locked(
+        &self,
+        sq: &mut Sq,
+    ) -> io::Result<u64> {
+        if let Some(issuer) = self.issuer {
+            // the kernel would fail this with EEXIST,
+            // which would be much harder to make sense of
+            assert_eq!(
+                std::thread::current().id(),
+                issuer,
+                "a ring started with Config::single_issuer \
+                 may only submit from the thread that \
+                 started it",
+            );
+        }
+        let (submitted, res) =
+            sq.submit_all(self.flags, self.ring_fd);
+        let _ =
+            self.submitted.fetch_add(submitted, Release);
+        *self.deferred_since.lock().unwrap() = None;
+        res.map(|()| submitted)
+    }
+
+    /// Submits until there are at least `needed`
+    /// free SQEs, failing if a submission does.
+    ///
+    /// Under SQPOLL, submitting doesn't make room
+    /// by itself. It's the kernel thread consuming
+    /// what we already published that does, and
+    /// it won't go to sleep while there's anything
+    /// left for it to consume. So after the first
+    /// submission, which wakes it up if it needs
+    /// to, we back off and wait for it, rather than
+    /// spinning on `submit_all` and risking a
+    /// wakeup `enter` for every time we look while
+    /// it's still starting back up.
+    fn make_room(
+        &self,
+        sq: &mut Sq,
+        needed: usize,
+    ) -> io::Result<()> {
+        let sq_poll = self.flags & IORING_SETUP_SQPOLL != 0;
+        let mut waited: u32 = 0;
+
+        while sq.space_left(self.flags) < needed {
+            if !sq_poll
+                || waited.is_multiple_of(SQ_POLL_RECHECK)
+            {
+                self.submit_locked(sq)?;
+            } else if waited < SQ_POLL_SPINS {
+                std::hint::spin_loop();
+            } else {
+                std::thread::yield_now();
+            }
+            waited = waited.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the SQE with `sqe_id` has been
+    /// handed to the kernel.
+    pub(crate) fn is_submitted(&self, sqe_id: u64) -> bool {
+        self.submitted.load(Acquire) >= sqe_id
+    }
+
+    pub(crate) fn ensure_submitted(
+        &self,
+        sqe_id: u64,
+    ) -> io::Result<()> {
+        let current = self.submitted.load(Acquire);
+        if current >= sqe_id {
+            return Ok(());
+        }
+        let mut sq = {
+            let _get_sq_mu =
+                self.metrics.measure(|m| &m.sq_mu_wait);
+            self.sq.lock().unwrap()
+        };
+        let _hold_sq_mu =
+            self.metrics.measure(|m| &m.sq_mu_hold);
+        let submitted = self.submit_locked(&mut sq)?;
+        let old = self.submitted.load(Acquire) - submitted;
+
+        if self.flags & IORING_SETUP_SQPOLL == 0 {
+            // we only check this if we're running in
+            // non-SQPOLL mode where we have to manually
+            // push our submissions to the kernel.
+            assert!(
+                old + submitted >= sqe_id,
+                "failed to submit our expected SQE on ensure_submitted. \
+                expected old {} + submitted {} to be >= sqe_id {}",
+                old,
+                submitted,
+                sqe_id,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Submits everything in the submission queue,
+    /// unless nothing has been loaded into it since
+    /// the last submission, in which case this
+    /// returns without taking the lock, or waking
+    /// up an idle SQPOLL thread for nothing.
+    pub(crate) fn submit_pending(&self) {
+        if self.submitted.load(Acquire)
+            >= self.loaded.load(Acquire)
+        {
+            return;
+        }
+        let _ = self.submit_all();
+    }
+
+    /// Like `submit_pending`, but with
+    /// `Config::submit_batch_min`, holds back fewer
+    /// SQEs than that, to go along with the next
+    /// ones, unless they've already been held back
+    /// for longer than `submit_batch_timeout_us`.
+    ///
+    /// Only hints to get things going may use
+    /// this. Anything that's about to block on an
+    /// SQE must make sure that it's submitted with
+    /// `ensure_submitted` or `submit_pending`.
+    pub(crate) fn submit_batched(&self) {
+        let pending = self
+            .loaded
+            .load(Acquire)
+            .saturating_sub(self.submitted.load(Acquire));
+        if pending == 0 {
+            return;
+        }
+
+        if pending < self.batch_min {
+            let mut deferred_since =
+                self.deferred_since.lock().unwrap();
+            let since = *deferred_since
+                .get_or_insert_with(Instant::now);
+            if since.elapsed() < self.batch_timeout {
+                return;
+            }
+        }
+
+        let _ = self.submit_all();
+    }
+
+    /// Submits everything in the submission queue,
+    /// returning the number of SQEs that were
+    /// submitted and the remaining free space in
+    /// the submission queue.
+    pub(crate) fn submit_all(
+        &self,
+    ) -> io::Result<(u64, usize)> {
+        let mut sq = {
+            let _get_sq_mu =
+                self.metrics.measure(|m| &m.sq_mu_wait);
+            self.sq.lock().unwrap()
+        };
+        let _hold_sq_mu =
+            self.metrics.measure(|m| &m.sq_mu_hold);
+        let submitted = self.submit_locked(&mut sq)?;
+
+        Ok((submitted, sq.space_left(self.flags)))
+    }
+}
@@ -1,41 +1,301 @@
+use std::sync::atomic::{fence, AtomicUsize};
+
 use super::*;
 
+/// The most shards that tickets are spread
+/// across, no matter how many cores there are.
+const MAX_SHARDS: usize = 16;
+
+/// Hands out the shard that each thread tries
+/// first, round-robin, so that threads mostly
+/// stay out of each other's way.
+static NEXT_HOME: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HOME: Cell<Option<usize>> =
+        const { Cell::new(None) };
+}
+
+fn home() -> usize {
+    HOME.with(|home| {
+        home.get().unwrap_or_else(|| {
+            let assigned = NEXT_HOME.fetch_add(1, Relaxed);
+            home.set(Some(assigned));
+            assigned
+        })
+    })
+}
+
+// padded to keep shards on separate cache lines
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct Shard(Mutex<Vec<usize>>);
+
 /// Exerts backpressure on submission threads
 /// to ensure that there are never more submissions
 /// in-flight than available slots in the completion
 /// queue. Normally io_uring would accept the excess,
 /// and just drop the overflowing completions.
+///
+/// Free tickets are spread across several shards,
+/// each with its own lock, and each thread starts
+/// looking in a different one, so that submitting
+/// threads don't all contend on a single lock.
+/// Threads only fall back to the shared condvar
+/// when every shard is empty.
 #[derive(Debug)]
 pub(crate) struct TicketQueue {
-    tickets: Mutex<Vec<usize>>,
+    shards: Vec<Shard>,
+    // the shard the next pushed batch starts at
+    next_push: AtomicUsize,
+    sleepers: AtomicUsize,
+    sleep: Mutex<()>,
     cv: Condvar,
+    size: usize,
+    metrics: Arc<Metrics>,
 }
 
 impl TicketQueue {
-    pub(crate) fn new(size: usize) -> TicketQueue {
-        let tickets = Mutex::new((0..size).collect());
+    pub(crate) fn new(
+        size: usize,
+        metrics: Arc<Metrics>,
+    ) -> TicketQueue {
+        let cores = std::thread::available_parallelism()
+            .map_or(1, std::num::NonZeroUsize::get);
+        let shard_count =
+            cores.min(MAX_SHARDS).min(size).max(1);
+
+        let mut shards: Vec<Shard> = (0..shard_count)
+            .map(|_| Shard::default())
+            .collect();
+        for ticket in 0..size {
+            shards[ticket % shard_count]
+                .0
+                .get_mut()
+                .unwrap()
+                .push(ticket);
+        }
+
         TicketQueue {
-            tickets,
+            shards,
+            next_push: AtomicUsize::new(0),
+            sleepers: AtomicUsize::new(0),
+            sleep: Mutex::new(()),
             cv: Condvar::new(),
+            size,
+            metrics,
+        }
+    }
+
+    /// The number of tickets that are currently
+    /// checked out by operations that have not
+    /// yet been reaped.
+    pub(crate) fn in_flight(&self) -> usize {
+        // every shard is held while counting, in
+        // order, so that a ticket that moves between
+        // shards isn't counted twice
+        let shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap())
+            .collect();
+        let free: usize = shards
+            .iter()
+            .map(|tickets| tickets.len())
+            .sum();
+        self.size.saturating_sub(free)
+    }
+
+    /// Like `in_flight`, but gives up rather than
+    /// waiting for a shard that is locked.
+    pub(crate) fn try_in_flight(&self) -> Option<usize> {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.try_lock().ok())
+            .collect::<Option<Vec<_>>>()?;
+        let free: usize = shards
+            .iter()
+            .map(|tickets| tickets.len())
+            .sum();
+        Some(self.size.saturating_sub(free))
+    }
+
+    /// The total number of tickets, whether they
+    /// are checked out or not.
+    pub(crate) const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Calls `f` on every ticket that is not
+    /// currently checked out. None of them can
+    /// be checked out until this returns.
+    pub(crate) fn for_each_free<F>(&self, mut f: F)
+    where
+        F: FnMut(usize),
+    {
+        // shards are always locked in order when
+        // more than one is held at once
+        let shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap())
+            .collect();
+        for tickets in &shards {
+            tickets.iter().copied().for_each(&mut f);
         }
     }
 
+    /// Hands back all of `new_tickets`, leaving
+    /// it empty but with its capacity intact so
+    /// that the reaper can reuse it.
     pub(crate) fn push_multi(
         &self,
-        mut new_tickets: Vec<usize>,
+        new_tickets: &mut Vec<usize>,
     ) {
-        let _ = Measure::new(&M.ticket_queue_push);
-        let mut tickets = self.tickets.lock().unwrap();
-        tickets.append(&mut new_tickets);
-        self.cv.notify_one();
+        let _ =
+            self.metrics.measure(|m| &m.ticket_queue_push);
+
+        // spread the batch over the shards, so that
+        // threads find tickets where they look first
+        let shard_count = self.shards.len();
+        let per_shard =
+            new_tickets.len().div_ceil(shard_count);
+        let start = self.next_push.fetch_add(1, Relaxed);
+        for (i, chunk) in
+            new_tickets.chunks(per_shard.max(1)).enumerate()
+        {
+            let shard =
+                &self.shards[(start + i) % shard_count];
+            shard
+                .0
+                .lock()
+                .unwrap()
+                .extend_from_slice(chunk);
+        }
+        new_tickets.clear();
+
+        // pairs with the fence in `pop`, so either
+        // a sleeper sees these tickets when it looks
+        // again, or we see it and wake it up.
+        fence(SeqCst);
+        if self.sleepers.load(SeqCst) > 0 {
+            let _sleep = self.sleep.lock().unwrap();
+            self.cv.notify_all();
+        }
     }
 
+    #[cfg(not(feature = "single_thread"))]
     pub(crate) fn pop(&self) -> usize {
-        let _ = Measure::new(&M.ticket_queue_pop);
-        let mut tickets = self.tickets.lock().unwrap();
-        while tickets.is_empty() {
-            tickets = self.cv.wait(tickets).unwrap();
+        self.pop_with(|| self.try_pop())
+    }
+
+    /// Fills `tickets` all at once, blocking until
+    /// that many are free, so that an operation
+    /// that needs several never holds some of them
+    /// while it waits for the rest, which could
+    /// leave it waiting on itself, or on another
+    /// one that's doing the same.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `size` tickets are
+    /// needed, since that many can never be free
+    /// at once.
+    #[cfg(not(feature = "single_thread"))]
+    pub(crate) fn pop_into(&self, tickets: &mut [usize]) {
+        assert!(
+            tickets.len() <= self.size,
+            "{} tickets were needed at once, but there \
+             are only {}",
+            tickets.len(),
+            self.size
+        );
+        self.pop_with(|| {
+            self.try_pop_into(tickets).then_some(())
+        });
+    }
+
+    /// Blocks until `try_pop` succeeds.
+    #[cfg(not(feature = "single_thread"))]
+    fn pop_with<T, F>(&self, mut try_pop: F) -> T
+    where
+        F: FnMut() -> Option<T>,
+    {
+        let _ =
+            self.metrics.measure(|m| &m.ticket_queue_pop);
+
+        if let Some(tickets) = try_pop() {
+            return tickets;
+        }
+
+        let mut sleep = self.sleep.lock().unwrap();
+        let _ = self.sleepers.fetch_add(1, SeqCst);
+        fence(SeqCst);
+        let tickets = loop {
+            if let Some(tickets) = try_pop() {
+                break tickets;
+            }
+            sleep = self.cv.wait(sleep).unwrap();
+        };
+        let _ = self.sleepers.fetch_sub(1, SeqCst);
+        tickets
+    }
+
+    /// Fills `tickets` if that many are free, and
+    /// takes none otherwise, without blocking.
+    pub(crate) fn try_pop_into(
+        &self,
+        tickets: &mut [usize],
+    ) -> bool {
+        // shards are always locked in order when
+        // more than one is held at once
+        let mut shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap())
+            .collect();
+        let free: usize = shards
+            .iter()
+            .map(|tickets| tickets.len())
+            .sum();
+        if free < tickets.len() {
+            return false;
+        }
+
+        let shard_count = shards.len();
+        let mut shard = home();
+        for ticket in tickets {
+            while shards[shard % shard_count].is_empty() {
+                shard += 1;
+            }
+            *ticket =
+                shards[shard % shard_count].pop().unwrap();
         }
-        tickets.pop().unwrap()
+        true
+    }
+
+    /// Takes a ticket if one is free, without
+    /// blocking for one to be handed back.
+    pub(crate) fn try_pop(&self) -> Option<usize> {
+        let shard_count = self.shards.len();
+        let home = home();
+        (0..shard_count).find_map(|i| {
+            let shard =
+                &self.shards[(home + i) % shard_count];
+            shard.0.lock().unwrap().pop()
+        })
+    }
+
+    /// Whether at least `n` tickets are free to be
+    /// taken.
+    #[cfg(feature = "single_thread")]
+    pub(crate) fn has_free(&self, n: usize) -> bool {
+        let free: usize = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap().len())
+            .sum();
+        free >= n
     }
 }
@@ -1,6 +1,18 @@
 use super::*;
 
 /// Nice bindings for the shiny new linux IO system
+///
+/// Clones of a `Rio` share one ring, which is
+/// dropped along with the last of them. See
+/// `Completion` for how that interacts with
+/// operations that are still in flight.
+///
+/// A `Rio` can't be shared across `fork`. The
+/// child inherits the ring fd and its memory,
+/// which still belong to the parent's ring, and
+/// none of the threads that serve it. A child
+/// that wants to do IO should call
+/// `post_fork_child` before anything else.
 #[derive(Debug, Clone)]
 pub struct Rio(pub(crate) Arc<Uring>);
 
@@ -12,17 +24,457 @@ impl std::ops::Deref for Rio {
     }
 }
 
+impl Rio {
+    /// Replaces a ring inherited across `fork`
+    /// with a new one, started from the same
+    /// `Config`. Call this in the child, on the
+    /// `Rio` it inherited, before using it.
+    ///
+    /// The inherited ring is abandoned without
+    /// touching its memory, which is shared with
+    /// the parent, and its fd is closed. Any
+    /// operations that were in flight at the fork
+    /// will never complete in the child, and any
+    /// other clones of the inherited `Rio` must not
+    /// be used there.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let pid = unsafe { libc::fork() };
+    /// let ring = if pid == 0 {
+    ///     ring.post_fork_child().unwrap()
+    /// } else {
+    ///     ring
+    /// };
+    /// ring.nop().wait().unwrap();
+    /// ```
+    pub fn post_fork_child(self) -> io::Result<Rio> {
+        let ring_fd = self.ring_fd;
+        let fresh = self.config.clone().start();
+
+        // dropping the inherited ring would submit
+        // a poison pill into the parent's SQ and
+        // then wait on a reaper that doesn't exist
+        // here, so it's leaked instead. it's closed
+        // only after the new ring exists, so that
+        // the new one can't reuse its fd number.
+        let _leaked = std::mem::ManuallyDrop::new(self);
+
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::close(ring_fd);
+        }
+
+        fresh
+    }
+
+    /// Wraps a connected `TcpStream`, usually one
+    /// that was just accepted, so that it's shut
+    /// down and closed through this ring when
+    /// it's dropped, instead of by a blocking
+    /// `close(2)`. See `RioStream`.
+    pub fn stream(&self, stream: TcpStream) -> RioStream {
+        RioStream::new(self.clone(), stream)
+    }
+}
+
+/// A snapshot of submission and completion
+/// activity, returned by `Uring::submit_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitStats {
+    /// The number of SQEs that were submitted
+    /// to the kernel by this call.
+    pub submitted: u64,
+    /// The number of free slots remaining in
+    /// the submission queue.
+    pub sq_space_left: usize,
+    /// The number of operations that have been
+    /// submitted but whose completions have not
+    /// yet been reaped.
+    pub in_flight: usize,
+}
+
+/// How long a batch of NOPs took to go through
+/// a ring, returned by `Uring::benchmark_nops`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    /// The number of NOPs in the batch.
+    pub ops: usize,
+    /// How long it took to push all of them onto
+    /// the submission queue.
+    pub submit_time: Duration,
+    /// How long it took from then until all of
+    /// them had completed.
+    pub complete_time: Duration,
+}
+
+impl BenchResult {
+    /// The time that the whole batch took.
+    pub fn total_time(&self) -> Duration {
+        self.submit_time + self.complete_time
+    }
+
+    /// How many NOPs went through the ring per
+    /// second, over the whole batch.
+    pub fn ops_per_sec(&self) -> u64 {
+        let nanos = self.total_time().as_nanos().max(1);
+        let ops = u128::try_from(self.ops).unwrap();
+        u64::try_from(ops * 1_000_000_000 / nanos)
+            .unwrap_or(u64::MAX)
+    }
+}
+
+/// A snapshot of the raw indices that rio and
+/// the kernel share through the submission and
+/// completion queues, returned by
+/// `Uring::dump_ring_state`. This is only meant
+/// for diagnosing rings that seem stuck, and
+/// the kernel keeps moving them along while it
+/// is taken, so they aren't guaranteed to be
+/// consistent with each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RingState {
+    /// The kernel's submission queue head, which
+    /// it advances as it consumes SQEs.
+    pub sq_head: u32,
+    /// The submission queue tail, advanced by rio
+    /// as it hands SQEs to the kernel.
+    pub sq_tail: u32,
+    /// The mask that turns a submission queue
+    /// index into a slot.
+    pub sq_mask: u32,
+    /// The first SQE that rio has filled in, but
+    /// not yet handed to the kernel.
+    pub sqe_head: u32,
+    /// One past the last SQE that rio has handed
+    /// out to be filled in.
+    pub sqe_tail: u32,
+    /// The number of invalid SQEs that the kernel
+    /// has skipped over.
+    pub sq_dropped: u32,
+    /// The completion queue head, advanced by rio
+    /// as it reaps CQEs.
+    pub cq_head: u32,
+    /// The completion queue tail, advanced by the
+    /// kernel as it posts CQEs.
+    pub cq_tail: u32,
+    /// The mask that turns a completion queue
+    /// index into a slot.
+    pub cq_mask: u32,
+    /// The number of CQEs that the kernel couldn't
+    /// post because the completion queue was full.
+    pub cq_overflow: u32,
+}
+
+/// A way for the submission queue to be broken,
+/// that `Uring::inject_sq_fault` makes the next
+/// submission run into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqFault {
+    /// The tail moves while rio is publishing new
+    /// SQEs, as if someone else had written to it.
+    TailMoved,
+    /// The kernel's count of dropped invalid SQEs
+    /// goes up by one.
+    Dropped,
+}
+
+thread_local! {
+    /// The ring fd and personality that SQEs
+    /// filled on this thread are tagged with,
+    /// set by `Uring::with_personality`.
+    static PERSONALITY: Cell<Option<(i32, u16)>> =
+        const { Cell::new(None) };
+}
+
+/// Restores the previous personality, even if
+/// the closure passed to `with_personality`
+/// panics.
+struct PersonalityGuard(Option<(i32, u16)>);
+
+impl Drop for PersonalityGuard {
+    fn drop(&mut self) {
+        PERSONALITY.with(|p| p.set(self.0));
+    }
+}
+
+/// A buffer that a read has filled some prefix
+/// of. Returned by `Uring::read_at_prefix`, so
+/// that only the bytes that were actually read
+/// are handed out as such.
+#[derive(Debug)]
+pub struct FilledBuf<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> FilledBuf<'a> {
+    /// The bytes that were read.
+    pub fn filled(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// The bytes that were read, mutably.
+    pub fn filled_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..self.len]
+    }
+
+    /// The rest of the buffer, after a short read,
+    /// which still holds whatever was in it before.
+    pub fn unfilled(&mut self) -> &mut [u8] {
+        &mut self.buf[self.len..]
+    }
+
+    /// The number of bytes that were read.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing was read, like at the end
+    /// of a file.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The whole buffer, including the unfilled
+    /// part.
+    pub const fn into_inner(self) -> &'a mut [u8] {
+        self.buf
+    }
+}
+
+/// Where a vectored read stopped, returned by
+/// `Uring::read_vectored_fill_at`, so that a
+/// short read can be resumed from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectoredStop {
+    /// The total number of bytes that were read.
+    pub read: usize,
+    /// The index of the first buffer that wasn't
+    /// filled, or the number of buffers if they
+    /// all were.
+    pub index: usize,
+    /// How many bytes were read into the buffer
+    /// at `index`, which is where to resume.
+    pub offset: usize,
+}
+
+impl VectoredStop {
+    fn locate(lens: &[usize], read: usize) -> VectoredStop {
+        let mut left = read;
+        for (index, len) in lens.iter().enumerate() {
+            if left < *len {
+                return VectoredStop {
+                    read,
+                    index,
+                    offset: left,
+                };
+            }
+            left -= len;
+        }
+        VectoredStop {
+            read,
+            index: lens.len(),
+            offset: 0,
+        }
+    }
+}
+
+/// The iovecs of a vectored operation, which
+/// the kernel may read from until it completes.
+/// They're only ever read through the pointer
+/// in the SQE.
+#[allow(dead_code)]
+struct Iovecs(Vec<libc::iovec>);
+
+#[allow(unsafe_code)]
+unsafe impl Send for Iovecs {}
+
+/// Space for a peer's address, which
+/// `Uring::accept_into` fills in. It can be
+/// reused across accepts, so that accepting
+/// doesn't need to allocate anywhere to put it.
+#[derive(Clone, Copy)]
+pub struct SocketAddrStorage {
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+}
+
+impl SocketAddrStorage {
+    /// Creates storage that has not been filled
+    /// in yet.
+    pub fn new() -> SocketAddrStorage {
+        SocketAddrStorage {
+            #[allow(unsafe_code)]
+            storage: unsafe { std::mem::zeroed() },
+            len: socklen_of::<libc::sockaddr_storage>(),
+        }
+    }
+
+    /// The address that was last stored here,
+    /// which fails with `InvalidData` if nothing
+    /// has been, or if it's not IPv4 or IPv6.
+    pub fn addr(&self) -> io::Result<SocketAddr> {
+        storage2addr(&self.storage)
+    }
+}
+
+impl Default for SocketAddrStorage {
+    fn default() -> SocketAddrStorage {
+        SocketAddrStorage::new()
+    }
+}
+
+impl std::fmt::Debug for SocketAddrStorage {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("SocketAddrStorage")
+            .field("addr", &self.addr().ok())
+            .finish()
+    }
+}
+
+/// A peer's address, converted once into the
+/// form that the kernel takes, so that it can
+/// be passed to `Uring::send_to_resolved` over
+/// and over again, for instance to keep
+/// replying to the same peer, without being
+/// converted and boxed for every datagram like
+/// `Uring::send_to` does.
+#[derive(Clone, Copy)]
+pub struct ResolvedAddr {
+    storage: libc::sockaddr_storage,
+    len: libc::socklen_t,
+}
+
+impl ResolvedAddr {
+    /// Converts `addr`, which may be either IPv4
+    /// or IPv6.
+    pub fn new(addr: &SocketAddr) -> ResolvedAddr {
+        let (storage, len) = addr2storage(addr);
+        ResolvedAddr { storage, len }
+    }
+
+    /// The address that this was created from.
+    pub fn addr(&self) -> SocketAddr {
+        storage2addr(&self.storage)
+            .expect("ResolvedAddr is always IPv4 or IPv6")
+    }
+}
+
+impl From<SocketAddr> for ResolvedAddr {
+    fn from(addr: SocketAddr) -> ResolvedAddr {
+        ResolvedAddr::new(&addr)
+    }
+}
+
+impl std::fmt::Debug for ResolvedAddr {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_tuple("ResolvedAddr")
+            .field(&self.addr())
+            .finish()
+    }
+}
+
+/// Whether a read was served from the page
+/// cache, as far as rio can tell. Returned by
+/// `Uring::read_at_cache_hint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheHint {
+    /// The read completed while it was being
+    /// submitted, so it was most likely served
+    /// from the page cache.
+    Cached,
+    /// The read was still running once it had
+    /// been submitted, so it most likely had to
+    /// go to the device.
+    Uncached,
+    /// There's no way to tell, because the ring
+    /// uses `SQPOLL`, so reads aren't submitted
+    /// by a call that they could complete within.
+    Unknown,
+}
+
+/// What a `Uring::timeout` resolves to once it
+/// fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expired;
+
+impl FromCqe for Expired {
+    fn from_cqe(_: io_uring_cqe) -> Expired {
+        Expired
+    }
+
+    fn from_result(
+        res: io::Result<io_uring_cqe>,
+    ) -> io::Result<Expired> {
+        match res {
+            // firing is how a timeout succeeds
+            Err(e)
+                if e.raw_os_error()
+                    == Some(libc::ETIME) =>
+            {
+                Ok(Expired)
+            }
+            other => other.map(Self::from_cqe),
+        }
+    }
+}
+
+/// Identifies a timeout submitted by
+/// `Uring::timeout`, so that it can be
+/// rescheduled with `Uring::timeout_update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutToken(u64);
+
+/// The state that the reaper's `Cq` and the
+/// `Uring` share, handed to both when a ring
+/// is started.
+#[derive(Debug, Clone)]
+pub(crate) struct RingShared {
+    pub(crate) submitter: Arc<Submitter>,
+    pub(crate) in_flight: Arc<InFlight>,
+    pub(crate) ticket_queue: Arc<TicketQueue>,
+    pub(crate) wakeup: Arc<Wakeup>,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) messages: Arc<Mutex<VecDeque<RingMessage>>>,
+}
+
 /// The top-level `io_uring` structure.
 #[derive(Debug)]
 pub struct Uring {
-    sq: Mutex<Sq>,
+    submitter: Arc<Submitter>,
     ticket_queue: Arc<TicketQueue>,
     in_flight: Arc<InFlight>,
-    flags: u32,
     ring_fd: i32,
     config: Config,
-    loaded: AtomicU64,
-    submitted: AtomicU64,
+    sq_capacity: usize,
+    cq_entries: usize,
+    buffer_groups: Mutex<HashMap<u16, Arc<BufferGroup>>>,
+    drain_guard: Option<DrainGuard>,
+    #[cfg_attr(
+        feature = "single_thread",
+        allow(dead_code)
+    )]
+    wakeup: Arc<Wakeup>,
+    metrics: Arc<Metrics>,
+    cq_peek: CqPeek,
+    messages: Arc<Mutex<VecDeque<RingMessage>>>,
+    // whether IORING_OP_READ and WRITE are
+    // supported, which came in 5.6
+    plain_rw: bool,
+    #[cfg(feature = "single_thread")]
+    cq: Mutex<Cq>,
+    #[cfg(feature = "bytes")]
+    bytes_pool: Arc<BytesPool>,
 }
 
 #[allow(unsafe_code)]
@@ -31,8 +483,36 @@ unsafe impl Send for Uring {}
 #[allow(unsafe_code)]
 unsafe impl Sync for Uring {}
 
+impl AsRawFd for Uring {
+    fn as_raw_fd(&self) -> RawFd {
+        self.ring_fd
+    }
+}
+
 impl Drop for Uring {
     fn drop(&mut self) {
+        // nothing can be waiting on what's still in
+        // flight by now, so whatever is left was
+        // detached or forgotten, and might never
+        // complete on its own, like a recv on an
+        // idle socket. kernels before 5.19 fail
+        // this with EINVAL, and there the poison
+        // pill still waits for all of it.
+        let _ = self
+            .with_sqe::<_, ()>(None, false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_ASYNC_CANCEL,
+                    -1,
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.__bindgen_anon_1.cancel_flags =
+                    IORING_ASYNC_CANCEL_ANY
+                        | IORING_ASYNC_CANCEL_ALL;
+            })
+            .wait();
+
         let poison_pill_res =
             self.with_sqe::<_, ()>(None, false, |sqe| {
                 sqe.prep_rw(
@@ -47,11 +527,14 @@ impl Drop for Uring {
             });
 
         // this waits for the NOP event to complete.
-        drop(poison_pill_res);
+        // we wait explicitly rather than dropping
+        // it, so that it's never canceled, even
+        // with `Config::cancel_on_drop` set.
+        let _ = poison_pill_res.wait();
 
         if self.config.print_profile_on_drop {
             #[cfg(not(feature = "no_metrics"))]
-            M.print_profile();
+            self.metrics.print_profile();
         }
     }
 }
@@ -59,62 +542,174 @@ impl Drop for Uring {
 impl Uring {
     pub(crate) fn new(
         config: Config,
-        flags: u32,
         ring_fd: i32,
-        sq: Sq,
-        in_flight: Arc<InFlight>,
-        ticket_queue: Arc<TicketQueue>,
+        params: &io_uring_params,
+        shared: RingShared,
+        cq_peek: CqPeek,
+        #[cfg(feature = "single_thread")] cq: Cq,
     ) -> Uring {
+        let RingShared {
+            submitter,
+            in_flight,
+            ticket_queue,
+            wakeup,
+            metrics,
+            messages,
+        } = shared;
         Uring {
-            flags,
             ring_fd,
-            sq: Mutex::new(sq),
-            config,
+            sq_capacity: submitter.capacity(),
+            cq_entries: params.cq_entries as usize,
+            submitter,
             in_flight,
             ticket_queue,
-            loaded: 0.into(),
-            submitted: 0.into(),
+            buffer_groups: Mutex::new(HashMap::new()),
+            drain_guard: if config.warn_on_drain {
+                Some(DrainGuard::new())
+            } else {
+                None
+            },
+            config,
+            wakeup,
+            metrics,
+            cq_peek,
+            messages,
+            // RW_CUR_POS came in the same release
+            plain_rw: params.features
+                & IORING_FEAT_RW_CUR_POS
+                != 0,
+            #[cfg(feature = "single_thread")]
+            cq: Mutex::new(cq),
+            #[cfg(feature = "bytes")]
+            bytes_pool: Arc::default(),
+        }
+    }
+
+    /// The number of entries in the submission
+    /// queue that the kernel actually set up,
+    /// which it rounds up to a power of two, so
+    /// it can be more than was asked for with
+    /// `Config::raw_params`.
+    pub const fn sq_capacity(&self) -> usize {
+        self.sq_capacity
+    }
+
+    /// The number of entries in the completion
+    /// queue that the kernel actually set up. By
+    /// default, it's twice `sq_capacity`. This is
+    /// also the most operations that can be
+    /// in-flight at once.
+    pub const fn cq_entries(&self) -> usize {
+        self.cq_entries
+    }
+
+    /// The number of times that this ring has
+    /// had to wake up its SQPOLL kernel thread
+    /// because it went idle. If this grows
+    /// quickly, `Config::sq_poll_idle` may be too
+    /// short for the workload. Always 0 when not
+    /// using `Config::sq_poll`.
+    pub fn sq_poll_wakeups(&self) -> u64 {
+        self.submitter.sq_poll_wakeups()
+    }
+
+    /// A snapshot of this ring's own activity,
+    /// which is tracked separately from that of
+    /// any other ring in the process.
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// This ring's metrics in the Prometheus text
+    /// exposition format, ready to be served from
+    /// a scrape endpoint. Latencies are reported
+    /// as summaries named like `rio_wait_seconds`,
+    /// labeled with the `stage` (`sq`, `cq` or
+    /// `wait`) that they measure.
+    #[cfg(feature = "prometheus")]
+    pub fn metrics_prometheus(&self) -> String {
+        self.metrics.prometheus()
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Touch the bookkeeping memory that every
+    /// in-flight operation uses, and submit and
+    /// reap a full submission queue's worth of
+    /// NOPs, so that none of this adds latency
+    /// to the first real operations. The rings
+    /// themselves are already populated when
+    /// they are mapped.
+    ///
+    /// This blocks while there are fewer than
+    /// `sq_capacity` operations' worth of room
+    /// left in the completion queue.
+    pub fn prewarm(&self) -> io::Result<()> {
+        self.ticket_queue.for_each_free(|ticket| {
+            self.in_flight.prewarm(ticket)
+        });
+
+        let nops: Vec<_> = (0..self.sq_capacity)
+            .map(|_| self.nop())
+            .collect();
+        for nop in nops {
+            nop.wait()?;
         }
+
+        Ok(())
+    }
+
+    /// The number of times that
+    /// `Config::warn_on_drain` has warned about
+    /// too many `Ordering::Drain` operations.
+    /// Always 0 when it is not set.
+    pub fn drain_warnings(&self) -> u64 {
+        self.drain_guard
+            .as_ref()
+            .map_or(0, DrainGuard::warnings)
     }
 
     pub(crate) fn ensure_submitted(
         &self,
         sqe_id: u64,
     ) -> io::Result<()> {
-        let current = self.submitted.load(Acquire);
-        if current >= sqe_id {
-            return Ok(());
-        }
-        let mut sq = {
-            let _get_sq_mu = Measure::new(&M.sq_mu_wait);
-            self.sq.lock().unwrap()
-        };
-        let _hold_sq_mu = Measure::new(&M.sq_mu_hold);
-        let submitted =
-            sq.submit_all(self.flags, self.ring_fd);
-        let old =
-            self.submitted.fetch_add(submitted, Release);
-
-        if self.flags & IORING_SETUP_SQPOLL == 0 {
-            // we only check this if we're running in
-            // non-SQPOLL mode where we have to manually
-            // push our submissions to the kernel.
-            assert!(
-                old + submitted >= sqe_id,
-                "failed to submit our expected SQE on ensure_submitted. \
-                expected old {} + submitted {} to be >= sqe_id {}",
-                old,
-                submitted,
-                sqe_id,
-            );
-        }
+        self.submitter.ensure_submitted(sqe_id)
+    }
 
-        Ok(())
+    /// Like `ensure_submitted`, for when whatever
+    /// the SQE uses is about to stop being
+    /// borrowed, so it has to have reached the
+    /// kernel by the time this returns. Failures
+    /// are handed to `Config::drop_error_handler`
+    /// and submitting is tried again until then.
+    pub(crate) fn ensure_submitted_for_drop(
+        &self,
+        sqe_id: u64,
+    ) {
+        while let Err(e) = self.ensure_submitted(sqe_id) {
+            if let Some(handler) = self.drop_error_handler()
+            {
+                handler(e);
+            }
+            if self.submitter.is_submitted(sqe_id) {
+                return;
+            }
+            std::thread::yield_now();
+        }
     }
 
     /// Asynchronously accepts a `TcpStream` from
     /// a provided `TcpListener`.
     ///
+    /// The listener is best left in blocking mode.
+    /// Depending on the kernel, accepts on a
+    /// non-blocking one may fail with `EAGAIN`,
+    /// which is an error of kind `WouldBlock`,
+    /// whenever there's no connection pending,
+    /// unless `Config::retry_accept_eagain` is set.
+    ///
     /// # Warning
     ///
     /// This only becomes usable on linux kernels
@@ -134,6 +729,103 @@ impl Uring {
         })
     }
 
+    /// Queues up to `max` accepts on the same
+    /// listener, and submits them all at once, so
+    /// that a burst of connections is accepted in
+    /// parallel, rather than one per round trip.
+    /// Unlike multishot accept, this works on
+    /// every kernel that `accept` does.
+    ///
+    /// Each `Completion` resolves as soon as it is
+    /// handed a connection, so ones that go beyond
+    /// what is pending keep waiting for more to
+    /// arrive. Each one holds a ticket until then,
+    /// so `max` is capped at the depth of the
+    /// submission queue.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let listener =
+    ///     std::net::TcpListener::bind("127.0.0.1:0")
+    ///         .unwrap();
+    ///
+    /// for accept in ring.accept_batch(&listener, 16) {
+    ///     let stream = accept.wait().unwrap();
+    ///     println!("accepted {:?}", stream.peer_addr());
+    /// }
+    /// ```
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.5 and up.
+    pub fn accept_batch<'a>(
+        &'a self,
+        tcp_listener: &'a TcpListener,
+        max: usize,
+    ) -> Vec<Completion<'a, TcpStream>> {
+        let accepts = (0..max.min(self.sq_capacity))
+            .map(|_| self.accept(tcp_listener))
+            .collect();
+        self.submitter.submit_pending();
+        accepts
+    }
+
+    /// Accepts a `TcpStream` like `accept`, also
+    /// writing the peer's address into `addr`,
+    /// which is borrowed until the returned
+    /// `Completion` is done, and can then be
+    /// reused for the next accept.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let listener =
+    ///     std::net::TcpListener::bind("127.0.0.1:0")
+    ///         .unwrap();
+    /// let mut addr = rio::SocketAddrStorage::new();
+    ///
+    /// loop {
+    ///     let stream = ring
+    ///         .accept_into(&listener, &mut addr)
+    ///         .wait()
+    ///         .unwrap();
+    ///     println!("accepted {:?}", addr.addr());
+    /// }
+    /// ```
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.5 and up.
+    pub fn accept_into<'a>(
+        &'a self,
+        tcp_listener: &'a TcpListener,
+        addr: &'a mut SocketAddrStorage,
+    ) -> Completion<'a, TcpStream> {
+        // the kernel reads how much space there is
+        // from `len`, and then overwrites it with
+        // the length of the address
+        addr.len = socklen_of::<libc::sockaddr_storage>();
+        let storage_ptr: *mut libc::sockaddr_storage =
+            &mut addr.storage;
+        let len_ptr: *mut libc::socklen_t = &mut addr.len;
+
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_ACCEPT,
+                tcp_listener.as_raw_fd(),
+                0,
+                len_ptr as u64,
+                Ordering::None,
+            );
+            sqe.addr = storage_ptr as u64;
+        })
+    }
+
     /// Asynchronously connects a `TcpStream` from
     /// a provided `SocketAddr`.
     ///
@@ -144,7 +836,7 @@ impl Uring {
     pub fn connect<'a, F>(
         &'a self,
         socket: &'a F,
-        address: &std::net::SocketAddr,
+        address: &SocketAddr,
         order: Ordering,
     ) -> Completion<'a, ()>
     where
@@ -181,7 +873,7 @@ impl Uring {
     ) -> Completion<'a, usize>
     where
         F: AsRawFd,
-        B: 'a + AsIoVec,
+        B: 'a + ?Sized + AsIoVec,
     {
         self.send_ordered(stream, iov, Ordering::None)
     }
@@ -206,7 +898,7 @@ impl Uring {
     ) -> Completion<'a, usize>
     where
         F: AsRawFd,
-        B: 'a + AsIoVec,
+        B: 'a + ?Sized + AsIoVec,
     {
         let iov = iov.into_new_iovec();
 
@@ -223,526 +915,4793 @@ impl Uring {
         })
     }
 
-    /// Receive data from the target socket
-    /// or file-like destination, and place
-    /// it in the given buffer.
+    /// Sends `header` on `socket`, followed by
+    /// `len` bytes of `file` starting at `offset`,
+    /// as a single chain of linked operations, like
+    /// `sendfile(2)` with a header in front.
     ///
-    /// Returns the length that was successfully
-    /// read.
+    /// `splice(2)` needs a pipe on one side or the
+    /// other, so the file is spliced into a pipe
+    /// that is created for this call, and from
+    /// there into the socket. The pipe is grown to
+    /// hold all `len` bytes, which fails for more
+    /// than `/proc/sys/fs/pipe-max-size` bytes (1MiB
+    /// by default) without `CAP_SYS_RESOURCE`, so
+    /// bigger files need to be sent in pieces.
+    ///
+    /// Resolves to the number of bytes of the file
+    /// that were sent. If the header isn't sent in
+    /// full, or fewer than `len` bytes can be read
+    /// from the file, the rest of the chain is
+    /// canceled and this fails with `ECANCELED`.
     ///
     /// # Warning
     ///
     /// This only becomes usable on linux kernels
-    /// 5.6 and up.
-    pub fn recv<'a, F, B>(
+    /// 5.7 and up.
+    pub fn send_header_and_file<'a, S, B>(
         &'a self,
-        stream: &'a F,
-        iov: &'a B,
+        socket: &'a S,
+        header: &'a B,
+        file: &'a File,
+        offset: u64,
+        len: usize,
     ) -> Completion<'a, usize>
     where
-        F: AsRawFd,
-        B: AsIoVec + AsIoVecMut,
+        S: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
     {
-        self.recv_ordered(stream, iov, Ordering::None)
+        let header_iov = header.into_new_iovec();
+        let (pipe_out, pipe_in) = match pipe_holding(len) {
+            Ok(pipe) => pipe,
+            Err(e) => return Completion::failed(self, e),
+        };
+
+        let tickets = match self.pop_tickets::<3>() {
+            Ok(tickets) => tickets,
+            Err(e) => return Completion::failed(self, e),
+        };
+        let user_data = tickets.map(|ticket| {
+            self.in_flight.next_user_data(ticket)
+        });
+
+        // only the last op's completion is reported,
+        // and it's canceled if either of the others
+        // fails.
+        let (mut completion, filler) = pair(self);
+        completion.user_data = user_data[2];
+        for ticket in &tickets[..2] {
+            self.in_flight.insert(
+                *ticket,
+                None,
+                false,
+                None,
+                Filler::detached(),
+            );
+        }
+        self.in_flight
+            .insert(tickets[2], None, false, None, filler);
+
+        let socket_fd = socket.as_raw_fd();
+        let pipe_out_fd = pipe_out.as_raw_fd();
+        let pipe_in_fd = pipe_in.as_raw_fd();
+
+        let pushed =
+            self.submitter.push_chain(3, |i, sqe| {
+                match i {
+                    0 => {
+                        sqe.prep_rw(
+                            IORING_OP_SEND,
+                            socket_fd,
+                            header_iov.iov_len,
+                            0,
+                            Ordering::Link,
+                        );
+                        sqe.addr =
+                            header_iov.iov_base as u64;
+                        sqe.__bindgen_anon_1.msg_flags =
+                            libc::MSG_WAITALL as u32;
+                    }
+                    1 => {
+                        sqe.prep_splice(
+                            file.as_raw_fd(),
+                            offset,
+                            pipe_in_fd,
+                            len,
+                        );
+                        sqe.flags |= IOSQE_IO_LINK;
+                    }
+                    _ => sqe.prep_splice(
+                        pipe_out_fd,
+                        u64::MAX,
+                        socket_fd,
+                        len,
+                    ),
+                }
+                sqe.user_data = user_data[i];
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(tickets[i], *sqe);
+            });
+        completion.sqe_id = match pushed {
+            Ok(sqe_id) => sqe_id,
+            Err(e) => {
+                let [first, second, last] = tickets;
+                self.fail_unpushed(
+                    &[last, first, second],
+                    e,
+                );
+                return completion;
+            }
+        };
+
+        let mut keep_alive = KeepAlive::new();
+        let _ = keep_alive.hold(pipe_out);
+        let _ = keep_alive.hold(pipe_in);
+        completion.keep_alive = Some(keep_alive);
+
+        completion
     }
 
-    /// Receive data from the target socket
-    /// or file-like destination, and place
-    /// it in the given buffer.
-    ///
-    /// Returns the length that was successfully
-    /// read.
+    /// Sends the same buffer to every one of the
+    /// given sockets, returning a `Completion` for
+    /// each send, in the same order.
     ///
-    /// Accepts an `Ordering` specification.
+    /// The sends are all pushed onto the
+    /// submission queue while holding it, and
+    /// submitted with a single `io_uring_enter`,
+    /// so the whole broadcast costs one syscall.
+    /// More sockets than fit in the submission
+    /// queue at once take one per `sq_capacity`.
     ///
     /// # Warning
     ///
     /// This only becomes usable on linux kernels
     /// 5.6 and up.
-    pub fn recv_ordered<'a, F, B>(
+    pub fn broadcast<'a, B>(
         &'a self,
-        stream: &'a F,
-        iov: &'a B,
-        ordering: Ordering,
-    ) -> Completion<'a, usize>
+        sockets: &[&'a TcpStream],
+        buf: &'a B,
+    ) -> Vec<Completion<'a, usize>>
     where
-        F: AsRawFd,
-        B: AsIoVec + AsIoVecMut,
+        B: 'a + ?Sized + AsIoVec,
     {
-        let iov = iov.into_new_iovec();
+        let iov = buf.into_new_iovec();
+        let mut completions =
+            Vec::with_capacity(sockets.len());
 
-        self.with_sqe(Some(iov), true, |sqe| {
-            sqe.prep_rw(
-                IORING_OP_RECV,
-                stream.as_raw_fd(),
-                0,
-                0,
-                ordering,
+        // a chunk never needs more tickets than the
+        // submission queue has room for, so taking
+        // them all at once can't fail
+        for chunk in sockets.chunks(self.sq_capacity) {
+            let first = completions.len();
+            let mut tickets = vec![0; chunk.len()];
+            self.pop_tickets_into(&mut tickets).unwrap();
+            let mut user_data =
+                Vec::with_capacity(chunk.len());
+
+            for &ticket in &tickets {
+                let (mut completion, filler) = pair(self);
+                completion.user_data =
+                    self.in_flight.next_user_data(ticket);
+                self.in_flight.insert(
+                    ticket, None, false, None, filler,
+                );
+
+                user_data.push(completion.user_data);
+                completions.push(completion);
+            }
+
+            let pushed = self.submitter.push_chain(
+                chunk.len(),
+                |i, sqe| {
+                    sqe.prep_rw(
+                        IORING_OP_SEND,
+                        chunk[i].as_raw_fd(),
+                        iov.iov_len,
+                        0,
+                        Ordering::None,
+                    );
+                    sqe.addr = iov.iov_base as u64;
+                    sqe.user_data = user_data[i];
+                    self.apply_personality(sqe);
+                    self.in_flight
+                        .set_sqe(tickets[i], *sqe);
+                },
             );
-            sqe.len = u32::try_from(iov.iov_len).unwrap();
-        })
+            let sqe_id = match pushed {
+                Ok(sqe_id) => sqe_id,
+                Err(e) => {
+                    // along with every chunk after it
+                    let rest =
+                        sockets.len() - completions.len();
+                    for _ in 0..rest {
+                        completions.push(Completion::failed(
+                            self,
+                            io::Error::new(
+                                e.kind(),
+                                e.to_string(),
+                            ),
+                        ));
+                    }
+                    self.fail_unpushed(&tickets, e);
+                    return completions;
+                }
+            };
+
+            // they were all pushed together, so once
+            // the last is submitted, so are the rest.
+            for completion in &mut completions[first..] {
+                completion.sqe_id = sqe_id;
+            }
+
+            self.submitter.submit_pending();
+        }
+
+        completions
     }
 
-    /// Flushes all buffered writes, and associated
-    /// metadata changes.
+    /// Send an entire buffer to the target socket,
+    /// resubmitting the unsent tail of the buffer
+    /// whenever a `send` only sends part of it,
+    /// like when the socket's send buffer is full.
+    ///
+    /// Fails with `io::ErrorKind::WriteZero` if
+    /// a `send` ever sends nothing. An empty
+    /// buffer succeeds right away, without
+    /// submitting anything.
     ///
     /// # Warning
     ///
-    /// You usually don't want to do this without
-    /// linking to a previous write, because
-    /// `io_uring` will execute operations out-of-order.
-    /// Without setting a `Link` ordering on the previous
-    /// operation, or using `fsync_ordered` with
-    /// the `Drain` ordering, causing all previous
-    /// operations to complete before itself.
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn send_all<'a, F, B>(
+        &'a self,
+        stream: &'a F,
+        iov: &'a B,
+    ) -> Completion<'a, ()>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        let iovec = iov.into_new_iovec();
+        if iovec.iov_len == 0 {
+            return Completion::succeeded(self, 0);
+        }
+
+        self.with_msg_sqe(
+            None,
+            false,
+            None,
+            None,
+            Resubmit::SendAll,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SEND,
+                    stream.as_raw_fd(),
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.addr = iovec.iov_base as u64;
+                sqe.len =
+                    u32::try_from(iovec.iov_len).unwrap();
+            },
+        )
+    }
+
+    /// Receive data from the target socket
+    /// or file-like destination, and place
+    /// it in the given buffer.
     ///
-    /// Additionally, fsync does not ensure that
-    /// the file actually exists in its parent
-    /// directory. So, for new files, you must
-    /// also fsync the parent directory.
+    /// Returns the length that was successfully
+    /// read.
     ///
-    /// This does nothing for files opened in
-    /// `O_DIRECT` mode.
-    pub fn fsync<'a>(
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv<'a, F, B>(
         &'a self,
-        file: &'a File,
-    ) -> Completion<'a, ()> {
-        self.fsync_ordered(file, Ordering::None)
+        stream: &'a F,
+        iov: &'a B,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.recv_ordered(stream, iov, Ordering::None)
     }
 
-    /// Flushes all buffered writes, and associated
-    /// metadata changes.
-    ///
-    /// You probably want to
-    /// either use a `Link` ordering on a previous
-    /// write (or chain of separate writes), or
-    /// use the `Drain` ordering on this operation.
+    /// Receive data from the target socket
+    /// or file-like destination, and place
+    /// it in the given buffer.
     ///
-    /// You may pass in an `Ordering` to specify
-    /// two different optional behaviors:
+    /// Returns the length that was successfully
+    /// read.
     ///
-    /// * `Ordering::Link` causes the next
-    ///   submitted operation to wait until
-    ///   this one finishes. Useful for
-    ///   things like file copy, fsync-after-write,
-    ///   or proxies.
-    /// * `Ordering::Drain` causes all previously
-    ///   submitted operations to complete before
-    ///   this one begins.
+    /// Accepts an `Ordering` specification.
     ///
     /// # Warning
     ///
-    /// fsync does not ensure that
-    /// the file actually exists in its parent
-    /// directory. So, for new files, you must
-    /// also fsync the parent directory.
-    /// This does nothing for files opened in
-    /// `O_DIRECT` mode.
-    pub fn fsync_ordered<'a>(
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv_ordered<'a, F, B>(
         &'a self,
-        file: &'a File,
+        stream: &'a F,
+        iov: &'a B,
         ordering: Ordering,
-    ) -> Completion<'a, ()> {
-        self.with_sqe(None, false, |sqe| {
-            sqe.prep_rw(
-                IORING_OP_FSYNC,
-                file.as_raw_fd(),
-                0,
-                0,
-                ordering,
-            )
-        })
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.recv_inner(stream, iov, ordering)
     }
 
-    /// Flushes all buffered writes, and the specific
-    /// metadata required to access the data. This
-    /// will skip syncing metadata like atime.
+    /// Receive data from the target socket
+    /// and place it in the given buffer, like
+    /// `recv`, additionally reporting whether
+    /// the socket had more data immediately
+    /// available when the receive completed.
     ///
-    /// You probably want to
-    /// either use a `Link` ordering on a previous
-    /// write (or chain of separate writes), or
-    /// use the `Drain` ordering on this operation
-    /// with the `fdatasync_ordered` method.
+    /// This lets a busy connection keep
+    /// receiving in a tight loop while
+    /// `RecvResult::more_available` is set,
+    /// without needing to wait for readiness.
+    /// The same hint can be had from any other
+    /// receive with `Completion::wait_with_flags`.
     ///
     /// # Warning
     ///
-    /// fdatasync does not ensure that
-    /// the file actually exists in its parent
-    /// directory. So, for new files, you must
-    /// also fsync the parent directory.
-    /// This does nothing for files opened in
-    /// `O_DIRECT` mode.
-    pub fn fdatasync<'a>(
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up. `more_available` is only ever
+    /// set on linux kernels 5.19 and up, and will
+    /// always be `false` on older kernels.
+    pub fn recv_hinted<'a, F, B>(
         &'a self,
-        file: &'a File,
-    ) -> Completion<'a, ()> {
-        self.fdatasync_ordered(file, Ordering::None)
+        stream: &'a F,
+        iov: &'a B,
+    ) -> Completion<'a, RecvResult>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.recv_inner(stream, iov, Ordering::None)
     }
 
-    /// Flushes all buffered writes, and the specific
-    /// metadata required to access the data. This
-    /// will skip syncing metadata like atime.
+    /// Receive data from the target socket
+    /// and place it in the given buffer, like
+    /// `recv`, but give up once `timeout` has
+    /// passed without any data arriving.
     ///
-    /// You probably want to
-    /// either use a `Link` ordering on a previous
-    /// write (or chain of separate writes), or
-    /// use the `Drain` ordering on this operation.
+    /// Resolves to `Some` with the length that
+    /// was read, or `None` if it timed out. The
+    /// timeout stops the receive by canceling it,
+    /// so this is also `None` if something else,
+    /// like `cancel_fd`, canceled it first.
     ///
-    /// You may pass in an `Ordering` to specify
-    /// two different optional behaviors:
+    /// # Examples
     ///
-    /// * `Ordering::Link` causes the next
-    ///   submitted operation to wait until
-    ///   this one finishes. Useful for
-    ///   things like file copy, fsync-after-write,
-    ///   or proxies.
-    /// * `Ordering::Drain` causes all previously
-    ///   submitted operations to complete before
-    ///   this one begins.
+    /// ```no_run
+    /// use std::{net::TcpStream, time::Duration};
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let stream =
+    ///     TcpStream::connect("127.0.0.1:6666").unwrap();
+    /// let buf = &mut [0_u8; 64];
+    ///
+    /// let timeout = Duration::from_millis(100);
+    /// match ring
+    ///     .recv_timeout(&stream, buf, timeout)
+    ///     .wait()
+    ///     .unwrap()
+    /// {
+    ///     Some(n) => println!("got {} bytes", n),
+    ///     None => println!("timed out"),
+    /// }
+    /// ```
     ///
     /// # Warning
     ///
-    /// fdatasync does not ensure that
-    /// the file actually exists in its parent
-    /// directory. So, for new files, you must
-    /// also fsync the parent directory.
-    /// This does nothing for files opened in
-    /// `O_DIRECT` mode.
-    pub fn fdatasync_ordered<'a>(
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv_timeout<'a, F, B>(
         &'a self,
-        file: &'a File,
+        stream: &'a F,
+        iov: &'a B,
+        timeout: Duration,
+    ) -> OwnedCompletion<'a, Option<usize>>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let iovec = iov.into_new_iovec();
+
+        let completion =
+            self.with_timeout_sqe(timeout, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_RECV,
+                    stream.as_raw_fd(),
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.addr = iovec.iov_base as u64;
+                sqe.len =
+                    u32::try_from(iovec.iov_len).unwrap();
+            });
+
+        // the timeout stops the recv by canceling it
+        OwnedCompletion::new_fallible(completion, |res| {
+            match res {
+                Err(e)
+                    if e.raw_os_error()
+                        == Some(libc::ECANCELED) =>
+                {
+                    Ok(None)
+                }
+                other => other.map(Some),
+            }
+        })
+    }
+
+    fn recv_inner<'a, F, B, C>(
+        &'a self,
+        stream: &'a F,
+        iov: &'a B,
         ordering: Ordering,
-    ) -> Completion<'a, ()> {
-        self.with_sqe(None, false, |mut sqe| {
+    ) -> Completion<'a, C>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+        C: FromCqe,
+    {
+        let iov = iov.into_new_iovec();
+
+        self.with_sqe(None, true, |sqe| {
             sqe.prep_rw(
-                IORING_OP_FSYNC,
-                file.as_raw_fd(),
+                IORING_OP_RECV,
+                stream.as_raw_fd(),
                 0,
                 0,
                 ordering,
             );
-            sqe.flags |= IORING_FSYNC_DATASYNC;
+            sqe.addr = iov.iov_base as u64;
+            sqe.len = u32::try_from(iov.iov_len).unwrap();
         })
     }
 
-    /// Synchronizes the data associated with a range
-    /// in a file. Does not synchronize any metadata
-    /// updates, which can cause data loss if you
-    /// are not writing to a file whose metadata
-    /// has previously been synchronized.
+    /// Receive data from the target socket
+    /// into the full capacity of the given `Vec`,
+    /// which is owned by the returned
+    /// `OwnedCompletion` until the operation
+    /// is complete.
     ///
-    /// You probably want to have a prior write
-    /// linked to this, or set `Ordering::Drain`
-    /// by using `sync_file_range_ordered` instead.
+    /// Resolves to the same `Vec`, with its
+    /// length set to the number of bytes that
+    /// were received. Any previous contents of
+    /// the `Vec` are discarded.
     ///
-    /// Under the hood, this uses the "pessimistic"
-    /// set of flags:
-    /// `SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER`
-    pub fn sync_file_range<'a>(
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv_into_vec<'a, F>(
         &'a self,
-        file: &'a File,
-        offset: u64,
-        len: usize,
-    ) -> Completion<'a, ()> {
-        self.sync_file_range_ordered(
-            file,
-            offset,
-            len,
-            Ordering::None,
+        stream: &'a F,
+        mut buf: Vec<u8>,
+    ) -> OwnedCompletion<'a, Vec<u8>>
+    where
+        F: AsRawFd,
+    {
+        let ptr = buf.as_mut_ptr();
+        let capacity = buf.capacity();
+
+        let completion = self.with_sqe(None, true, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_RECV,
+                stream.as_raw_fd(),
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.addr = ptr as u64;
+            sqe.len = u32::try_from(capacity).unwrap();
+        });
+
+        OwnedCompletion::owning(
+            completion,
+            buf,
+            |len, mut vec| {
+                // the kernel initialized the first
+                // `len` bytes of the buffer for us.
+                #[allow(unsafe_code)]
+                unsafe {
+                    vec.set_len(len)
+                };
+                Ok(vec)
+            },
         )
     }
 
-    /// Synchronizes the data associated with a range
-    /// in a file. Does not synchronize any metadata
-    /// updates, which can cause data loss if you
-    /// are not writing to a file whose metadata
-    /// has previously been synchronized.
+    /// Receive a single length-prefixed frame from
+    /// the target socket, resolving to its body.
+    /// The length is the first `len_prefix_bytes`
+    /// bytes, in big-endian order, and doesn't
+    /// include the prefix itself. The body is
+    /// received into a `Vec` of exactly that
+    /// length, which is only allocated once the
+    /// prefix has arrived, and only if the length
+    /// is within `Config::max_frame_len`.
     ///
-    /// You probably want to have a prior write
-    /// linked to this, or set `Ordering::Drain`.
+    /// Both receives are resubmitted by the reaper
+    /// until they're complete, so this resolves
+    /// once, with the whole body. Fails with
+    /// `UnexpectedEof` if the peer hangs up before
+    /// the whole frame has arrived, and with
+    /// `InvalidInput` unless `len_prefix_bytes`
+    /// is between 1 and 8.
     ///
-    /// Under the hood, this uses the "pessimistic"
-    /// set of flags:
-    /// `SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER`
-    pub fn sync_file_range_ordered<'a>(
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv_framed<'a, F>(
         &'a self,
-        file: &'a File,
-        offset: u64,
-        len: usize,
-        ordering: Ordering,
-    ) -> Completion<'a, ()> {
-        self.with_sqe(None, false, |mut sqe| {
+        stream: &'a F,
+        len_prefix_bytes: usize,
+    ) -> OwnedCompletion<'a, Vec<u8>>
+    where
+        F: AsRawFd,
+    {
+        if len_prefix_bytes == 0 || len_prefix_bytes > 8 {
+            return OwnedCompletion::new(
+                Completion::failed(
+                    self,
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "len_prefix_bytes must be 1 to 8",
+                    ),
+                ),
+                |_| unreachable!(),
+            );
+        }
+
+        let mut frame = Box::new(Frame {
+            prefix: [0; 8],
+            prefix_len: len_prefix_bytes,
+            max_len: self.config.max_frame_len,
+            received: 0,
+            body: None,
+        });
+        let frame_ptr: *mut Frame = &mut *frame;
+        let prefix_ptr = frame.prefix.as_mut_ptr();
+
+        let ticket = self.pop_ticket();
+        let (mut completion, filler) = pair(self);
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        completion.user_data = user_data;
+        self.in_flight
+            .insert(ticket, None, false, None, filler);
+        self.in_flight.set_frame(ticket, frame_ptr);
+
+        let pushed = self.submitter.push(|sqe| {
             sqe.prep_rw(
-                IORING_OP_SYNC_FILE_RANGE,
-                file.as_raw_fd(),
-                len,
-                offset,
-                ordering,
+                IORING_OP_RECV,
+                stream.as_raw_fd(),
+                len_prefix_bytes,
+                0,
+                Ordering::None,
             );
-            sqe.flags |= u8::try_from(
-                // We don't use this because it causes
-                // EBADF to be thrown. Looking at
-                // linux's fs/sync.c, it seems as though
-                // it performs an identical operation
-                // as SYNC_FILE_RANGE_WAIT_AFTER.
-                // libc::SYNC_FILE_RANGE_WAIT_BEFORE |
-                libc::SYNC_FILE_RANGE_WRITE
-                    | libc::SYNC_FILE_RANGE_WAIT_AFTER,
-            )
-            .unwrap();
+            sqe.user_data = user_data;
+            sqe.addr = prefix_ptr as u64;
+            sqe.__bindgen_anon_1.msg_flags =
+                libc::MSG_WAITALL as u32;
+            self.apply_personality(sqe);
+            self.in_flight.set_sqe(ticket, *sqe);
+        });
+        match pushed {
+            Ok(sqe_id) => completion.sqe_id = sqe_id,
+            Err(e) => self.fail_unpushed(&[ticket], e),
+        }
+
+        OwnedCompletion::owning(
+            completion,
+            frame,
+            |_, mut done| {
+                let mut body = done.body.take().unwrap();
+                // the kernel initialized the first
+                // `received` bytes of the body for us.
+                #[allow(unsafe_code)]
+                unsafe {
+                    body.set_len(done.received)
+                };
+                Ok(body)
+            },
+        )
+    }
+
+    /// Receive data from the target socket into
+    /// a buffer that may not be initialized, which
+    /// avoids zeroing a large buffer before every
+    /// receive.
+    ///
+    /// Resolves to the prefix of the buffer that
+    /// the kernel filled in, which is only
+    /// available once the operation completes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::{mem::MaybeUninit, net::TcpStream};
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let stream =
+    ///     TcpStream::connect("127.0.0.1:6666").unwrap();
+    ///
+    /// let mut buf = vec![MaybeUninit::uninit(); 4096];
+    /// let received = ring
+    ///     .recv_uninit(&stream, &mut buf)
+    ///     .wait()
+    ///     .unwrap();
+    /// println!("got {} bytes", received.len());
+    /// ```
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn recv_uninit<'a, F>(
+        &'a self,
+        stream: &'a F,
+        buf: &'a mut [MaybeUninit<u8>],
+    ) -> OwnedCompletion<'a, &'a mut [u8]>
+    where
+        F: AsRawFd,
+    {
+        let ptr = buf.as_mut_ptr();
+        let len = buf.len();
+
+        let completion =
+            self.with_sqe(None, false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_RECV,
+                    stream.as_raw_fd(),
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.addr = ptr as u64;
+                sqe.len = u32::try_from(len).unwrap();
+            });
+
+        OwnedCompletion::new(completion, move |received| {
+            Ok(assume_init_prefix(buf, received))
         })
     }
 
-    /// Writes data at the provided buffer using
-    /// vectored IO. Be sure to check the returned
-    /// `io_uring_cqe`'s `res` field to see if a
-    /// short write happened. This will contain
-    /// the number of bytes written.
+    /// Receive a datagram from the target socket,
+    /// placing it in the given buffer.
     ///
-    /// Note that the file argument is generic
-    /// for anything that supports AsRawFd:
-    /// sockets, files, etc...
-    pub fn write_at<'a, F, B>(
+    /// Resolves to the length that was received,
+    /// along with the address of the peer that
+    /// sent it, which may be either IPv4 or IPv6.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.3 and up.
+    pub fn recv_from<'a, F, B>(
         &'a self,
-        file: &'a F,
+        socket: &'a F,
         iov: &'a B,
-        at: u64,
+    ) -> OwnedCompletion<'a, (usize, SocketAddr)>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        #[allow(unsafe_code)]
+        let mut storage: Box<
+            libc::sockaddr_storage,
+        > = Box::new(unsafe { std::mem::zeroed() });
+
+        let msg_name = MsgName {
+            ptr: &mut *storage,
+            len: socklen_of::<libc::sockaddr_storage>(),
+        };
+
+        let completion = self.with_msg_sqe(
+            Some(iov.into_new_iovec()),
+            true,
+            Some(msg_name),
+            None,
+            Resubmit::Never,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_RECVMSG,
+                    socket.as_raw_fd(),
+                    1,
+                    0,
+                    Ordering::None,
+                )
+            },
+        );
+
+        OwnedCompletion::new(completion, move |len| {
+            Ok((len, storage2addr(&storage)?))
+        })
+    }
+
+    /// Send a datagram from the given buffer
+    /// to the provided address, which may be
+    /// either IPv4 or IPv6.
+    ///
+    /// Returns the length that was successfully
+    /// sent.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.3 and up.
+    pub fn send_to<'a, F, B>(
+        &'a self,
+        socket: &'a F,
+        iov: &'a B,
+        addr: &SocketAddr,
+    ) -> OwnedCompletion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        let (raw_addr, len) = addr2storage(addr);
+        let mut storage = Box::new(raw_addr);
+
+        let msg_name = MsgName {
+            ptr: &mut *storage,
+            len,
+        };
+
+        let completion = self.with_msg_sqe(
+            Some(iov.into_new_iovec()),
+            true,
+            Some(msg_name),
+            None,
+            Resubmit::Never,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SENDMSG,
+                    socket.as_raw_fd(),
+                    1,
+                    0,
+                    Ordering::None,
+                )
+            },
+        );
+
+        OwnedCompletion::new(completion, move |sent| {
+            // keep the address alive until completion
+            drop(storage);
+            Ok(sent)
+        })
+    }
+
+    /// Like `send_to`, but to an address that was
+    /// converted ahead of time, which is borrowed
+    /// until the send completes, instead of being
+    /// converted and copied into a new allocation
+    /// for every call.
+    ///
+    /// Returns the length that was successfully
+    /// sent.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.3 and up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::UdpSocket;
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let socket =
+    ///     UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// let peer =
+    ///     UdpSocket::bind("127.0.0.1:0").unwrap();
+    ///
+    /// let peer_addr = peer.local_addr().unwrap();
+    /// let addr = rio::ResolvedAddr::new(&peer_addr);
+    /// for _ in 0..3 {
+    ///     ring.send_to_resolved(&socket, b"hi", &addr)
+    ///         .wait()
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn send_to_resolved<'a, F, B>(
+        &'a self,
+        socket: &'a F,
+        iov: &'a B,
+        addr: &'a ResolvedAddr,
     ) -> Completion<'a, usize>
     where
         F: AsRawFd,
-        B: 'a + AsIoVec,
+        B: 'a + ?Sized + AsIoVec,
     {
-        self.write_at_ordered(file, iov, at, Ordering::None)
+        // the kernel only reads the name of a
+        // sendmsg, so it's never written through
+        let msg_name = MsgName {
+            ptr: std::ptr::addr_of!(addr.storage)
+                .cast_mut(),
+            len: addr.len,
+        };
+
+        self.with_msg_sqe(
+            Some(iov.into_new_iovec()),
+            true,
+            Some(msg_name),
+            None,
+            Resubmit::Never,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SENDMSG,
+                    socket.as_raw_fd(),
+                    1,
+                    0,
+                    Ordering::None,
+                )
+            },
+        )
+    }
+
+    /// Sends `buf` over a unix domain socket, along
+    /// with copies of the file descriptors in
+    /// `fds`, as `SCM_RIGHTS` ancillary data. The
+    /// receiving process gets its own descriptors
+    /// for the same open files, so `fds` may be
+    /// closed here once this completes.
+    ///
+    /// `buf` should not be empty, because on a
+    /// stream socket, the descriptors travel with
+    /// the data, and nothing is sent without any.
+    /// The kernel refuses to send more than 253
+    /// descriptors at once, failing with `EINVAL`.
+    ///
+    /// Returns the length of `buf` that was
+    /// successfully sent.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.3 and up.
+    pub fn send_fds<'a, F, B>(
+        &'a self,
+        socket: &'a F,
+        fds: &[RawFd],
+        buf: &'a B,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        let mut keep_alive = KeepAlive::new();
+        let msg_control = if fds.is_empty() {
+            None
+        } else {
+            let control = scm_rights(fds);
+            // the Vec's buffer doesn't move when it's
+            // handed to the KeepAlive
+            let msg_control = MsgControl {
+                ptr: control.as_ptr().cast_mut().cast(),
+                len: control.len() * size_of::<u64>(),
+            };
+            let _ = keep_alive.hold(control);
+            Some(msg_control)
+        };
+
+        let mut completion = self.with_msg_sqe(
+            Some(buf.into_new_iovec()),
+            true,
+            None,
+            msg_control,
+            Resubmit::Never,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SENDMSG,
+                    socket.as_raw_fd(),
+                    1,
+                    0,
+                    Ordering::None,
+                )
+            },
+        );
+        completion.keep_alive = Some(keep_alive);
+        completion
+    }
+
+    /// Receives data from a unix domain socket
+    /// into `buf`, along with up to `max_fds` file
+    /// descriptors sent with it, like by
+    /// `send_fds`. They are received with
+    /// `O_CLOEXEC` set, and are closed when the
+    /// returned `OwnedFd`s are dropped.
+    ///
+    /// If more descriptors than `max_fds` were
+    /// sent, the kernel closes the rest, and only
+    /// the first `max_fds` are returned.
+    ///
+    /// Resolves to the length that was received,
+    /// along with the descriptors.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.3 and up.
+    pub fn recv_fds<'a, F, B>(
+        &'a self,
+        socket: &'a F,
+        buf: &'a B,
+        max_fds: usize,
+    ) -> OwnedCompletion<'a, (usize, Vec<OwnedFd>)>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let mut control =
+            ScmRightsSpace(scm_rights_space(max_fds));
+        // the Vec's buffer doesn't move when it's
+        // moved into the completion below
+        let msg_control = MsgControl {
+            ptr: control.0.as_mut_ptr().cast(),
+            len: control.0.len() * size_of::<u64>(),
+        };
+
+        let completion = self.with_msg_sqe(
+            Some(buf.into_new_iovec()),
+            true,
+            None,
+            Some(msg_control),
+            Resubmit::Never,
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_RECVMSG,
+                    socket.as_raw_fd(),
+                    1,
+                    0,
+                    Ordering::None,
+                );
+                sqe.__bindgen_anon_1.msg_flags =
+                    libc::MSG_CMSG_CLOEXEC as u32;
+            },
+        );
+
+        OwnedCompletion::owning(
+            completion,
+            control,
+            |received, mut space| {
+                let fds = parse_scm_rights(&mut space.0);
+                Ok((received, fds))
+            },
+        )
+    }
+
+    /// Hands `count` buffers of `size` bytes each
+    /// to the kernel as the buffer group `bgid`.
+    /// Operations like `recv_multishot` can then
+    /// have the kernel pick a buffer from the group
+    /// when data arrives, rather than tying up a
+    /// buffer for every pending receive.
+    ///
+    /// The memory is owned by this `Uring`, and
+    /// buffers are handed back to the kernel when
+    /// the `PoolBuf` they were received in is
+    /// dropped.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.7 and up.
+    pub fn provide_buffers(
+        &self,
+        bgid: u16,
+        count: u16,
+        size: usize,
+    ) -> io::Result<()> {
+        let mut groups = self.buffer_groups.lock().unwrap();
+        if groups.contains_key(&bgid) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a buffer group with this bgid \
+                 has already been provided",
+            ));
+        }
+
+        let group =
+            Arc::new(BufferGroup::new(bgid, count, size)?);
+        self.provide(&group, 0, group.count()).wait()?;
+        groups.insert(bgid, group);
+
+        Ok(())
+    }
+
+    pub(crate) fn provide<'a>(
+        &'a self,
+        group: &BufferGroup,
+        bid: u16,
+        count: u16,
+    ) -> Completion<'a, ()> {
+        let addr = group.buf_ptr(bid) as u64;
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_PROVIDE_BUFFERS,
+                i32::from(count),
+                group.buf_size(),
+                u64::from(bid),
+                Ordering::None,
+            );
+            sqe.addr = addr;
+            sqe.__bindgen_anon_2.buf_group = group.bgid;
+        })
+    }
+
+    /// Like `provide` for a single buffer, but
+    /// without submitting or waiting. The provide
+    /// goes out with whatever is submitted next,
+    /// which `PoolBuf::drop` relies on to stay
+    /// cheap.
+    pub(crate) fn provide_detached(
+        &self,
+        group: &BufferGroup,
+        bid: u16,
+    ) {
+        let ticket = if let Some(ticket) =
+            self.ticket_queue.try_pop()
+        {
+            ticket
+        } else {
+            // queued provides hold their tickets
+            // until they're submitted, so they have
+            // to go out before we wait for one.
+            self.submit_pending();
+            self.pop_ticket()
+        };
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        self.in_flight.insert(
+            ticket,
+            None,
+            false,
+            None,
+            Filler::detached(),
+        );
+
+        let addr = group.buf_ptr(bid) as u64;
+        let _ = self.submitter.push(|sqe| {
+            sqe.prep_rw(
+                IORING_OP_PROVIDE_BUFFERS,
+                1,
+                group.buf_size(),
+                u64::from(bid),
+                Ordering::None,
+            );
+            sqe.user_data = user_data;
+            sqe.addr = addr;
+            sqe.__bindgen_anon_2.buf_group = group.bgid;
+        });
+    }
+
+    /// Submits whatever is waiting in the
+    /// submission queue, if anything is.
+    pub(crate) fn submit_pending(&self) {
+        self.submitter.submit_pending();
+    }
+
+    /// Receive data from the target socket
+    /// repeatedly, using a single submission that
+    /// stays armed until the connection is shut
+    /// down, an error occurs, or the returned
+    /// `RecvMultishot` is dropped. Each receive
+    /// lands in a buffer that the kernel picks from
+    /// the group `bgid`, which must first be set up
+    /// with `Uring::provide_buffers`.
+    ///
+    /// If the group runs out of buffers, the stream
+    /// ends with an `ENOBUFS` error. Dropping the
+    /// received `PoolBuf`s promptly avoids this.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 6.0 and up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let listener =
+    ///     std::net::TcpListener::bind("127.0.0.1:6666")
+    ///         .unwrap();
+    /// let stream = ring.accept(&listener).wait().unwrap();
+    ///
+    /// ring.provide_buffers(0, 64, 4096).unwrap();
+    ///
+    /// for buf in ring.recv_multishot(&stream, 0) {
+    ///     let buf = buf.unwrap();
+    ///     println!("got {} bytes", buf.len());
+    /// }
+    /// ```
+    pub fn recv_multishot<'a, F>(
+        &'a self,
+        socket: &'a F,
+        bgid: u16,
+    ) -> RecvMultishot<'a>
+    where
+        F: AsRawFd,
+    {
+        let group = self
+            .buffer_groups
+            .lock()
+            .unwrap()
+            .get(&bgid)
+            .cloned();
+
+        let multishot = self.with_multishot_sqe(|sqe| {
+            sqe.prep_rw(
+                IORING_OP_RECV,
+                socket.as_raw_fd(),
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.flags |= IOSQE_BUFFER_SELECT;
+            sqe.ioprio = IORING_RECV_MULTISHOT;
+            sqe.__bindgen_anon_2.buf_group = bgid;
+        });
+
+        RecvMultishot::new(self, multishot, group)
+    }
+
+    /// Flushes all buffered writes, and associated
+    /// metadata changes.
+    ///
+    /// # Warning
+    ///
+    /// You usually don't want to do this without
+    /// linking to a previous write, because
+    /// `io_uring` will execute operations out-of-order.
+    /// Without setting a `Link` ordering on the previous
+    /// operation, or using `fsync_ordered` with
+    /// the `Drain` ordering, causing all previous
+    /// operations to complete before itself.
+    ///
+    /// Additionally, fsync does not ensure that
+    /// the file actually exists in its parent
+    /// directory. So, for new files, you must
+    /// also fsync the parent directory.
+    ///
+    /// This does nothing for files opened in
+    /// `O_DIRECT` mode.
+    pub fn fsync<'a>(
+        &'a self,
+        file: &'a File,
+    ) -> Completion<'a, ()> {
+        self.fsync_ordered(file, Ordering::None)
+    }
+
+    /// Flushes all buffered writes, and associated
+    /// metadata changes.
+    ///
+    /// You probably want to
+    /// either use a `Link` ordering on a previous
+    /// write (or chain of separate writes), or
+    /// use the `Drain` ordering on this operation.
+    ///
+    /// You may pass in an `Ordering` to specify
+    /// two different optional behaviors:
+    ///
+    /// * `Ordering::Link` causes the next
+    ///   submitted operation to wait until
+    ///   this one finishes. Useful for
+    ///   things like file copy, fsync-after-write,
+    ///   or proxies.
+    /// * `Ordering::Drain` causes all previously
+    ///   submitted operations to complete before
+    ///   this one begins.
+    ///
+    /// # Warning
+    ///
+    /// fsync does not ensure that
+    /// the file actually exists in its parent
+    /// directory. So, for new files, you must
+    /// also fsync the parent directory.
+    /// This does nothing for files opened in
+    /// `O_DIRECT` mode.
+    pub fn fsync_ordered<'a>(
+        &'a self,
+        file: &'a File,
+        ordering: Ordering,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_FSYNC,
+                file.as_raw_fd(),
+                0,
+                0,
+                ordering,
+            )
+        })
+    }
+
+    /// Flushes all buffered writes, and the specific
+    /// metadata required to access the data. This
+    /// will skip syncing metadata like atime.
+    ///
+    /// You probably want to
+    /// either use a `Link` ordering on a previous
+    /// write (or chain of separate writes), or
+    /// use the `Drain` ordering on this operation
+    /// with the `fdatasync_ordered` method.
+    ///
+    /// # Warning
+    ///
+    /// fdatasync does not ensure that
+    /// the file actually exists in its parent
+    /// directory. So, for new files, you must
+    /// also fsync the parent directory.
+    /// This does nothing for files opened in
+    /// `O_DIRECT` mode.
+    pub fn fdatasync<'a>(
+        &'a self,
+        file: &'a File,
+    ) -> Completion<'a, ()> {
+        self.fdatasync_ordered(file, Ordering::None)
+    }
+
+    /// Flushes all buffered writes, and the specific
+    /// metadata required to access the data. This
+    /// will skip syncing metadata like atime.
+    ///
+    /// You probably want to
+    /// either use a `Link` ordering on a previous
+    /// write (or chain of separate writes), or
+    /// use the `Drain` ordering on this operation.
+    ///
+    /// You may pass in an `Ordering` to specify
+    /// two different optional behaviors:
+    ///
+    /// * `Ordering::Link` causes the next
+    ///   submitted operation to wait until
+    ///   this one finishes. Useful for
+    ///   things like file copy, fsync-after-write,
+    ///   or proxies.
+    /// * `Ordering::Drain` causes all previously
+    ///   submitted operations to complete before
+    ///   this one begins.
+    ///
+    /// # Warning
+    ///
+    /// fdatasync does not ensure that
+    /// the file actually exists in its parent
+    /// directory. So, for new files, you must
+    /// also fsync the parent directory.
+    /// This does nothing for files opened in
+    /// `O_DIRECT` mode.
+    pub fn fdatasync_ordered<'a>(
+        &'a self,
+        file: &'a File,
+        ordering: Ordering,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |mut sqe| {
+            sqe.prep_rw(
+                IORING_OP_FSYNC,
+                file.as_raw_fd(),
+                0,
+                0,
+                ordering,
+            );
+            sqe.__bindgen_anon_1.fsync_flags =
+                u32::from(IORING_FSYNC_DATASYNC);
+        })
+    }
+
+    /// Synchronizes the data associated with a range
+    /// in a file. Does not synchronize any metadata
+    /// updates, which can cause data loss if you
+    /// are not writing to a file whose metadata
+    /// has previously been synchronized.
+    ///
+    /// You probably want to have a prior write
+    /// linked to this, or set `Ordering::Drain`
+    /// by using `sync_file_range_ordered` instead.
+    ///
+    /// Under the hood, this uses the "pessimistic"
+    /// set of flags:
+    /// `SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER`
+    pub fn sync_file_range<'a>(
+        &'a self,
+        file: &'a File,
+        offset: u64,
+        len: usize,
+    ) -> Completion<'a, ()> {
+        self.sync_file_range_ordered(
+            file,
+            offset,
+            len,
+            Ordering::None,
+        )
+    }
+
+    /// Synchronizes the data associated with a range
+    /// in a file. Does not synchronize any metadata
+    /// updates, which can cause data loss if you
+    /// are not writing to a file whose metadata
+    /// has previously been synchronized.
+    ///
+    /// You probably want to have a prior write
+    /// linked to this, or set `Ordering::Drain`.
+    ///
+    /// Under the hood, this uses the "pessimistic"
+    /// set of flags:
+    /// `SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER`
+    pub fn sync_file_range_ordered<'a>(
+        &'a self,
+        file: &'a File,
+        offset: u64,
+        len: usize,
+        ordering: Ordering,
+    ) -> Completion<'a, ()> {
+        self.sync_file_range_with_flags(
+            file,
+            offset,
+            len,
+            SyncFileRangeFlags::pessimistic(),
+            ordering,
+        )
+    }
+
+    /// Synchronizes the data associated with a range
+    /// in a file, like `sync_file_range_ordered`,
+    /// but going through only the phases that are
+    /// set in `flags`, rather than the "pessimistic"
+    /// ones.
+    pub fn sync_file_range_with_flags<'a>(
+        &'a self,
+        file: &'a File,
+        offset: u64,
+        len: usize,
+        flags: SyncFileRangeFlags,
+        ordering: Ordering,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_SYNC_FILE_RANGE,
+                file.as_raw_fd(),
+                len,
+                offset,
+                ordering,
+            );
+            sqe.__bindgen_anon_1.sync_range_flags =
+                flags.bits();
+        })
+    }
+
+    /// Makes the given range of a file durable,
+    /// along with the metadata needed to read it
+    /// back, like the file's size after an append.
+    ///
+    /// This submits a `sync_file_range` over the
+    /// range, with the "pessimistic" flags, linked
+    /// to an `fdatasync` of the whole file, in one
+    /// go so that nothing can end up between them.
+    /// Once it resolves, the guarantee is exactly
+    /// that of `fdatasync`: every write to the
+    /// file that completed before this was
+    /// submitted is durable, along with its size,
+    /// even if the machine loses power. That
+    /// includes writes outside of the range, since
+    /// Linux has no way to sync only part of a
+    /// file's data along with its metadata. The
+    /// range is written back first, so when it's
+    /// the only part of the file that is dirty,
+    /// like for an append-only log, that's all
+    /// that gets written.
+    ///
+    /// Resolves to the result of the `fdatasync`.
+    /// If the `sync_file_range` fails, that's
+    /// canceled, and this fails with `ECANCELED`.
+    ///
+    /// # Warning
+    ///
+    /// Like `fdatasync`, this doesn't ensure that a
+    /// new file exists in its parent directory, so
+    /// that must be synced separately. It also
+    /// needs prior writes to have completed, or be
+    /// linked to this, to cover them.
+    pub fn datasync_range<'a>(
+        &'a self,
+        file: &'a File,
+        offset: u64,
+        len: usize,
+    ) -> Completion<'a, ()> {
+        let [range_ticket, ticket] = match self
+            .pop_tickets()
+        {
+            Ok(tickets) => tickets,
+            Err(e) => return Completion::failed(self, e),
+        };
+        let (mut completion, filler) = pair(self);
+
+        let range_user_data =
+            self.in_flight.next_user_data(range_ticket);
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        completion.user_data = user_data;
+
+        self.in_flight.insert(
+            range_ticket,
+            None,
+            false,
+            None,
+            Filler::detached(),
+        );
+        self.in_flight
+            .insert(ticket, None, false, None, filler);
+
+        let fd = file.as_raw_fd();
+
+        let pushed = self.submitter.push_pair(
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SYNC_FILE_RANGE,
+                    fd,
+                    len,
+                    offset,
+                    Ordering::Link,
+                );
+                sqe.__bindgen_anon_1.sync_range_flags =
+                    SyncFileRangeFlags::pessimistic()
+                        .bits();
+                sqe.user_data = range_user_data;
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(range_ticket, *sqe);
+            },
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_FSYNC,
+                    fd,
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.__bindgen_anon_1.fsync_flags =
+                    u32::from(IORING_FSYNC_DATASYNC);
+                sqe.user_data = user_data;
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(ticket, *sqe);
+            },
+        );
+        match pushed {
+            Ok(sqe_id) => completion.sqe_id = sqe_id,
+            Err(e) => self.fail_unpushed(
+                &[ticket, range_ticket],
+                e,
+            ),
+        }
+
+        completion
+    }
+
+    /// Submits `first` and then `second`, with
+    /// `first` linked to `second`, so that
+    /// `second` only starts once `first` has
+    /// completed successfully. The two always go
+    /// into adjacent SQEs, so there's no way for
+    /// another operation to end up in between, or
+    /// for the link to be set on the wrong one.
+    /// This is the common case of the file copy
+    /// or write-then-read pattern, without having
+    /// to get `Ordering::Link` right by hand.
+    ///
+    /// If `first` fails, or completes with a short
+    /// read or write, `second` is canceled and
+    /// fails with `ECANCELED`. If either of them
+    /// is invalid, like an offset that is out of
+    /// range, neither is submitted, and the other
+    /// one fails with `ECANCELED` in the same way.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rio::PreppedOp;
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .open("file")
+    ///     .unwrap();
+    /// let out: &[u8] = b"hello";
+    /// let buf: &mut [u8] = &mut [0; 5];
+    ///
+    /// let (write, read) = ring.linked_pair(
+    ///     PreppedOp::write_at(&file, &out, 0),
+    ///     PreppedOp::read_at(&file, &buf, 0),
+    /// );
+    /// write.wait().unwrap();
+    /// read.wait().unwrap();
+    /// ```
+    pub fn linked_pair<'a>(
+        &'a self,
+        first: PreppedOp<'a>,
+        second: PreppedOp<'a>,
+    ) -> (Completion<'a, usize>, Completion<'a, usize>)
+    {
+        let canceled = || {
+            Completion::failed(
+                self,
+                io::Error::from_raw_os_error(
+                    libc::ECANCELED,
+                ),
+            )
+        };
+        if let Err(e) =
+            checked_end(first.at, first.iovec.iov_len)
+        {
+            return (
+                Completion::failed(self, e),
+                canceled(),
+            );
+        }
+        if let Err(e) =
+            checked_end(second.at, second.iovec.iov_len)
+        {
+            return (
+                canceled(),
+                Completion::failed(self, e),
+            );
+        }
+
+        let [first_ticket, second_ticket] =
+            match self.pop_tickets() {
+                Ok(tickets) => tickets,
+                Err(e) => {
+                    return (
+                        Completion::failed(self, e),
+                        canceled(),
+                    )
+                }
+            };
+        let (mut first_completion, first_filler) =
+            pair(self);
+        let (mut second_completion, second_filler) =
+            pair(self);
+
+        let first_user_data =
+            self.in_flight.next_user_data(first_ticket);
+        let second_user_data =
+            self.in_flight.next_user_data(second_ticket);
+        first_completion.user_data = first_user_data;
+        second_completion.user_data = second_user_data;
+
+        let first_data_ptr = self.in_flight.insert(
+            first_ticket,
+            Some(first.iovec),
+            false,
+            None,
+            first_filler,
+        );
+        let second_data_ptr = self.in_flight.insert(
+            second_ticket,
+            Some(second.iovec),
+            false,
+            None,
+            second_filler,
+        );
+
+        let pushed = self.submitter.push_pair(
+            |sqe| {
+                sqe.user_data = first_user_data;
+                sqe.addr = first_data_ptr;
+                sqe.prep_rw(
+                    first.opcode,
+                    first.fd,
+                    1,
+                    first.at,
+                    Ordering::Link,
+                );
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(first_ticket, *sqe);
+            },
+            |sqe| {
+                sqe.user_data = second_user_data;
+                sqe.addr = second_data_ptr;
+                sqe.prep_rw(
+                    second.opcode,
+                    second.fd,
+                    1,
+                    second.at,
+                    Ordering::None,
+                );
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(second_ticket, *sqe);
+            },
+        );
+        match pushed {
+            Ok(sqe_id) => {
+                first_completion.sqe_id = sqe_id;
+                second_completion.sqe_id = sqe_id;
+            }
+            Err(e) => self.fail_unpushed(
+                &[first_ticket, second_ticket],
+                e,
+            ),
+        }
+
+        (first_completion, second_completion)
+    }
+
+    /// Copies `len` bytes from `src` at `src_at` to
+    /// `dst` at `dst_at`, with a series of reads,
+    /// each linked to the write of what it read,
+    /// several of which are in flight at once.
+    /// Blocks until it's done, and returns the
+    /// number of bytes copied, which is less than
+    /// `len` if `src` ends first.
+    ///
+    /// `progress` is called with the total number
+    /// of bytes written so far, every time one of
+    /// the writes completes. That happens on the
+    /// reaper thread, which can't reap anything
+    /// else in the mean time, so it should be
+    /// quick, like updating a progress bar. Writes
+    /// may complete out of order, but the totals
+    /// only ever go up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::Arc;
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let src = std::fs::File::open("src").unwrap();
+    /// let dst = std::fs::File::create("dst").unwrap();
+    /// let len = src.metadata().unwrap().len();
+    ///
+    /// let copied = ring
+    ///     .copy(
+    ///         &src,
+    ///         0,
+    ///         &dst,
+    ///         0,
+    ///         len,
+    ///         Some(Arc::new(move |written| {
+    ///             println!("{}/{}", written, len)
+    ///         })),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn copy<S, D>(
+        &self,
+        src: &S,
+        src_at: u64,
+        dst: &D,
+        dst_at: u64,
+        len: u64,
+        progress: Option<Arc<dyn Fn(u64) + Send + Sync>>,
+    ) -> io::Result<u64>
+    where
+        S: AsRawFd,
+        D: AsRawFd,
+    {
+        const CHUNK: u64 = 64 * 1024;
+        const WINDOW: usize = 8;
+
+        // each chunk takes two tickets, and they are
+        // all taken before any are submitted
+        let window = WINDOW.min(self.cq_entries / 2).max(1);
+        let written = Arc::new(AtomicU64::new(0));
+        let track = move |write: &Completion<'_, usize>| {
+            if let Some(shared) = &progress {
+                let callback = Arc::clone(shared);
+                let total = Arc::clone(&written);
+                write.on_success(move |res| {
+                    let wrote = u64::try_from(res).unwrap();
+                    callback(
+                        total.fetch_add(wrote, Relaxed)
+                            + wrote,
+                    );
+                });
+            }
+        };
+
+        let mut copied: u64 = 0;
+        while copied < len {
+            let bufs: Vec<Vec<u8>> = (0..window)
+                .map(|i| copied + CHUNK * i as u64)
+                .take_while(|offset| *offset < len)
+                .map(|offset| {
+                    let chunk = CHUNK.min(len - offset);
+                    vec![0; usize::try_from(chunk).unwrap()]
+                })
+                .collect();
+
+            let mut offset = copied;
+            let pairs: Vec<_> = bufs
+                .iter()
+                .map(|buf| {
+                    let pair = self.linked_pair(
+                        PreppedOp::read_at(
+                            src,
+                            buf,
+                            src_at + offset,
+                        ),
+                        PreppedOp::write_at(
+                            dst,
+                            buf,
+                            dst_at + offset,
+                        ),
+                    );
+                    track(&pair.1);
+                    offset += buf.len() as u64;
+                    pair
+                })
+                .collect();
+            self.submitter.submit_pending();
+
+            for ((reading, writing), buf) in
+                pairs.into_iter().zip(&bufs)
+            {
+                let mut filled = reading.wait()?;
+                let mut done = if filled == buf.len() {
+                    writing.wait()?
+                } else {
+                    // a short read cancels the write
+                    drop(writing);
+                    0
+                };
+
+                // finish off whatever was left by a
+                // short read or write, one at a time
+                let mut pending =
+                    buf[done..filled].to_vec();
+                while done < buf.len() {
+                    if pending.is_empty() {
+                        let more =
+                            vec![0; buf.len() - filled];
+                        let got = self
+                            .read_at(
+                                src,
+                                &more,
+                                src_at
+                                    + copied
+                                    + filled as u64,
+                            )
+                            .wait()?;
+                        if got == 0 {
+                            // `src` ended here
+                            return Ok(copied + done as u64);
+                        }
+                        pending = more;
+                        pending.truncate(got);
+                        filled += got;
+                    }
+
+                    let rewrite = self.write_at(
+                        dst,
+                        &pending,
+                        dst_at + copied + done as u64,
+                    );
+                    track(&rewrite);
+                    match rewrite.wait()? {
+                        0 => {
+                            return Err(io::Error::from(
+                                io::ErrorKind::WriteZero,
+                            ))
+                        }
+                        wrote => {
+                            let _ = pending.drain(..wrote);
+                            done += wrote;
+                        }
+                    }
+                }
+                copied += buf.len() as u64;
+            }
+        }
+
+        Ok(copied)
+    }
+
+    /// Writes data at the provided buffer using
+    /// vectored IO. Be sure to check the returned
+    /// `io_uring_cqe`'s `res` field to see if a
+    /// short write happened. This will contain
+    /// the number of bytes written.
+    ///
+    /// Note that the file argument is generic
+    /// for anything that supports AsRawFd:
+    /// sockets, files, etc...
+    pub fn write_at<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        self.write_at_ordered(file, iov, at, Ordering::None)
+    }
+
+    /// Writes data at the provided buffer using
+    /// vectored IO.
+    ///
+    /// Be sure to check the returned
+    /// `io_uring_cqe`'s `res` field to see if a
+    /// short write happened. This will contain
+    /// the number of bytes written.
+    ///
+    /// You may pass in an `Ordering` to specify
+    /// two different optional behaviors:
+    ///
+    /// * `Ordering::Link` causes the next
+    ///   submitted operation to wait until
+    ///   this one finishes. Useful for
+    ///   things like file copy, fsync-after-write,
+    ///   or proxies.
+    /// * `Ordering::Drain` causes all previously
+    ///   submitted operations to complete before
+    ///   this one begins.
+    ///
+    /// Note that the file argument is generic
+    /// for anything that supports AsRawFd:
+    /// sockets, files, etc...
+    pub fn write_at_ordered<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        ordering: Ordering,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        self.rw_at(
+            IORING_OP_WRITEV,
+            file.as_raw_fd(),
+            iov.into_new_iovec(),
+            at,
+            ordering,
+            RwFlags::empty(),
+        )
+    }
+
+    /// Writes data from the provided buffer at the
+    /// given offset, like `write_at`, with `flags`
+    /// for just this write, the same as those
+    /// taken by `pwritev2(2)`. For example,
+    /// `RwFlags::APPEND | RwFlags::DSYNC` appends
+    /// the buffer to the end of the file, ignoring
+    /// `at`, and only completes once it is durable.
+    ///
+    /// # Warning
+    ///
+    /// Which flags are supported depends on the
+    /// kernel, and ones it doesn't know about fail
+    /// the write with `EOPNOTSUPP`.
+    pub fn write_at_with_flags<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        flags: RwFlags,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        self.rw_at(
+            IORING_OP_WRITEV,
+            file.as_raw_fd(),
+            iov.into_new_iovec(),
+            at,
+            Ordering::None,
+            flags,
+        )
+    }
+
+    /// Reads or writes a single buffer, with
+    /// `IORING_OP_READ` or `IORING_OP_WRITE` when
+    /// the kernel has them, and otherwise with
+    /// `opcode`, the vectored version. The plain
+    /// ones take the buffer straight from the SQE,
+    /// so they don't need an iovec kept in the
+    /// `InFlight` slot until they complete. Either
+    /// way, `flags` go in the SQE's `rw_flags`.
+    fn rw_at(
+        &self,
+        opcode: u8,
+        fd: RawFd,
+        iovec: libc::iovec,
+        at: u64,
+        ordering: Ordering,
+        flags: RwFlags,
+    ) -> Completion<'_, usize> {
+        if let Err(e) = checked_end(at, iovec.iov_len) {
+            return Completion::failed(self, e);
+        }
+
+        let plain_opcode = match opcode {
+            IORING_OP_READV => IORING_OP_READ,
+            _ => IORING_OP_WRITE,
+        };
+
+        if self.plain_rw
+            && u32::try_from(iovec.iov_len).is_ok()
+        {
+            self.with_sqe(None, false, |sqe| {
+                sqe.prep_rw(
+                    plain_opcode,
+                    fd,
+                    iovec.iov_len,
+                    at,
+                    ordering,
+                );
+                sqe.addr = iovec.iov_base as u64;
+                sqe.__bindgen_anon_1.rw_flags =
+                    flags.bits();
+            })
+        } else {
+            self.with_sqe(Some(iovec), false, |sqe| {
+                sqe.prep_rw(opcode, fd, 1, at, ordering);
+                sqe.__bindgen_anon_1.rw_flags =
+                    flags.bits();
+            })
+        }
+    }
+
+    /// Writes `iov` at `at`, after reserving disk
+    /// space for all of it with an `fallocate` of
+    /// that range. The `fallocate` is linked to the
+    /// write, and both are submitted in one go, so
+    /// nothing can end up between them, and once
+    /// the space is reserved, the write can't run
+    /// out of it partway through with `ENOSPC`.
+    /// Like any `fallocate` without flags, this
+    /// grows the file to cover the range, if it is
+    /// shorter.
+    ///
+    /// Resolves to the number of bytes written.
+    /// If the `fallocate` fails, the write is
+    /// canceled, and this fails with `ECANCELED`.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn write_at_preallocated<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: 'a + ?Sized + AsIoVec,
+    {
+        let fd = file.as_raw_fd();
+        let iovec = iov.into_new_iovec();
+
+        // fallocate rejects empty ranges, and there
+        // is nothing to reserve for them anyway
+        if iovec.iov_len == 0 {
+            return self.rw_at(
+                IORING_OP_WRITEV,
+                fd,
+                iovec,
+                at,
+                Ordering::None,
+                RwFlags::empty(),
+            );
+        }
+        if let Err(e) = checked_end(at, iovec.iov_len) {
+            return Completion::failed(self, e);
+        }
+
+        let plain = self.plain_rw
+            && u32::try_from(iovec.iov_len).is_ok();
+
+        let [fallocate_ticket, ticket] = match self
+            .pop_tickets()
+        {
+            Ok(tickets) => tickets,
+            Err(e) => return Completion::failed(self, e),
+        };
+        let (mut completion, filler) = pair(self);
+
+        let fallocate_user_data =
+            self.in_flight.next_user_data(fallocate_ticket);
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        completion.user_data = user_data;
+
+        self.in_flight.insert(
+            fallocate_ticket,
+            None,
+            false,
+            None,
+            Filler::detached(),
+        );
+        let data_ptr = self.in_flight.insert(
+            ticket,
+            if plain { None } else { Some(iovec) },
+            false,
+            None,
+            filler,
+        );
+
+        let pushed = self.submitter.push_pair(
+            |sqe| {
+                // fallocate takes its mode in `len`,
+                // and the length of the range in `addr`
+                sqe.prep_rw(
+                    IORING_OP_FALLOCATE,
+                    fd,
+                    0,
+                    at,
+                    Ordering::Link,
+                );
+                sqe.addr = iovec.iov_len as u64;
+                sqe.user_data = fallocate_user_data;
+                self.apply_personality(sqe);
+                self.in_flight
+                    .set_sqe(fallocate_ticket, *sqe);
+            },
+            |sqe| {
+                if plain {
+                    sqe.prep_rw(
+                        IORING_OP_WRITE,
+                        fd,
+                        iovec.iov_len,
+                        at,
+                        Ordering::None,
+                    );
+                    sqe.addr = iovec.iov_base as u64;
+                } else {
+                    sqe.prep_rw(
+                        IORING_OP_WRITEV,
+                        fd,
+                        1,
+                        at,
+                        Ordering::None,
+                    );
+                    sqe.addr = data_ptr;
+                }
+                sqe.user_data = user_data;
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(ticket, *sqe);
+            },
+        );
+        match pushed {
+            Ok(sqe_id) => completion.sqe_id = sqe_id,
+            Err(e) => self.fail_unpushed(
+                &[ticket, fallocate_ticket],
+                e,
+            ),
+        }
+
+        completion
+    }
+
+    /// Writes the given buffer to the file at the
+    /// given offset, first with `RWF_NOWAIT`, so
+    /// that it only goes ahead right away if it
+    /// can do so without blocking. If it would
+    /// have blocked, and fails with `EAGAIN`, it
+    /// is transparently resubmitted without
+    /// `RWF_NOWAIT`, and completes whenever the
+    /// write can go ahead instead. The buffer is
+    /// owned by the returned `OwnedCompletion`
+    /// until then, which is what makes that safe.
+    ///
+    /// Resolves to the number of bytes written,
+    /// along with the buffer, so it can be reused.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn write_at_nowait<'a, F>(
+        &'a self,
+        file: &'a F,
+        buf: Vec<u8>,
+        at: u64,
+    ) -> OwnedCompletion<'a, (usize, Vec<u8>)>
+    where
+        F: AsRawFd,
+    {
+        let completion =
+            if let Err(e) = checked_end(at, buf.len()) {
+                Completion::failed(self, e)
+            } else {
+                self.with_msg_sqe(
+                    None,
+                    false,
+                    None,
+                    None,
+                    Resubmit::WithoutNowait,
+                    |sqe| {
+                        sqe.prep_rw(
+                            IORING_OP_WRITE,
+                            file.as_raw_fd(),
+                            buf.len(),
+                            at,
+                            Ordering::None,
+                        );
+                        sqe.addr = buf.as_ptr() as u64;
+                        sqe.__bindgen_anon_1.rw_flags =
+                            RwFlags::NOWAIT.bits();
+                    },
+                )
+            };
+
+        OwnedCompletion::owning(
+            completion,
+            buf,
+            |written, vec| Ok((written, vec)),
+        )
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given file-like object, at the given offest,
+    /// using vectored IO. Be sure to check the returned
+    /// `io_uring_cqe`'s `res` field to see if a
+    /// short read happened. This will contain
+    /// the number of bytes read.
+    ///
+    /// Note that the file argument is generic
+    /// for anything that supports AsRawFd:
+    /// sockets, files, etc...
+    pub fn read_at<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.read_at_ordered(file, iov, at, Ordering::None)
+    }
+
+    /// Reads data into the provided buffer using
+    /// vectored IO. Be sure to check the returned
+    /// `io_uring_cqe`'s `res` field to see if a
+    /// short read happened. This will contain
+    /// the number of bytes read.
+    ///
+    /// You may pass in an `Ordering` to specify
+    /// two different optional behaviors:
+    ///
+    /// * `Ordering::Link` causes the next
+    ///   submitted operation to wait until
+    ///   this one finishes. Useful for
+    ///   things like file copy, fsync-after-write,
+    ///   or proxies.
+    /// * `Ordering::Drain` causes all previously
+    ///   submitted operations to complete before
+    ///   this one begins.
+    ///
+    /// Note that the file argument is generic
+    /// for anything that supports AsRawFd:
+    /// sockets, files, etc...
+    pub fn read_at_ordered<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        ordering: Ordering,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.rw_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            iov.into_new_iovec(),
+            at,
+            ordering,
+            RwFlags::empty(),
+        )
+    }
+
+    /// Reads up to `len` bytes from the given file
+    /// at the given offset into a buffer from a
+    /// pool kept by this ring, resolving to a
+    /// `Bytes` of the ones that were read. The
+    /// buffer goes back to the pool once that
+    /// `Bytes`, and every clone and slice of it,
+    /// has been dropped, so that reads don't need
+    /// to allocate a new buffer every time.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    ///
+    /// let header = ring
+    ///     .read_at_bytes(&file, 512, 0)
+    ///     .wait()
+    ///     .unwrap();
+    /// // pass the `Bytes` along, or clone it, as
+    /// // with any other
+    /// let magic = header.slice(..4);
+    /// ```
+    #[cfg(feature = "bytes")]
+    pub fn read_at_bytes<'a, F>(
+        &'a self,
+        file: &'a F,
+        len: usize,
+        at: u64,
+    ) -> OwnedCompletion<'a, bytes::Bytes>
+    where
+        F: AsRawFd,
+    {
+        let mut buf = self.bytes_pool.take(len);
+        // the Vec's buffer doesn't move when the Vec
+        // is handed to the OwnedCompletion
+        let iovec = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: len,
+        };
+        let completion = self.rw_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            iovec,
+            at,
+            Ordering::None,
+            RwFlags::empty(),
+        );
+
+        let pool = self.bytes_pool.clone();
+        OwnedCompletion::owning(
+            completion,
+            buf,
+            move |read, vec| Ok(pool.freeze(vec, read)),
+        )
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given offset, like `read_at`, with `flags`
+    /// for just this read, the same as those taken
+    /// by `preadv2(2)`, like `RwFlags::NOWAIT` or
+    /// `RwFlags::HIPRI`.
+    ///
+    /// # Warning
+    ///
+    /// Which flags are supported depends on the
+    /// kernel, and ones it doesn't know about fail
+    /// the read with `EOPNOTSUPP`.
+    pub fn read_at_with_flags<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        flags: RwFlags,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        self.rw_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            iov.into_new_iovec(),
+            at,
+            Ordering::None,
+            flags,
+        )
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given file at the given offset, like
+    /// `read_at`, but resolves to `None` when the
+    /// offset is at or past the end of the file,
+    /// rather than to a read of 0 bytes. Reads of
+    /// any other length, including short ones,
+    /// resolve to `Some`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let buf: &mut [u8] = &mut [0; 4096];
+    ///
+    /// let mut at = 0;
+    /// while let Some(read) = ring
+    ///     .read_at_exact_or_eof(&file, &buf, at)
+    ///     .wait()
+    ///     .unwrap()
+    /// {
+    ///     at += read as u64;
+    /// }
+    /// ```
+    pub fn read_at_exact_or_eof<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> OwnedCompletion<'a, Option<usize>>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let completion = self.read_at(file, iov, at);
+        OwnedCompletion::new(completion, |read| {
+            Ok(if read == 0 { None } else { Some(read) })
+        })
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given file at the given offset, like
+    /// `read_at`, and also resolves to a guess at
+    /// whether it was served from the page cache.
+    ///
+    /// `io_uring` doesn't say, but a buffered read
+    /// that hits the page cache completes before
+    /// the `io_uring_enter` call that submits it
+    /// returns, while one that misses completes
+    /// later. This submits the read right away and
+    /// checks which of those happened, so it's only
+    /// a heuristic: a miss on a fast enough device
+    /// may look like a hit, and a hit that was
+    /// punted to a kernel worker looks like a miss.
+    pub fn read_at_cache_hint<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+    ) -> OwnedCompletion<'a, (usize, CacheHint)>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let completion = self.read_at(file, iov, at);
+
+        // if submitting fails, there's no telling
+        // when the read would have completed
+        let hint = if self.config.sq_poll
+            || self
+                .ensure_submitted(completion.sqe_id)
+                .is_err()
+        {
+            CacheHint::Unknown
+        } else if completion.is_done()
+            || self.cq_peek.is_posted(completion.user_data)
+        {
+            CacheHint::Cached
+        } else {
+            CacheHint::Uncached
+        };
+
+        OwnedCompletion::new(completion, move |read| {
+            Ok((read, hint))
+        })
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given file at the given offset, like
+    /// `read_at`, and then runs `verify` on what
+    /// was read, on the reaper thread, as soon as
+    /// the read completes. That way, checking
+    /// something like a block's checksum overlaps
+    /// with other IO, instead of taking another
+    /// pass over the data after waiting.
+    ///
+    /// Resolves to the number of bytes read, or
+    /// fails with `InvalidData` if `verify`
+    /// returns false. `verify` only sees what was
+    /// read, so it's given a shorter slice after a
+    /// short read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let buf = &mut [0; 4096];
+    ///
+    /// let read = ring
+    ///     .read_at_verified(&file, buf, 0, |block| {
+    ///         block.first() == Some(&0xAA)
+    ///     })
+    ///     .wait()
+    ///     .unwrap();
+    /// ```
+    pub fn read_at_verified<'a, F, B, V>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        verify: V,
+    ) -> OwnedCompletion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+        V: 'static + Send + Fn(&[u8]) -> bool,
+    {
+        // passed to the reaper as a plain address.
+        // `on_success` runs before the completion is
+        // marked as done, so the buffer is still
+        // borrowed by it while `verify` reads it.
+        let base = iov.into_new_iovec().iov_base as usize;
+        let verified = Arc::new(AtomicBool::new(false));
+
+        let completion = self.read_at(file, iov, at);
+        let verifier = verified.clone();
+        completion.on_success(move |res| {
+            let read = usize::try_from(res).unwrap();
+            #[allow(unsafe_code)]
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    base as *const u8,
+                    read,
+                )
+            };
+            verifier.store(verify(data), SeqCst);
+        });
+
+        OwnedCompletion::new(completion, move |read| {
+            if verified.load(SeqCst) {
+                Ok(read)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "read failed verification",
+                ))
+            }
+        })
+    }
+
+    /// Reads data into the provided buffer from the
+    /// given file at the given offset, like
+    /// `read_at`, and then runs `decode` on what
+    /// was read, on the reaper thread, as soon as
+    /// the read completes, so that decompressing
+    /// a block overlaps with other IO, instead of
+    /// happening after waiting for it.
+    ///
+    /// Resolves to whatever `decode` returns.
+    /// `decode` only sees what was read, so it's
+    /// given a shorter slice after a short read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let buf = &mut [0; 4096];
+    ///
+    /// let decoded = ring
+    ///     .read_at_decoded(&file, buf, 0, |block| {
+    ///         Ok(block.iter().rev().copied().collect())
+    ///     })
+    ///     .wait()
+    ///     .unwrap();
+    /// ```
+    pub fn read_at_decoded<'a, F, B, D>(
+        &'a self,
+        file: &'a F,
+        iov: &'a B,
+        at: u64,
+        decode: D,
+    ) -> OwnedCompletion<'a, Vec<u8>>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+        D: 'static
+            + Send
+            + Fn(&[u8]) -> io::Result<Vec<u8>>,
+    {
+        // the buffer is still borrowed while
+        // `decode` reads it, like in
+        // `read_at_verified`.
+        let base = iov.into_new_iovec().iov_base as usize;
+        let decoded = Arc::new(Mutex::new(None));
+
+        let completion = self.read_at(file, iov, at);
+        let decoder = decoded.clone();
+        completion.on_success(move |res| {
+            let read = usize::try_from(res).unwrap();
+            #[allow(unsafe_code)]
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    base as *const u8,
+                    read,
+                )
+            };
+            *decoder.lock().unwrap() = Some(decode(data));
+        });
+
+        OwnedCompletion::new(completion, move |_| {
+            // `on_success` has always run by the time
+            // a successful read is waited on
+            decoded.lock().unwrap().take().unwrap()
+        })
+    }
+
+    /// Reads into the provided buffer from the
+    /// given file at the given offset, like
+    /// `read_at`, but resolves to a `FilledBuf`,
+    /// which knows which prefix of the buffer was
+    /// filled by the read, even when it was short.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let buf = &mut [0; 4096];
+    ///
+    /// let read = ring
+    ///     .read_at_prefix(&file, buf, 0)
+    ///     .wait()
+    ///     .unwrap();
+    /// println!("read {:?}", read.filled());
+    /// ```
+    pub fn read_at_prefix<'a, F>(
+        &'a self,
+        file: &'a F,
+        buf: &'a mut [u8],
+        at: u64,
+    ) -> OwnedCompletion<'a, FilledBuf<'a>>
+    where
+        F: AsRawFd,
+    {
+        let iovec = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+
+        let completion = self.rw_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            iovec,
+            at,
+            Ordering::None,
+            RwFlags::empty(),
+        );
+
+        OwnedCompletion::new(completion, move |len| {
+            Ok(FilledBuf { buf, len })
+        })
+    }
+
+    /// Reads `count` fixed-size records, starting
+    /// with the one at `start_index`, from a file
+    /// made up of records of `record_size` bytes,
+    /// into the start of `buf`. Resolves to how
+    /// many whole records were read, so a record
+    /// that was cut short, by the end of the file
+    /// or a short read, isn't counted.
+    ///
+    /// Fails with `InvalidInput` if `record_size`
+    /// is 0, if `buf` is too small for `count`
+    /// records, or if the records' offset doesn't
+    /// fit in a `u64`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let buf = &mut [0; 16 * 64];
+    ///
+    /// let read = ring
+    ///     .read_records(&file, 64, 2, 16, buf)
+    ///     .wait()
+    ///     .unwrap();
+    /// for record in buf.chunks(64).take(read) {
+    ///     println!("{:?}", record);
+    /// }
+    /// ```
+    pub fn read_records<'a, F>(
+        &'a self,
+        file: &'a F,
+        record_size: usize,
+        start_index: u64,
+        count: usize,
+        buf: &'a mut [u8],
+    ) -> OwnedCompletion<'a, usize>
+    where
+        F: AsRawFd,
+    {
+        let offset = u64::try_from(record_size)
+            .ok()
+            .and_then(|size| size.checked_mul(start_index));
+        let span = record_size
+            .checked_mul(count)
+            .filter(|len| *len <= buf.len());
+
+        let completion = match (offset, span) {
+            (Some(at), Some(len)) if record_size > 0 => {
+                let iovec = libc::iovec {
+                    iov_base: buf.as_mut_ptr().cast(),
+                    iov_len: len,
+                };
+                self.rw_at(
+                    IORING_OP_READV,
+                    file.as_raw_fd(),
+                    iovec,
+                    at,
+                    Ordering::None,
+                    RwFlags::empty(),
+                )
+            }
+            _ => Completion::failed(
+                self,
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "record_size must be non-zero, and \
+                     the records must fit in buf and \
+                     at a u64 offset",
+                ),
+            ),
+        };
+
+        OwnedCompletion::new(completion, move |read| {
+            Ok(read / record_size)
+        })
+    }
+
+    /// Reads into each of the provided buffers in
+    /// turn, from the given file at the given
+    /// offset, with a single `readv`. Like
+    /// `read_at`, the read may be short, in which
+    /// case it stops partway through one of the
+    /// buffers, and the ones after it are left
+    /// untouched.
+    pub fn read_vectored_at<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        bufs: &'a [B],
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let iovecs: Vec<libc::iovec> = bufs
+            .iter()
+            .map(AsIoVec::into_new_iovec)
+            .collect();
+        let total =
+            iovecs.iter().map(|iov| iov.iov_len).sum();
+        if let Err(e) = checked_end(at, total) {
+            return Completion::failed(self, e);
+        }
+
+        // the Vec's buffer doesn't move when it's
+        // handed to the KeepAlive
+        let addr = iovecs.as_ptr() as u64;
+        let count = iovecs.len();
+        let mut keep_alive = KeepAlive::new();
+        let _ = keep_alive.hold(Iovecs(iovecs));
+
+        let mut completion =
+            self.with_sqe(None, false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_READV,
+                    file.as_raw_fd(),
+                    count,
+                    at,
+                    Ordering::None,
+                );
+                sqe.addr = addr;
+            });
+        completion.keep_alive = Some(keep_alive);
+        completion
+    }
+
+    /// Reads data from the provided file at the
+    /// specified offset into the free space of
+    /// `ring_buf`, after the bytes that it already
+    /// holds. If that space wraps around the end
+    /// of the buffer, it is read into with a
+    /// single `readv` of both parts, the tail and
+    /// then the head.
+    ///
+    /// Resolves to the number of bytes read, which
+    /// are added to what `ring_buf` holds once
+    /// this is waited on. A full `ring_buf` has
+    /// no space to read into, so this resolves to
+    /// 0 for it, like at the end of the file.
+    pub fn read_into_ring<'a, F>(
+        &'a self,
+        file: &'a F,
+        ring_buf: &'a mut RingBuf,
+        at: u64,
+    ) -> OwnedCompletion<'a, usize>
+    where
+        F: AsRawFd,
+    {
+        let iovecs = ring_buf.free_iovecs();
+        let total = ring_buf.free();
+        let completion =
+            if let Err(e) = checked_end(at, total) {
+                Completion::failed(self, e)
+            } else {
+                // the Vec's buffer doesn't move when
+                // it's handed to the KeepAlive
+                let addr = iovecs.as_ptr() as u64;
+                let count = iovecs.len();
+                let mut keep_alive = KeepAlive::new();
+                let _ = keep_alive.hold(Iovecs(iovecs));
+
+                let mut completion =
+                    self.with_sqe(None, false, |sqe| {
+                        sqe.prep_rw(
+                            IORING_OP_READV,
+                            file.as_raw_fd(),
+                            count,
+                            at,
+                            Ordering::None,
+                        );
+                        sqe.addr = addr;
+                    });
+                completion.keep_alive = Some(keep_alive);
+                completion
+            };
+
+        OwnedCompletion::new(completion, move |read| {
+            ring_buf.fill(read);
+            Ok(read)
+        })
+    }
+
+    /// Writes each of `bufs` in turn, to the given
+    /// file at the given offset, with a single
+    /// `writev`, for code that already has its
+    /// data in `IoSlice`s. They have the same
+    /// layout as the kernel's iovecs, so they're
+    /// handed to it as they are, without being
+    /// copied anywhere. Like `write_at`, the write
+    /// may be short.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io::IoSlice;
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::create("file").unwrap();
+    /// let bufs = [
+    ///     IoSlice::new(b"header"),
+    ///     IoSlice::new(b"payload"),
+    /// ];
+    ///
+    /// let wrote = ring
+    ///     .write_io_slices_at(&file, &bufs, 0)
+    ///     .wait()
+    ///     .unwrap();
+    /// ```
+    pub fn write_io_slices_at<'a, F>(
+        &'a self,
+        file: &'a F,
+        bufs: &'a [io::IoSlice<'a>],
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+    {
+        let total = bufs.iter().map(|buf| buf.len()).sum();
+        self.io_slices_at(
+            IORING_OP_WRITEV,
+            file.as_raw_fd(),
+            bufs.as_ptr().cast(),
+            bufs.len(),
+            total,
+            at,
+        )
+    }
+
+    /// Reads into each of `bufs` in turn, from the
+    /// given file at the given offset, with a
+    /// single `readv`, like `write_io_slices_at`.
+    /// The read may be short, in which case it
+    /// stops partway through one of the buffers,
+    /// and the ones after it are left untouched.
+    pub fn read_io_slices_at<'a, F>(
+        &'a self,
+        file: &'a F,
+        bufs: &'a mut [io::IoSliceMut<'a>],
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+    {
+        let total = bufs.iter().map(|buf| buf.len()).sum();
+        self.io_slices_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            bufs.as_ptr().cast(),
+            bufs.len(),
+            total,
+            at,
+        )
+    }
+
+    /// Submits a vectored read or write of the
+    /// `count` iovecs at `iovecs`, which must stay
+    /// put until it completes, and add up to
+    /// `total` bytes.
+    fn io_slices_at(
+        &self,
+        opcode: u8,
+        fd: RawFd,
+        iovecs: *const libc::iovec,
+        count: usize,
+        total: usize,
+        at: u64,
+    ) -> Completion<'_, usize> {
+        if let Err(e) = checked_end(at, total) {
+            return Completion::failed(self, e);
+        }
+
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                opcode,
+                fd,
+                count,
+                at,
+                Ordering::None,
+            );
+            sqe.addr = iovecs as u64;
+        })
+    }
+
+    /// Like `read_vectored_at`, but resolves to
+    /// where the read stopped: which buffer it
+    /// was filling, and how far into it it got,
+    /// so that a short read can be resumed there.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let bufs = [[0_u8; 512], [0; 512], [0; 512]];
+    ///
+    /// let stop = ring
+    ///     .read_vectored_fill_at(&file, &bufs, 0)
+    ///     .wait()
+    ///     .unwrap();
+    /// if stop.index < bufs.len() {
+    ///     // resume at bufs[stop.index][stop.offset..]
+    /// }
+    /// ```
+    pub fn read_vectored_fill_at<'a, F, B>(
+        &'a self,
+        file: &'a F,
+        bufs: &'a [B],
+        at: u64,
+    ) -> OwnedCompletion<'a, VectoredStop>
+    where
+        F: AsRawFd,
+        B: AsIoVec + AsIoVecMut,
+    {
+        let lens: Vec<usize> = bufs
+            .iter()
+            .map(|buf| buf.into_new_iovec().iov_len)
+            .collect();
+        let completion =
+            self.read_vectored_at(file, bufs, at);
+
+        OwnedCompletion::new(completion, move |read| {
+            Ok(VectoredStop::locate(&lens, read))
+        })
+    }
+
+    /// Reads data from the provided file at the
+    /// specified offset into a buffer that may
+    /// not be initialized, like `read_at`.
+    ///
+    /// Resolves to the prefix of the buffer that
+    /// the kernel filled in, which is only
+    /// available once the operation completes.
+    pub fn read_uninit<'a, F>(
+        &'a self,
+        file: &'a F,
+        buf: &'a mut [MaybeUninit<u8>],
+        at: u64,
+    ) -> OwnedCompletion<'a, &'a mut [u8]>
+    where
+        F: AsRawFd,
+    {
+        let iovec = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        };
+
+        let completion = if let Err(e) =
+            checked_end(at, iovec.iov_len)
+        {
+            Completion::failed(self, e)
+        } else {
+            self.with_sqe(Some(iovec), false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_READV,
+                    file.as_raw_fd(),
+                    1,
+                    at,
+                    Ordering::None,
+                )
+            })
+        };
+
+        OwnedCompletion::new(completion, move |read| {
+            Ok(assume_init_prefix(buf, read))
+        })
+    }
+
+    /// Gives the kernel advice about how the
+    /// memory region starting at `addr` and
+    /// spanning `len` bytes will be used, like
+    /// `madvise(2)`. `advice` is one of the
+    /// `libc::MADV_*` constants, such as
+    /// `MADV_WILLNEED` or `MADV_DONTNEED`.
+    ///
+    /// # Safety
+    ///
+    /// The region must remain mapped until
+    /// the returned `Completion` has completed.
+    /// Unmapping it earlier may cause the kernel
+    /// to apply the advice to whatever is mapped
+    /// there next.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    #[allow(unsafe_code)]
+    pub unsafe fn madvise<'a>(
+        &'a self,
+        addr: *const u8,
+        len: usize,
+        advice: i32,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_MADVISE,
+                -1,
+                len,
+                0,
+                Ordering::None,
+            );
+            sqe.addr = addr as u64;
+            sqe.__bindgen_anon_1.fadvise_advice =
+                u32::try_from(advice).unwrap();
+        })
+    }
+
+    /// Reads `len` bytes from the file at the given
+    /// offset into the memory at `ptr`, like
+    /// `pread(2)`, for filling memory that isn't
+    /// owned by a Rust buffer, like an mmapped
+    /// page.
+    ///
+    /// # Safety
+    ///
+    /// The `len` bytes at `ptr` must be valid for
+    /// writes, and must not be read or written by
+    /// anything else, until the returned
+    /// `Completion` has completed. If it's mmapped,
+    /// it must stay mapped, with write permission,
+    /// until then too, or the kernel may write to
+    /// whatever is mapped there next.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    /// let mut page = vec![0_u8; 4096];
+    ///
+    /// let read = unsafe {
+    ///     ring.read_into_raw(
+    ///         &file,
+    ///         page.as_mut_ptr(),
+    ///         page.len(),
+    ///         0,
+    ///     )
+    /// }
+    /// .wait()
+    /// .unwrap();
+    /// ```
+    #[allow(unsafe_code)]
+    pub unsafe fn read_into_raw<'a, F>(
+        &'a self,
+        file: &'a F,
+        ptr: *mut u8,
+        len: usize,
+        at: u64,
+    ) -> Completion<'a, usize>
+    where
+        F: AsRawFd,
+    {
+        let iovec = libc::iovec {
+            iov_base: ptr.cast(),
+            iov_len: len,
+        };
+
+        self.rw_at(
+            IORING_OP_READV,
+            file.as_raw_fd(),
+            iovec,
+            at,
+            Ordering::None,
+            RwFlags::empty(),
+        )
+    }
+
+    /// Wraps `file` in a `Cursor`, starting at
+    /// offset 0, which tracks an offset that
+    /// advances with each `read` and `write`
+    /// like a `std::fs::File` does.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let file = std::fs::File::open("file").unwrap();
+    ///
+    /// let mut cursor = ring.cursor(&file);
+    ///
+    /// let buf = vec![0_u8; 4096];
+    /// let read = cursor.read(&buf).wait().unwrap();
+    /// assert_eq!(cursor.position(), read as u64);
+    /// ```
+    pub fn cursor<'a>(
+        &'a self,
+        file: &'a File,
+    ) -> Cursor<'a> {
+        Cursor::new(self, file, 0)
+    }
+
+    /// Waits until any of the given `events` are
+    /// ready on the file descriptor, without
+    /// reading or writing anything, like a single
+    /// call to `poll(2)`. Resolves to the events
+    /// that are ready, which may also include
+    /// `PollFlags::ERR`, `HUP` or `NVAL` even
+    /// when they weren't asked for.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.1 and up.
+    pub fn poll_add<'a, F>(
+        &'a self,
+        file: &'a F,
+        events: PollFlags,
+    ) -> Completion<'a, PollFlags>
+    where
+        F: AsRawFd,
+    {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_POLL_ADD,
+                file.as_raw_fd(),
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.__bindgen_anon_1.poll_events =
+                events.bits();
+        })
+    }
+
+    /// Like `poll_add`, but stays armed, and
+    /// yields the ready events every time that
+    /// the file descriptor becomes ready, until
+    /// the returned `PollMultishot` is dropped.
+    /// This saves submitting a new poll after each
+    /// event, which makes it a good fit for the
+    /// core of a readiness-based reactor.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.13 and up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rio::PollFlags;
+    ///
+    /// let ring = rio::new().unwrap();
+    /// let listener =
+    ///     std::net::TcpListener::bind("127.0.0.1:6666")
+    ///         .unwrap();
+    ///
+    /// let events = ring
+    ///     .poll_add_multishot(&listener, PollFlags::IN);
+    /// for ready in events {
+    ///     let _ = ready.unwrap();
+    ///     let stream = listener.accept().unwrap();
+    /// }
+    /// ```
+    pub fn poll_add_multishot<'a, F>(
+        &'a self,
+        file: &'a F,
+        events: PollFlags,
+    ) -> PollMultishot<'a>
+    where
+        F: AsRawFd,
+    {
+        PollMultishot::new(self, file.as_raw_fd(), events)
+    }
+
+    /// Arms a multishot poll on `ticket`, like the
+    /// one of a finished poll that is being armed
+    /// again, or on a new one if that's `None`.
+    pub(crate) fn poll_multishot_sqe(
+        &self,
+        fd: RawFd,
+        events: PollFlags,
+        ticket: Option<usize>,
+    ) -> Multishot<'_> {
+        let ticket =
+            ticket.unwrap_or_else(|| self.pop_ticket());
+        let mut multishot =
+            self.with_multishot_sqe_on(ticket, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_POLL_ADD,
+                    fd,
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.__bindgen_anon_1.poll_events =
+                    events.bits();
+                sqe.len = IORING_POLL_ADD_MULTI;
+            });
+        multishot.is_poll = true;
+        multishot
+    }
+
+    /// Posts a message to `target`'s completion
+    /// queue, which may be this ring, for it to
+    /// pick up with `recv_msg` or `try_recv_msg`.
+    /// This hands work between rings, like in a
+    /// thread-per-core design with a ring per core,
+    /// without any other channel between them.
+    /// Resolves once the message has been posted.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 6.3 and up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let a = rio::new().unwrap();
+    /// let b = rio::new().unwrap();
+    ///
+    /// a.msg_ring(&b, 42, 7).wait().unwrap();
+    ///
+    /// let msg = b.recv_msg();
+    /// assert_eq!((msg.data, msg.len), (42, 7));
+    /// ```
+    pub fn msg_ring<'a>(
+        &'a self,
+        target: &Uring,
+        data: u64,
+        len: u32,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_MSG_RING,
+                target.ring_fd,
+                0,
+                data,
+                Ordering::None,
+            );
+            sqe.len = len;
+            sqe.addr = IORING_MSG_DATA;
+            // have the kernel post it with flags
+            // that tell the reaper what it is
+            sqe.__bindgen_anon_1.msg_ring_flags =
+                IORING_MSG_RING_FLAGS_PASS;
+            sqe.__bindgen_anon_2
+                .__bindgen_anon_1
+                .splice_fd_in = i32::from_ne_bytes(
+                RING_MESSAGE.to_ne_bytes(),
+            );
+        })
+    }
+
+    /// Takes the oldest message posted to this ring
+    /// by `msg_ring`, if there are any.
+    pub fn try_recv_msg(&self) -> Option<RingMessage> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    /// Blocks until a message is posted to this
+    /// ring by `msg_ring`, and takes it, or takes
+    /// the oldest one if there already are some.
+    pub fn recv_msg(&self) -> RingMessage {
+        loop {
+            if let Some(msg) = self.try_recv_msg() {
+                return msg;
+            }
+            self.wait_until(|| {
+                !self.messages.lock().unwrap().is_empty()
+            });
+        }
+    }
+
+    /// Cancels every operation on `fd` that is
+    /// still in-flight, like when a connection has
+    /// died and everything waiting on its socket
+    /// should be torn down at once. Each of them
+    /// fails with `ECANCELED`, and this resolves
+    /// to how many there were. It fails with
+    /// `ENOENT` if there weren't any.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.19 and up. Older ones fail it with
+    /// `EINVAL`.
+    pub fn cancel_fd<'a>(
+        &'a self,
+        fd: RawFd,
+    ) -> Completion<'a, usize> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_ASYNC_CANCEL,
+                fd,
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.__bindgen_anon_1.cancel_flags =
+                IORING_ASYNC_CANCEL_FD
+                    | IORING_ASYNC_CANCEL_ALL;
+        })
+    }
+
+    /// Don't do anything. This is
+    /// mostly for debugging and tuning.
+    pub fn nop<'a>(&'a self) -> Completion<'a, ()> {
+        self.nop_ordered(Ordering::None)
+    }
+
+    /// Pushes `count` NOPs through the ring and
+    /// waits for all of them, timing how long it
+    /// takes to submit them, and then to complete
+    /// them. NOPs don't touch any device, so this
+    /// measures rio's own overhead, and the
+    /// kernel's, for performance tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let result = ring.benchmark_nops(1000).unwrap();
+    /// println!("{} ops/s", result.ops_per_sec());
+    /// ```
+    pub fn benchmark_nops(
+        &self,
+        count: usize,
+    ) -> io::Result<BenchResult> {
+        let before = Instant::now();
+        let nops = (0..count).map(|_| self.nop()).collect();
+        let submitted = Instant::now();
+        let _ = self.drain_completions(nops)?;
+
+        Ok(BenchResult {
+            ops: count,
+            submit_time: submitted - before,
+            complete_time: submitted.elapsed(),
+        })
+    }
+
+    /// Don't do anything. This is
+    /// mostly for debugging and tuning.
+    pub fn nop_ordered<'a>(
+        &'a self,
+        ordering: Ordering,
+    ) -> Completion<'a, ()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(IORING_OP_NOP, 0, 1, 0, ordering)
+        })
+    }
+
+    /// An escape hatch for submitting operations
+    /// that rio does not wrap yet. `prep` is
+    /// handed a zeroed SQE to fill in, while rio
+    /// still takes care of its `user_data`,
+    /// submission, and routing its completion back
+    /// to the returned `Completion`. Anything that
+    /// the SQE points to can be handed to
+    /// `keep_alive`, which is held by the
+    /// `Completion` until the operation is done.
+    ///
+    /// Like every other `Completion`, the result
+    /// is an error if the kernel returns a negative
+    /// `res`. Otherwise the raw CQE is returned.
+    ///
+    /// # Safety
+    ///
+    /// The kernel will read from and write to
+    /// whatever the SQE points at, with none of the
+    /// checks that rio's typed methods perform.
+    /// The caller must ensure that:
+    ///
+    /// * every pointer in the SQE is valid for
+    ///   the kind of access the opcode performs,
+    ///   for as long as the operation is in-flight,
+    ///   either because it points into something
+    ///   held by `keep_alive`, or because it
+    ///   otherwise outlives the `Completion`.
+    /// * `user_data` is left alone. It is reset
+    ///   after `prep` runs regardless, and is how
+    ///   rio finds the `Completion` to fill.
+    /// * any file descriptors stay open until the
+    ///   operation is complete.
+    /// * the opcode posts exactly one completion.
+    ///   Multishot operations are not supported.
+    /// * the `Completion` is never leaked, for the
+    ///   same reasons as any other `Completion`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// #[allow(unsafe_code)]
+    /// let completion = unsafe {
+    ///     ring.submit_raw(
+    ///         |sqe| {
+    ///             // IORING_OP_NOP
+    ///             sqe.opcode = 0;
+    ///         },
+    ///         rio::KeepAlive::new(),
+    ///     )
+    /// };
+    ///
+    /// let cqe = completion.wait().unwrap();
+    /// assert_eq!(cqe.res, 0);
+    /// ```
+    #[allow(unsafe_code)]
+    pub unsafe fn submit_raw<'a, F>(
+        &'a self,
+        prep: F,
+        keep_alive: KeepAlive,
+    ) -> Completion<'a, io_uring_cqe>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+    {
+        self.submit_raw_inner(prep, keep_alive, None)
+    }
+
+    /// Like `submit_raw`, but the CQE that the
+    /// `Completion` resolves to has its `user_data`
+    /// set to `tag`, rather than to whatever rio
+    /// used internally to route it. That makes it
+    /// possible to match completions back up with
+    /// your own requests, without keeping track of
+    /// which `Completion` belongs to which.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `submit_raw`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// #[allow(unsafe_code)]
+    /// let completion = unsafe {
+    ///     ring.submit_raw_tagged(
+    ///         |sqe| {
+    ///             // IORING_OP_NOP
+    ///             sqe.opcode = 0;
+    ///         },
+    ///         rio::KeepAlive::new(),
+    ///         42,
+    ///     )
+    /// };
+    ///
+    /// let cqe = completion.wait().unwrap();
+    /// assert_eq!(cqe.user_data, 42);
+    /// ```
+    #[allow(unsafe_code)]
+    pub unsafe fn submit_raw_tagged<'a, F>(
+        &'a self,
+        prep: F,
+        keep_alive: KeepAlive,
+        tag: u64,
+    ) -> Completion<'a, io_uring_cqe>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+    {
+        self.submit_raw_inner(prep, keep_alive, Some(tag))
+    }
+
+    fn submit_raw_inner<'a, F>(
+        &'a self,
+        prep: F,
+        keep_alive: KeepAlive,
+        tag: Option<u64>,
+    ) -> Completion<'a, io_uring_cqe>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+    {
+        let mut completion =
+            self.with_sqe(None, false, |sqe| {
+                let user_data = sqe.user_data;
+                if let Some(caller_tag) = tag {
+                    // this runs before the SQE can be
+                    // submitted, so before it's reaped
+                    let ticket = usize::try_from(
+                        user_data & TICKET_MASK,
+                    )
+                    .unwrap();
+                    self.in_flight
+                        .set_tag(ticket, caller_tag);
+                }
+                *sqe = io_uring_sqe::default();
+                prep(sqe);
+                sqe.user_data = user_data;
+            });
+        completion.keep_alive = Some(keep_alive);
+        completion
+    }
+
+    /// A `Completion` that isn't backed by any
+    /// operation, and is resolved by the returned
+    /// `Injector` instead of by the kernel. This
+    /// is for testing code that waits on, polls,
+    /// or drops completions, with exactly the
+    /// results and timing that the test wants,
+    /// rather than whatever real IO produces.
+    ///
+    /// A ring is still needed to create it, but
+    /// nothing is ever submitted to it, unless a
+    /// polled `Completion` is dropped before it is
+    /// filled, which submits a cancellation that
+    /// fails with `ENOENT`.
+    ///
+    /// # Warning
+    ///
+    /// With the `single_thread` feature, waiting
+    /// on the `Completion` blocks in the kernel
+    /// until it's filled, so it must be filled
+    /// before anything waits on it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let (completion, injector) =
+    ///     ring.inject_completion::<usize>();
+    /// injector.fill(Ok(rio::io_uring_cqe {
+    ///     user_data: 0,
+    ///     res: 42,
+    ///     flags: 0,
+    /// }));
+    /// assert_eq!(completion.wait().unwrap(), 42);
+    /// ```
+    #[cfg(feature = "testing")]
+    pub fn inject_completion<'a, C: FromCqe>(
+        &'a self,
+    ) -> (Completion<'a, C>, Injector<'a>) {
+        let (mut completion, filler) = pair(self);
+        completion.injected = true;
+        (completion, Injector::new(self, filler))
+    }
+
+    /// Makes the next submission run into `fault`,
+    /// as if the submission queue's invariants had
+    /// been broken by the kernel or something else
+    /// writing to it, for testing how that is
+    /// handled. The submission that runs into it
+    /// fails, as does `submit_stats` when that's
+    /// what submits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// ring.inject_sq_fault(rio::SqFault::Dropped);
+    /// assert!(ring.submit_stats().is_err());
+    /// assert!(ring.submit_stats().is_ok());
+    /// ```
+    #[cfg(feature = "testing")]
+    pub fn inject_sq_fault(&self, fault: SqFault) {
+        self.submitter.inject_fault(fault);
+    }
+
+    /// Wakes up every thread blocked waiting on
+    /// a `Completion`, so that they check whether
+    /// theirs has been filled.
+    #[cfg(feature = "testing")]
+    pub(crate) fn notify_waiters(&self) {
+        self.wakeup.notify();
+    }
+
+    /// Submit a NOP and block until it completes,
+    /// returning how long that took. On an idle
+    /// ring this is just the round-trip overhead
+    /// of the kernel, but it grows as the ring
+    /// gets backed up, which makes it a cheap
+    /// health metric to export to monitoring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let latency = ring.ping().unwrap();
+    /// println!("ring round-trip: {:?}", latency);
+    /// ```
+    pub fn ping(&self) -> io::Result<Duration> {
+        let _measure = self.metrics.measure(|m| &m.ping);
+        let before = Instant::now();
+        self.nop().wait()?;
+        Ok(before.elapsed())
+    }
+
+    /// Submits a timeout that fires once `after`
+    /// has passed, returning its `Completion`,
+    /// which then resolves to `Expired`, along
+    /// with a token that can be passed to
+    /// `timeout_update` to push it back, or bring
+    /// it forward, without submitting a new one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let (idle, token) =
+    ///     ring.timeout(Duration::from_millis(10));
+    /// // activity, so start counting again
+    /// let later = Duration::from_millis(20);
+    /// ring.timeout_update(token, later).wait().unwrap();
+    /// idle.wait().unwrap();
+    /// ```
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.4 and up.
+    pub fn timeout(
+        &self,
+        after: Duration,
+    ) -> (Completion<'_, Expired>, TimeoutToken) {
+        // the kernel copies the timespec when the
+        // SQE is submitted, but it's simplest to
+        // keep it around until the op is done.
+        let mut keep_alive = KeepAlive::new();
+        let ts_ptr =
+            keep_alive.hold(__kernel_timespec::from(after));
+
+        let mut completion =
+            self.with_sqe(None, false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_TIMEOUT,
+                    -1,
+                    1,
+                    0,
+                    Ordering::None,
+                );
+                sqe.addr = ts_ptr as u64;
+            });
+        completion.keep_alive = Some(keep_alive);
+
+        let token = TimeoutToken(completion.user_data);
+        (completion, token)
+    }
+
+    /// Reschedules the timeout identified by
+    /// `token` to fire once `after` has passed,
+    /// counting from when this runs, rather than
+    /// from when it was submitted. That makes it
+    /// cheap to keep pushing back something like a
+    /// connection's idle timeout every time that
+    /// data arrives, compared to canceling it and
+    /// submitting a new one.
+    ///
+    /// Fails with `ENOENT` if the timeout already
+    /// fired, or was removed.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.11 and up.
+    pub fn timeout_update(
+        &self,
+        token: TimeoutToken,
+        after: Duration,
+    ) -> Completion<'_, ()> {
+        let mut keep_alive = KeepAlive::new();
+        let ts_ptr =
+            keep_alive.hold(__kernel_timespec::from(after));
+
+        let mut completion =
+            self.with_sqe(None, false, |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_TIMEOUT_REMOVE,
+                    -1,
+                    0,
+                    ts_ptr as u64,
+                    Ordering::None,
+                );
+                sqe.addr = token.0;
+                sqe.__bindgen_anon_1.timeout_flags =
+                    IORING_TIMEOUT_UPDATE;
+            });
+        completion.keep_alive = Some(keep_alive);
+        completion
+    }
+
+    /// Block until all items in the submission queue
+    /// are submitted to the kernel. This can
+    /// be avoided by using the `SQPOLL` mode
+    /// (a privileged operation) on the `Config`
+    /// struct.
+    ///
+    /// Note that this is performed automatically
+    /// and in a more fine-grained way when a
+    /// `Completion` is consumed via `Completion::wait`
+    /// or awaited in a Future context.
+    ///
+    /// You don't need to call this if you are
+    /// calling `.wait()` or `.await` on the
+    /// `Completion` quickly, but if you are
+    /// doing some other stuff that could take
+    /// a while first, calling this will ensure
+    /// that the operation is being executed
+    /// by the kernel in the mean time.
+    ///
+    /// With `Config::submit_batch_min`, this may
+    /// hold back a small batch, to be submitted
+    /// along with what comes after it. Waiting on
+    /// an operation always submits it first.
+    pub fn submit_all(&self) {
+        self.submitter.submit_batched();
+    }
+
+    /// Submits everything in the submission queue
+    /// once, and then waits for each of
+    /// `completions` in turn, returning all of
+    /// their results, or the first error. The
+    /// rest are still waited on, as they are
+    /// dropped, before an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let nops = (0..100).map(|_| ring.nop()).collect();
+    /// let results = ring.drain_completions(nops).unwrap();
+    /// assert_eq!(results.len(), 100);
+    /// ```
+    pub fn drain_completions<C: FromCqe>(
+        &self,
+        completions: Vec<Completion<'_, C>>,
+    ) -> io::Result<Vec<C>> {
+        self.submitter.submit_pending();
+        completions
+            .into_iter()
+            .map(Completion::wait)
+            .collect()
+    }
+
+    /// Blocks until any one of `completions` is
+    /// done, like a blocking `select!`, then takes
+    /// it out of the `Vec`, returning the index it
+    /// had along with its result. When several are
+    /// done, the one that comes first wins. The
+    /// rest stay in the `Vec`, in the same order,
+    /// to be waited on again.
+    ///
+    /// Every completion must belong to this ring.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `completions` is empty, since it
+    /// would block forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    ///
+    /// let mut nops: Vec<_> =
+    ///     (0..3).map(|_| ring.nop()).collect();
+    /// while !nops.is_empty() {
+    ///     let (_index, res) = ring.wait_any(&mut nops);
+    ///     res.unwrap();
+    /// }
+    /// ```
+    pub fn wait_any<C: FromCqe>(
+        &self,
+        completions: &mut Vec<Completion<'_, C>>,
+    ) -> (usize, io::Result<C>) {
+        assert!(
+            !completions.is_empty(),
+            "wait_any needs at least one completion"
+        );
+        debug_assert!(
+            completions.iter().all(|c| c.is_from(self)),
+            "wait_any was given another ring's completion"
+        );
+
+        self.submitter.submit_pending();
+
+        // every completion on this ring wakes up the
+        // same waiters, so this is woken whichever
+        // of them completes first
+        self.wait_until(|| {
+            completions.iter().any(Completion::is_done)
+        });
+
+        let index = completions
+            .iter()
+            .position(Completion::is_done)
+            .unwrap();
+        (index, completions.remove(index).wait())
+    }
+
+    /// Submit all items in the submission queue,
+    /// like `submit_all`, and return a snapshot
+    /// of how many SQEs were submitted, how much
+    /// space is left in the submission queue, and
+    /// how many operations are still in-flight.
+    ///
+    /// The snapshot is taken while holding the
+    /// submission queue lock, so `submitted` and
+    /// `sq_space_left` are consistent with each
+    /// other.
+    ///
+    /// Fails if the submission queue is found to
+    /// be inconsistent, for instance because the
+    /// kernel dropped SQEs that it found invalid,
+    /// which means that the ring can't be relied
+    /// on anymore.
+    pub fn submit_stats(&self) -> io::Result<SubmitStats> {
+        let (submitted, sq_space_left) =
+            self.submitter.submit_all()?;
+
+        Ok(SubmitStats {
+            submitted,
+            sq_space_left,
+            in_flight: self.ticket_queue.in_flight(),
+        })
+    }
+
+    /// Snapshots the raw head, tail and mask of
+    /// the submission and completion queues, along
+    /// with the kernel's dropped and overflow
+    /// counters. This is read-only, and is meant
+    /// for diagnosing submission and completion
+    /// imbalances, or completion queue overflow,
+    /// when a ring seems to be stuck. See
+    /// `RingState`.
+    pub fn dump_ring_state(&self) -> RingState {
+        let mut state = RingState::default();
+        self.submitter.dump_state(&mut state);
+        self.cq_peek.dump_state(&mut state);
+        state
+    }
+
+    /// Registers the credentials of the current
+    /// process with this ring, returning an id
+    /// that can be passed to `with_personality`
+    /// to perform operations with them later on,
+    /// even after the process has switched to
+    /// different credentials.
+    ///
+    /// # Warning
+    ///
+    /// This only becomes usable on linux kernels
+    /// 5.6 and up.
+    pub fn register_personality(&self) -> io::Result<u16> {
+        let id = register(
+            self.ring_fd,
+            IORING_REGISTER_PERSONALITY,
+            std::ptr::null(),
+            0,
+        )
+        .map_err(|e| {
+            unsupported_register(
+                IORING_REGISTER_PERSONALITY,
+                e,
+            )
+        })?;
+
+        Ok(u16::try_from(id).unwrap())
+    }
+
+    /// Unregisters a personality that was returned
+    /// by `register_personality`.
+    pub fn unregister_personality(
+        &self,
+        personality: u16,
+    ) -> io::Result<()> {
+        register(
+            self.ring_fd,
+            IORING_UNREGISTER_PERSONALITY,
+            std::ptr::null(),
+            u32::from(personality),
+        )?;
+
+        Ok(())
+    }
+
+    /// Caps the number of kernel worker threads
+    /// that may be spawned to run this ring's
+    /// operations in the background, like ones
+    /// that would otherwise block. `bounded` caps
+    /// the workers for operations that finish in
+    /// bounded time, like reads of regular files,
+    /// and `unbounded` the ones for operations
+    /// that may never finish, like reads of
+    /// sockets. 0 leaves a cap as it was.
+    ///
+    /// The kernel spawns these on demand, with no
+    /// cap by default other than the process's
+    /// `RLIMIT_NPROC`, so a burst of blocking
+    /// operations can spawn thousands of them.
+    /// Operations beyond the cap wait their turn.
+    ///
+    /// Fails with an error of kind
+    /// `io::ErrorKind::Unsupported` on kernels
+    /// before 5.15.
+    pub fn set_iowq_max_workers(
+        &self,
+        bounded: u32,
+        unbounded: u32,
+    ) -> io::Result<()> {
+        // the kernel writes the previous caps
+        // back into this
+        let mut counts = [bounded, unbounded];
+        register(
+            self.ring_fd,
+            IORING_REGISTER_IOWQ_MAX_WORKERS,
+            counts.as_mut_ptr().cast(),
+            2,
+        )
+        .map_err(|e| {
+            unsupported_register(
+                IORING_REGISTER_IOWQ_MAX_WORKERS,
+                e,
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Registers `files` with this ring, so that
+    /// operations on them can be performed
+    /// through the returned registration, which
+    /// unregisters them when it's dropped.
+    ///
+    /// Fails with an error of kind
+    /// `io::ErrorKind::Unsupported` if the kernel
+    /// is too old to register files, in which
+    /// case the plain file descriptors can be used
+    /// with the rest of rio instead.
+    ///
+    /// Each file is operated on through the
+    /// `RegisteredFd` that the registration hands
+    /// out for it, which sets `IOSQE_FIXED_FILE`
+    /// on the operations by itself. In debug
+    /// builds, operations on it fail once its slot
+    /// is given another file, with
+    /// `FilesRegistration::update` or the like.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let ring = rio::new().unwrap();
+    /// let files = [
+    ///     std::fs::File::create("a").unwrap(),
+    ///     std::fs::File::create("b").unwrap(),
+    /// ];
+    ///
+    /// let registered =
+    ///     ring.register_files(&files).unwrap();
+    /// let b = registered.fd(1);
+    /// registered
+    ///     .write_at(&b, b"hello", 0)
+    ///     .wait()
+    ///     .unwrap();
+    /// ```
+    pub fn register_files<'a, F>(
+        &'a self,
+        files: &'a [F],
+    ) -> io::Result<FilesRegistration<'a>>
+    where
+        F: AsRawFd,
+    {
+        let fds: Vec<RawFd> =
+            files.iter().map(AsRawFd::as_raw_fd).collect();
+
+        let _ = register(
+            self.ring_fd,
+            IORING_REGISTER_FILES,
+            fds.as_ptr().cast(),
+            u32::try_from(fds.len()).unwrap(),
+        )
+        .map_err(|e| {
+            unsupported_register(IORING_REGISTER_FILES, e)
+        })?;
+
+        Ok(FilesRegistration::new(self, fds.len()))
+    }
+
+    /// Registers `count` empty file slots with
+    /// this ring, for operations like
+    /// `FilesRegistration::accept_fixed` to install
+    /// files into.
+    pub fn register_file_slots(
+        &self,
+        count: usize,
+    ) -> io::Result<FilesRegistration<'_>> {
+        let fds: Vec<RawFd> = vec![-1; count];
+
+        let _ = register(
+            self.ring_fd,
+            IORING_REGISTER_FILES,
+            fds.as_ptr().cast(),
+            u32::try_from(count).unwrap(),
+        )
+        .map_err(|e| {
+            unsupported_register(IORING_REGISTER_FILES, e)
+        })?;
+
+        Ok(FilesRegistration::new(self, count))
+    }
+
+    /// Registers `bufs` with this ring, pinning
+    /// their memory until the returned
+    /// registration is dropped. Operations on them
+    /// are performed by index through it.
+    ///
+    /// The kernel may charge pinned memory against
+    /// the memlock rlimit, unless the process has
+    /// `CAP_IPC_LOCK`. If it refuses with `ENOMEM`
+    /// and `bufs` don't fit within that rlimit,
+    /// this returns an error that says so, rather
+    /// than the bare `ENOMEM`.
+    ///
+    /// Fails with an error of kind
+    /// `io::ErrorKind::Unsupported` if the kernel
+    /// is too old to register buffers, in which
+    /// case the buffers can be used with the rest
+    /// of rio as they are instead.
+    pub fn register_buffers<'a, B>(
+        &'a self,
+        bufs: &'a [B],
+    ) -> io::Result<BuffersRegistration<'a>>
+    where
+        B: AsIoVec + AsIoVecMut,
+    {
+        let iovecs: Vec<libc::iovec> = bufs
+            .iter()
+            .map(AsIoVec::into_new_iovec)
+            .collect();
+
+        // the kernel refuses empty buffers and
+        // ones over 1GiB with a bare `EFAULT` or
+        // `EINVAL`.
+        if iovecs.iter().any(|iov| {
+            !(1..=1 << 30).contains(&iov.iov_len)
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "registered buffers must be between \
+                 1 byte and 1GiB long (inclusive)",
+            ));
+        }
+
+        let res = register(
+            self.ring_fd,
+            IORING_REGISTER_BUFFERS,
+            iovecs.as_ptr().cast(),
+            u32::try_from(iovecs.len()).unwrap(),
+        )
+        .map_err(|e| {
+            unsupported_register(IORING_REGISTER_BUFFERS, e)
+        });
+
+        match res {
+            Err(e)
+                if e.raw_os_error()
+                    == Some(libc::ENOMEM)
+                    && exceeds_memlock(&iovecs) =>
+            {
+                Err(not_enough_memlock())
+            }
+            other => other.map(|_| {
+                BuffersRegistration::new(self, iovecs)
+            }),
+        }
+    }
+
+    /// Every operation that is submitted to this
+    /// ring by the current thread while `f` runs
+    /// will be performed with the credentials that
+    /// were registered as `personality` by
+    /// `register_personality`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ring = rio::new().unwrap();
+    /// let personality =
+    ///     ring.register_personality().unwrap();
+    ///
+    /// let completion =
+    ///     ring.with_personality(personality, || ring.nop());
+    /// completion.wait().unwrap();
+    /// ```
+    pub fn with_personality<F, R>(
+        &self,
+        personality: u16,
+        f: F,
+    ) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let old = PERSONALITY.with(|p| {
+            p.replace(Some((self.ring_fd, personality)))
+        });
+        let _guard = PersonalityGuard(old);
+
+        f()
+    }
+
+    fn apply_personality(&self, sqe: &mut io_uring_sqe) {
+        if let Some((ring_fd, personality)) =
+            PERSONALITY.with(Cell::get)
+        {
+            if ring_fd == self.ring_fd {
+                sqe.__bindgen_anon_2
+                    .__bindgen_anon_1
+                    .personality = personality;
+            }
+        }
+    }
+
+    /// Cancels the operation that was submitted
+    /// with this `user_data`, if it is still
+    /// running. This fails with `ENOENT` if it
+    /// is not.
+    pub(crate) fn cancel(
+        &self,
+        user_data: u64,
+    ) -> io::Result<()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_ASYNC_CANCEL,
+                -1,
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.addr = user_data;
+        })
+        .wait()
+    }
+
+    /// Removes the poll that was submitted with
+    /// this `user_data`, if it is still armed.
+    /// This fails with `ENOENT` if it is not.
+    pub(crate) fn poll_remove(
+        &self,
+        user_data: u64,
+    ) -> io::Result<()> {
+        self.with_sqe(None, false, |sqe| {
+            sqe.prep_rw(
+                IORING_OP_POLL_REMOVE,
+                -1,
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.addr = user_data;
+        })
+        .wait()
+    }
+
+    /// Like `cancel`, but only if a ticket is free
+    /// for it right now, returning `None` if not.
+    /// Completions cancel themselves with this as
+    /// they are dropped, since waiting for a
+    /// ticket there could mean waiting for one
+    /// that only frees up once the very operation
+    /// being canceled does.
+    pub(crate) fn try_cancel(
+        &self,
+        user_data: u64,
+    ) -> Option<io::Result<()>> {
+        let ticket = self.ticket_queue.try_pop()?;
+        let (mut completion, filler) = pair(self);
+        let (cancel_user_data, sqe_id) =
+            self.push_cancel(ticket, user_data, filler);
+        completion.user_data = cancel_user_data;
+        completion.sqe_id = sqe_id;
+        Some(completion.wait())
+    }
+
+    /// Like `try_cancel`, but without waiting for
+    /// the cancellation to complete.
+    pub(crate) fn try_cancel_detached(
+        &self,
+        user_data: u64,
+    ) {
+        if let Some(ticket) = self.ticket_queue.try_pop() {
+            let (_, sqe_id) = self.push_cancel(
+                ticket,
+                user_data,
+                Filler::detached(),
+            );
+            let _ = self.ensure_submitted(sqe_id);
+        }
+    }
+
+    /// Pushes a cancellation of `user_data` that
+    /// completes to `filler`, with a `ticket` that
+    /// has already been taken for it, returning
+    /// the cancellation's own `user_data` and the
+    /// id of its SQE. If it can't be pushed,
+    /// `filler` is failed instead, and there's no
+    /// SQE to wait for.
+    fn push_cancel(
+        &self,
+        ticket: usize,
+        user_data: u64,
+        filler: Filler,
+    ) -> (u64, u64) {
+        let cancel_user_data =
+            self.in_flight.next_user_data(ticket);
+        self.in_flight
+            .insert(ticket, None, false, None, filler);
+
+        let pushed = self.submitter.push(|sqe| {
+            sqe.prep_rw(
+                IORING_OP_ASYNC_CANCEL,
+                -1,
+                0,
+                0,
+                Ordering::None,
+            );
+            sqe.user_data = cancel_user_data;
+            sqe.addr = user_data;
+        });
+        let sqe_id = pushed.unwrap_or_else(|e| {
+            self.fail_unpushed(&[ticket], e);
+            0
+        });
+        (cancel_user_data, sqe_id)
+    }
+
+    /// Shuts down both directions of a socket and
+    /// then closes it, without waiting for either.
+    /// The close is hard-linked behind the
+    /// shutdown, so it happens even if the
+    /// shutdown fails, like when the peer has
+    /// already hung up, or on kernels before 5.11
+    /// that don't support it.
+    pub(crate) fn shutdown_close_detached(
+        &self,
+        fd: RawFd,
+    ) {
+        let [shutdown_ticket, close_ticket] =
+            if let Ok(tickets) = self.pop_tickets() {
+                tickets
+            } else {
+                // a ring that was set up with room for
+                // just one op can't link the two, so
+                // this does them right here instead
+                shutdown_close(fd);
+                return;
+            };
+        let shutdown_user_data =
+            self.in_flight.next_user_data(shutdown_ticket);
+        let close_user_data =
+            self.in_flight.next_user_data(close_ticket);
+        for ticket in &[shutdown_ticket, close_ticket] {
+            self.in_flight.insert(
+                *ticket,
+                None,
+                false,
+                None,
+                Filler::detached(),
+            );
+        }
+
+        let pushed = self.submitter.push_pair(
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_SHUTDOWN,
+                    fd,
+                    usize::try_from(libc::SHUT_RDWR)
+                        .unwrap(),
+                    0,
+                    Ordering::None,
+                );
+                sqe.flags |= IOSQE_IO_HARDLINK;
+                sqe.user_data = shutdown_user_data;
+            },
+            |sqe| {
+                sqe.prep_rw(
+                    IORING_OP_CLOSE,
+                    fd,
+                    0,
+                    0,
+                    Ordering::None,
+                );
+                sqe.user_data = close_user_data;
+            },
+        );
+        match pushed {
+            Ok(sqe_id) => {
+                let _ = self.ensure_submitted(sqe_id);
+            }
+            Err(e) => {
+                self.fail_unpushed(
+                    &[close_ticket, shutdown_ticket],
+                    e,
+                );
+                shutdown_close(fd);
+            }
+        }
+    }
+
+    pub(crate) const fn cancel_on_drop(&self) -> bool {
+        self.config.cancel_on_drop
+    }
+
+    pub(crate) const fn detach_owned_on_drop(
+        &self,
+    ) -> bool {
+        self.config.detach_owned_on_drop
+    }
+
+    pub(crate) fn drop_error_handler(
+        &self,
+    ) -> Option<&(dyn Fn(io::Error) + Send + Sync)> {
+        self.config.drop_error_handler.as_deref()
+    }
+
+    /// Blocks until `done` returns true, which
+    /// happens when the reaper fills in the
+    /// completion that it checks for.
+    pub(crate) fn wait_until<F: Fn() -> bool>(
+        &self,
+        done: F,
+    ) {
+        #[cfg(not(feature = "single_thread"))]
+        self.wakeup.wait_until(done);
+
+        // without a reaper thread, we reap for
+        // ourselves (and anyone else) until it's done.
+        #[cfg(feature = "single_thread")]
+        loop {
+            // `done` is checked while holding the `Cq`,
+            // so that nobody else can reap what we're
+            // waiting for before we block for it.
+            let mut cq = self.cq.lock().unwrap();
+            if done() {
+                return;
+            }
+            let _ = cq.reap(self.ring_fd, 1);
+        }
+    }
+
+    /// Reaps whatever completions are ready, for
+    /// polling futures when there is no reaper
+    /// thread to wake them up. If that doesn't
+    /// make `done` true, it waits up to a
+    /// millisecond for more to be posted before
+    /// reaping again, so that a future that wakes
+    /// itself to be polled again doesn't spin,
+    /// without blocking on something that may
+    /// never complete.
+    #[cfg(feature = "single_thread")]
+    pub(crate) fn reap_unless<F: Fn() -> bool>(
+        &self,
+        done: F,
+    ) {
+        // as in `wait_until`, `done` is checked
+        // while holding the `Cq`
+        let mut cq = self.cq.lock().unwrap();
+        let _ = cq.reap(self.ring_fd, 0);
+        if done() {
+            return;
+        }
+
+        // the ring's fd is readable once it has
+        // completions to reap
+        let mut pollfd = libc::pollfd {
+            fd: self.ring_fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        #[allow(unsafe_code)]
+        let ready =
+            unsafe { libc::poll(&mut pollfd, 1, 1) };
+        if ready > 0 {
+            let _ = cq.reap(self.ring_fd, 0);
+        }
+    }
+
+    fn pop_ticket(&self) -> usize {
+        #[cfg(not(feature = "single_thread"))]
+        return self.ticket_queue.pop();
+
+        // tickets are only handed back by reaping,
+        // so if there are none left we have to make
+        // sure that everything holding one has been
+        // submitted, and then reap until one is.
+        #[cfg(feature = "single_thread")]
+        loop {
+            if let Some(ticket) =
+                self.ticket_queue.try_pop()
+            {
+                return ticket;
+            }
+            self.submitter.submit_pending();
+            self.wait_until(|| {
+                self.ticket_queue.has_free(1)
+            });
+        }
+    }
+
+    /// Like `pop_ticket`, but takes all `N` of the
+    /// tickets that a chain of operations needs at
+    /// once, rather than holding some while waiting
+    /// for the rest. Fails if the ring doesn't have
+    /// room for that many at all, either in flight
+    /// or in the submission queue, where the chain
+    /// is pushed all at once.
+    fn pop_tickets<const N: usize>(
+        &self,
+    ) -> io::Result<[usize; N]> {
+        let mut tickets = [0; N];
+        self.pop_tickets_into(&mut tickets)?;
+        Ok(tickets)
+    }
+
+    /// Like `pop_tickets`, but for a number of
+    /// tickets that's only known at runtime.
+    fn pop_tickets_into(
+        &self,
+        tickets: &mut [usize],
+    ) -> io::Result<()> {
+        let room =
+            self.ticket_queue.size().min(self.sq_capacity);
+        if tickets.len() > room {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "this needs {} operations in flight \
+                     at once, but the ring only has \
+                     room for {}",
+                    tickets.len(),
+                    room
+                ),
+            ));
+        }
+
+        #[cfg(not(feature = "single_thread"))]
+        self.ticket_queue.pop_into(tickets);
+
+        #[cfg(feature = "single_thread")]
+        while !self.ticket_queue.try_pop_into(tickets) {
+            let needed = tickets.len();
+            self.submitter.submit_pending();
+            self.wait_until(|| {
+                self.ticket_queue.has_free(needed)
+            });
+        }
+
+        Ok(())
     }
 
-    /// Writes data at the provided buffer using
-    /// vectored IO.
-    ///
-    /// Be sure to check the returned
-    /// `io_uring_cqe`'s `res` field to see if a
-    /// short write happened. This will contain
-    /// the number of bytes written.
-    ///
-    /// You may pass in an `Ordering` to specify
-    /// two different optional behaviors:
-    ///
-    /// * `Ordering::Link` causes the next
-    ///   submitted operation to wait until
-    ///   this one finishes. Useful for
-    ///   things like file copy, fsync-after-write,
-    ///   or proxies.
-    /// * `Ordering::Drain` causes all previously
-    ///   submitted operations to complete before
-    ///   this one begins.
-    ///
-    /// Note that the file argument is generic
-    /// for anything that supports AsRawFd:
-    /// sockets, files, etc...
-    pub fn write_at_ordered<'a, F, B>(
+    pub(crate) fn release_ticket(&self, ticket: usize) {
+        self.ticket_queue.push_multi(&mut vec![ticket]);
+    }
+
+    /// Fails the operations on `tickets` with `e`,
+    /// since pushing their SQEs did, and hands
+    /// back whichever tickets may be. The first
+    /// one gets `e` itself, the rest a copy.
+    fn fail_unpushed(
+        &self,
+        tickets: &[usize],
+        e: io::Error,
+    ) {
+        let (&first, rest) =
+            tickets.split_first().unwrap();
+        let mut released = vec![];
+        for &ticket in rest {
+            let copy =
+                io::Error::new(e.kind(), e.to_string());
+            if self.in_flight.fail(ticket, copy) {
+                released.push(ticket);
+            }
+        }
+        if self.in_flight.fail(first, e) {
+            released.push(first);
+        }
+        self.ticket_queue.push_multi(&mut released);
+    }
+
+    fn with_multishot_sqe<'a, F>(
         &'a self,
-        file: &'a F,
-        iov: &'a B,
-        at: u64,
-        ordering: Ordering,
-    ) -> Completion<'a, usize>
+        f: F,
+    ) -> Multishot<'a>
     where
-        F: AsRawFd,
-        B: 'a + AsIoVec,
+        F: FnOnce(&mut io_uring_sqe),
     {
-        self.with_sqe(
-            Some(iov.into_new_iovec()),
-            false,
-            |sqe| {
-                sqe.prep_rw(
-                    IORING_OP_WRITEV,
-                    file.as_raw_fd(),
-                    1,
-                    at,
-                    ordering,
-                )
-            },
-        )
+        let ticket = self.pop_ticket();
+        self.with_multishot_sqe_on(ticket, f)
     }
 
-    /// Reads data into the provided buffer from the
-    /// given file-like object, at the given offest,
-    /// using vectored IO. Be sure to check the returned
-    /// `io_uring_cqe`'s `res` field to see if a
-    /// short read happened. This will contain
-    /// the number of bytes read.
-    ///
-    /// Note that the file argument is generic
-    /// for anything that supports AsRawFd:
-    /// sockets, files, etc...
-    pub fn read_at<'a, F, B>(
+    /// Like `with_multishot_sqe`, but on a `ticket`
+    /// that has already been taken for it.
+    fn with_multishot_sqe_on<'a, F>(
         &'a self,
-        file: &'a F,
-        iov: &'a B,
-        at: u64,
-    ) -> Completion<'a, usize>
+        ticket: usize,
+        f: F,
+    ) -> Multishot<'a>
     where
-        F: AsRawFd,
-        B: AsIoVec + AsIoVecMut,
+        F: FnOnce(&mut io_uring_sqe),
     {
-        self.read_at_ordered(file, iov, at, Ordering::None)
+        let (mut multishot, filler) =
+            Multishot::new(self, ticket);
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        multishot.user_data = user_data;
+
+        self.in_flight.insert_multi(ticket, filler);
+
+        let pushed = self.submitter.push(|sqe| {
+            sqe.user_data = user_data;
+            f(sqe);
+            self.apply_personality(sqe);
+            self.in_flight.set_sqe(ticket, *sqe);
+        });
+        match pushed {
+            Ok(sqe_id) => multishot.sqe_id = sqe_id,
+            Err(e) => self.fail_unpushed(&[ticket], e),
+        }
+
+        multishot
     }
 
-    /// Reads data into the provided buffer using
-    /// vectored IO. Be sure to check the returned
-    /// `io_uring_cqe`'s `res` field to see if a
-    /// short read happened. This will contain
-    /// the number of bytes read.
-    ///
-    /// You may pass in an `Ordering` to specify
-    /// two different optional behaviors:
-    ///
-    /// * `Ordering::Link` causes the next
-    ///   submitted operation to wait until
-    ///   this one finishes. Useful for
-    ///   things like file copy, fsync-after-write,
-    ///   or proxies.
-    /// * `Ordering::Drain` causes all previously
-    ///   submitted operations to complete before
-    ///   this one begins.
-    ///
-    /// Note that the file argument is generic
-    /// for anything that supports AsRawFd:
-    /// sockets, files, etc...
-    pub fn read_at_ordered<'a, F, B>(
+    /// Submits the operation described by `f`,
+    /// linked to an `IORING_OP_LINK_TIMEOUT` that
+    /// cancels it if it is still running after
+    /// `timeout`. The timeout's own completion
+    /// is ignored.
+    fn with_timeout_sqe<'a, F, C>(
         &'a self,
-        file: &'a F,
-        iov: &'a B,
-        at: u64,
-        ordering: Ordering,
-    ) -> Completion<'a, usize>
+        timeout: Duration,
+        f: F,
+    ) -> Completion<'a, C>
     where
-        F: AsRawFd,
-        B: AsIoVec + AsIoVecMut,
+        F: FnOnce(&mut io_uring_sqe),
+        C: FromCqe,
     {
-        self.with_sqe(
-            Some(iov.into_new_iovec()),
+        let [ticket, timeout_ticket] = match self
+            .pop_tickets()
+        {
+            Ok(tickets) => tickets,
+            Err(e) => return Completion::failed(self, e),
+        };
+        let (mut completion, filler) = pair(self);
+
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        let timeout_user_data =
+            self.in_flight.next_user_data(timeout_ticket);
+        completion.user_data = user_data;
+
+        self.in_flight
+            .insert(ticket, None, false, None, filler);
+        self.in_flight.insert(
+            timeout_ticket,
+            None,
             false,
+            None,
+            Filler::detached(),
+        );
+
+        // the kernel copies the timespec when the
+        // SQE is submitted, but it's simplest to
+        // keep it around until the op is done.
+        let mut keep_alive = KeepAlive::new();
+        let ts_ptr = keep_alive
+            .hold(__kernel_timespec::from(timeout));
+
+        let pushed = self.submitter.push_pair(
             |sqe| {
+                sqe.user_data = user_data;
+                f(sqe);
+                sqe.flags |= IOSQE_IO_LINK;
+                self.apply_personality(sqe);
+                self.in_flight.set_sqe(ticket, *sqe);
+            },
+            |sqe| {
+                sqe.user_data = timeout_user_data;
                 sqe.prep_rw(
-                    IORING_OP_READV,
-                    file.as_raw_fd(),
+                    IORING_OP_LINK_TIMEOUT,
+                    -1,
                     1,
-                    at,
-                    ordering,
-                )
+                    0,
+                    Ordering::None,
+                );
+                sqe.addr = ts_ptr as u64;
+                self.in_flight
+                    .set_sqe(timeout_ticket, *sqe);
             },
-        )
-    }
+        );
+        match pushed {
+            Ok(sqe_id) => completion.sqe_id = sqe_id,
+            Err(e) => self.fail_unpushed(
+                &[ticket, timeout_ticket],
+                e,
+            ),
+        }
+        completion.keep_alive = Some(keep_alive);
 
-    /// Don't do anything. This is
-    /// mostly for debugging and tuning.
-    pub fn nop<'a>(&'a self) -> Completion<'a, ()> {
-        self.nop_ordered(Ordering::None)
+        completion
     }
 
-    /// Don't do anything. This is
-    /// mostly for debugging and tuning.
-    pub fn nop_ordered<'a>(
+    pub(crate) fn with_sqe<'a, F, C>(
         &'a self,
-        ordering: Ordering,
-    ) -> Completion<'a, ()> {
-        self.with_sqe(None, false, |sqe| {
-            sqe.prep_rw(IORING_OP_NOP, 0, 1, 0, ordering)
-        })
-    }
-
-    /// Block until all items in the submission queue
-    /// are submitted to the kernel. This can
-    /// be avoided by using the `SQPOLL` mode
-    /// (a privileged operation) on the `Config`
-    /// struct.
-    ///
-    /// Note that this is performed automatically
-    /// and in a more fine-grained way when a
-    /// `Completion` is consumed via `Completion::wait`
-    /// or awaited in a Future context.
-    ///
-    /// You don't need to call this if you are
-    /// calling `.wait()` or `.await` on the
-    /// `Completion` quickly, but if you are
-    /// doing some other stuff that could take
-    /// a while first, calling this will ensure
-    /// that the operation is being executed
-    /// by the kernel in the mean time.
-    pub fn submit_all(&self) {
-        let mut sq = {
-            let _get_sq_mu = Measure::new(&M.sq_mu_wait);
-            self.sq.lock().unwrap()
-        };
-        let _hold_sq_mu = Measure::new(&M.sq_mu_hold);
-        sq.submit_all(self.flags, self.ring_fd);
+        iovec: Option<libc::iovec>,
+        msghdr: bool,
+        f: F,
+    ) -> Completion<'a, C>
+    where
+        F: FnOnce(&mut io_uring_sqe),
+        C: FromCqe,
+    {
+        self.with_msg_sqe(
+            iovec,
+            msghdr,
+            None,
+            None,
+            Resubmit::Never,
+            f,
+        )
     }
 
-    fn with_sqe<'a, F, C>(
+    /// `resubmit` says whether the reaper may
+    /// resubmit the operation instead of completing
+    /// it, see `Resubmit`.
+    fn with_msg_sqe<'a, F, C>(
         &'a self,
         iovec: Option<libc::iovec>,
         msghdr: bool,
+        msg_name: Option<MsgName>,
+        msg_control: Option<MsgControl>,
+        resubmit: Resubmit,
         f: F,
     ) -> Completion<'a, C>
     where
         F: FnOnce(&mut io_uring_sqe),
         C: FromCqe,
     {
-        let ticket = self.ticket_queue.pop();
+        let ticket = self.pop_ticket();
         let (mut completion, filler) = pair(self);
 
-        let data_ptr = self
-            .in_flight
-            .insert(ticket, iovec, msghdr, filler);
+        let user_data =
+            self.in_flight.next_user_data(ticket);
+        completion.user_data = user_data;
 
-        let mut sq = {
-            let _get_sq_mu = Measure::new(&M.sq_mu_wait);
-            self.sq.lock().unwrap()
-        };
-        let _hold_sq_mu = Measure::new(&M.sq_mu_hold);
+        let data_ptr = self.in_flight.insert(
+            ticket, iovec, msghdr, msg_name, filler,
+        );
+        if let Some(control) = msg_control {
+            self.in_flight.set_msg_control(ticket, control);
+        }
+        match resubmit {
+            Resubmit::Never => {}
+            Resubmit::SendAll => {
+                self.in_flight.set_send_all(ticket);
+            }
+            Resubmit::WithoutNowait => {
+                self.in_flight.set_nowait_fallback(ticket);
+            }
+        }
 
-        completion.sqe_id =
-            self.loaded.fetch_add(1, Release) + 1;
+        let mut drain = false;
+        let pushed = self.submitter.push(|sqe| {
+            sqe.user_data = user_data;
+            sqe.addr = data_ptr;
+            f(sqe);
+            self.apply_personality(sqe);
+            drain = sqe.flags & IOSQE_IO_DRAIN != 0;
 
-        let sqe = {
-            let _get_sqe = Measure::new(&M.get_sqe);
-            loop {
-                if let Some(sqe) =
-                    sq.try_get_sqe(self.flags)
-                {
-                    break sqe;
-                } else {
-                    let submitted = sq.submit_all(
-                        self.flags,
-                        self.ring_fd,
-                    );
-                    self.submitted
-                        .fetch_add(submitted, Release);
-                };
+            // keep a copy around in case the
+            // operation needs to be retried.
+            self.in_flight.set_sqe(ticket, *sqe);
+        });
+        match pushed {
+            Ok(sqe_id) => completion.sqe_id = sqe_id,
+            Err(e) => {
+                self.fail_unpushed(&[ticket], e);
+                return completion;
             }
-        };
+        }
 
-        sqe.user_data = ticket as u64;
-        sqe.addr = data_ptr;
-        f(sqe);
+        if drain {
+            if let Some(drain_guard) = &self.drain_guard {
+                drain_guard.note();
+            }
+        }
 
         completion
     }
 }
 
+/// When the reaper may resubmit an operation
+/// instead of completing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resubmit {
+    /// The first completion is the last.
+    Never,
+    /// A short `IORING_OP_SEND` is resubmitted for
+    /// the rest of its buffer.
+    SendAll,
+    /// A write that fails with `EAGAIN` is
+    /// resubmitted without `RWF_NOWAIT`, like
+    /// `Uring::write_at_nowait` asks for.
+    WithoutNowait,
+}
+
+/// Builds the ancillary data of a `sendmsg` that
+/// passes `fds` to another process: a single
+/// `SCM_RIGHTS` control message, in a buffer of
+/// `u64`s so that its header is aligned.
+fn scm_rights(fds: &[RawFd]) -> Vec<u64> {
+    let fds_len = u32::try_from(size_of_val(fds)).unwrap();
+    let mut control = scm_rights_space(fds.len());
+
+    #[allow(unsafe_code)]
+    unsafe {
+        let cmsg =
+            control.as_mut_ptr().cast::<libc::cmsghdr>();
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len =
+            usize::try_from(libc::CMSG_LEN(fds_len))
+                .unwrap();
+        std::ptr::copy_nonoverlapping(
+            fds.as_ptr(),
+            libc::CMSG_DATA(cmsg).cast::<RawFd>(),
+            fds.len(),
+        );
+
+        control
+    }
+}
+
+/// A zeroed buffer with room for a `SCM_RIGHTS`
+/// control message of `count` descriptors, or
+/// nothing at all if `count` is 0.
+fn scm_rights_space(count: usize) -> Vec<u64> {
+    if count == 0 {
+        return vec![];
+    }
+    let fds_len =
+        u32::try_from(count * size_of::<RawFd>()).unwrap();
+
+    #[allow(unsafe_code)]
+    let space = usize::try_from(unsafe {
+        libc::CMSG_SPACE(fds_len)
+    })
+    .unwrap();
+    vec![0_u64; space.div_ceil(size_of::<u64>())]
+}
+
+/// The control buffer of a `recv_fds`, which
+/// closes any descriptors that were received
+/// into it but never taken out, like when its
+/// `OwnedCompletion` is dropped without being
+/// waited on.
+struct ScmRightsSpace(Vec<u64>);
+
+impl Drop for ScmRightsSpace {
+    fn drop(&mut self) {
+        drop(parse_scm_rights(&mut self.0));
+    }
+}
+
+/// Takes ownership of the descriptors in the
+/// `SCM_RIGHTS` control messages that the kernel
+/// filled `control` in with. Its unused tail is
+/// still zeroed, which ends the walk over them,
+/// and it's zeroed entirely afterwards, so that
+/// they're only ever taken once.
+fn parse_scm_rights(control: &mut [u64]) -> Vec<OwnedFd> {
+    let mut fds = vec![];
+    if control.is_empty() {
+        return fds;
+    }
+
+    #[allow(unsafe_code)]
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_control =
+            control.as_ptr().cast_mut().cast();
+        msg.msg_controllen = size_of_val(control);
+
+        let data_offset =
+            usize::try_from(libc::CMSG_LEN(0)).unwrap();
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() && (*cmsg).cmsg_len != 0 {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                && (*cmsg).cmsg_type == libc::SCM_RIGHTS
+            {
+                let count = ((*cmsg).cmsg_len
+                    - data_offset)
+                    / size_of::<RawFd>();
+                let data =
+                    libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                for i in 0..count {
+                    fds.push(OwnedFd::from_raw_fd(
+                        data.add(i).read_unaligned(),
+                    ));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    control.fill(0);
+
+    fds
+}
+
 fn addr2raw(
-    addr: &std::net::SocketAddr,
+    addr: &SocketAddr,
 ) -> (*const libc::sockaddr, libc::socklen_t) {
     match *addr {
-        std::net::SocketAddr::V4(ref a) => {
-            let b: *const std::net::SocketAddrV4 = a;
+        SocketAddr::V4(ref a) => {
+            let b: *const SocketAddrV4 = a;
             (
                 b as *const _,
                 std::mem::size_of_val(a) as libc::socklen_t,
             )
         }
-        std::net::SocketAddr::V6(ref a) => {
-            let b: *const std::net::SocketAddrV6 = a;
+        SocketAddr::V6(ref a) => {
+            let b: *const SocketAddrV6 = a;
             (
                 b as *const _,
                 std::mem::size_of_val(a) as libc::socklen_t,
@@ -750,3 +5709,245 @@ fn addr2raw(
         }
     }
 }
+
+/// The first `len` bytes of `buf`, which the
+/// kernel has just filled in.
+fn assume_init_prefix(
+    buf: &mut [MaybeUninit<u8>],
+    len: usize,
+) -> &mut [u8] {
+    let prefix = &mut buf[..len];
+    #[allow(unsafe_code)]
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            prefix.as_mut_ptr().cast::<u8>(),
+            prefix.len(),
+        )
+    }
+}
+
+/// Returns the offset just past an operation
+/// of `len` bytes at `at`, or an error if that
+/// does not fit in the kernel's signed 64-bit
+/// file offsets. Without this, the kernel sees
+/// a negative or wrapped offset instead.
+pub(crate) fn checked_end(
+    at: u64,
+    len: usize,
+) -> io::Result<u64> {
+    u64::try_from(len)
+        .ok()
+        .and_then(|len64| at.checked_add(len64))
+        .filter(|end| i64::try_from(*end).is_ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "an operation of {} bytes at offset {} \
+                     would go past the largest possible \
+                     file offset",
+                    len, at
+                ),
+            )
+        })
+}
+
+/// Shuts down and closes `fd` right away, for
+/// when `Uring::shutdown_close_detached` can't
+/// do it on the ring.
+fn shutdown_close(fd: RawFd) {
+    #[allow(unsafe_code)]
+    unsafe {
+        let _ = libc::shutdown(fd, libc::SHUT_RDWR);
+        let _ = libc::close(fd);
+    }
+}
+
+fn socklen_of<T>() -> libc::socklen_t {
+    libc::socklen_t::try_from(size_of::<T>()).unwrap()
+}
+
+fn addr2storage(
+    addr: &SocketAddr,
+) -> (libc::sockaddr_storage, libc::socklen_t) {
+    #[allow(unsafe_code)]
+    let mut storage: libc::sockaddr_storage =
+        unsafe { std::mem::zeroed() };
+    let storage_ptr: *mut libc::sockaddr_storage =
+        &mut storage;
+
+    match addr {
+        SocketAddr::V4(a) => {
+            let sin = libc::sockaddr_in {
+                sin_family: libc::sa_family_t::try_from(
+                    libc::AF_INET,
+                )
+                .unwrap(),
+                sin_port: a.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from(*a.ip()).to_be(),
+                },
+                sin_zero: [0; 8],
+            };
+            #[allow(unsafe_code)]
+            unsafe {
+                storage_ptr
+                    .cast::<libc::sockaddr_in>()
+                    .write(sin)
+            };
+            (storage, socklen_of::<libc::sockaddr_in>())
+        }
+        SocketAddr::V6(a) => {
+            let sin6 = libc::sockaddr_in6 {
+                sin6_family: libc::sa_family_t::try_from(
+                    libc::AF_INET6,
+                )
+                .unwrap(),
+                sin6_port: a.port().to_be(),
+                sin6_flowinfo: a.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: a.ip().octets(),
+                },
+                sin6_scope_id: a.scope_id(),
+            };
+            #[allow(unsafe_code)]
+            unsafe {
+                storage_ptr
+                    .cast::<libc::sockaddr_in6>()
+                    .write(sin6)
+            };
+            (storage, socklen_of::<libc::sockaddr_in6>())
+        }
+    }
+}
+
+fn storage2addr(
+    storage: &libc::sockaddr_storage,
+) -> io::Result<SocketAddr> {
+    let storage_ptr: *const libc::sockaddr_storage =
+        storage;
+
+    match i32::from(storage.ss_family) {
+        libc::AF_INET => {
+            #[allow(unsafe_code)]
+            let sin = unsafe {
+                &*storage_ptr.cast::<libc::sockaddr_in>()
+            };
+            Ok(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(
+                    sin.sin_addr.s_addr,
+                )),
+                u16::from_be(sin.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            #[allow(unsafe_code)]
+            let sin6 = unsafe {
+                &*storage_ptr.cast::<libc::sockaddr_in6>()
+            };
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(sin6.sin6_addr.s6_addr),
+                u16::from_be(sin6.sin6_port),
+                sin6.sin6_flowinfo,
+                sin6.sin6_scope_id,
+            )))
+        }
+        family => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "received unsupported address family {}",
+                family
+            ),
+        )),
+    }
+}
+
+/// Whether pinning `iovecs` would take more
+/// memory than the memlock rlimit allows. The
+/// kernel pins whole pages, so a buffer is
+/// charged for every page that it touches. This
+/// is only used to explain an `ENOMEM`, since
+/// whether the kernel enforces the rlimit at all
+/// depends on its version and our capabilities.
+fn exceeds_memlock(iovecs: &[libc::iovec]) -> bool {
+    #[allow(unsafe_code)]
+    let page_size = usize::try_from(unsafe {
+        libc::sysconf(libc::_SC_PAGESIZE)
+    })
+    .unwrap();
+
+    let pinned: usize = iovecs
+        .iter()
+        .map(|iov| {
+            let start = iov.iov_base as usize;
+            let end = start + iov.iov_len;
+            let first_page = start / page_size;
+            let last_page = end.div_ceil(page_size);
+            (last_page - first_page) * page_size
+        })
+        .sum();
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    #[allow(unsafe_code)]
+    let ret = unsafe {
+        libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit)
+    };
+
+    ret == 0
+        && limit.rlim_cur != libc::RLIM_INFINITY
+        && u64::try_from(pinned).unwrap() > limit.rlim_cur
+}
+
+/// Creates a pipe that can hold at least `len`
+/// bytes, returning its read and write ends.
+fn pipe_holding(
+    len: usize,
+) -> io::Result<(OwnedFd, OwnedFd)> {
+    let size =
+        libc::c_int::try_from(len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "len is too large to fit in a pipe",
+            )
+        })?;
+
+    let mut fds = [0; 2];
+    #[allow(unsafe_code)]
+    let ret = unsafe {
+        libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC)
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[allow(unsafe_code)]
+    let (read, write) = unsafe {
+        (
+            OwnedFd::from_raw_fd(fds[0]),
+            OwnedFd::from_raw_fd(fds[1]),
+        )
+    };
+
+    #[allow(unsafe_code)]
+    let capacity = unsafe {
+        libc::fcntl(read.as_raw_fd(), libc::F_GETPIPE_SZ)
+    };
+    if capacity < size {
+        #[allow(unsafe_code)]
+        let resized = unsafe {
+            libc::fcntl(
+                read.as_raw_fd(),
+                libc::F_SETPIPE_SZ,
+                size,
+            )
+        };
+        if resized < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok((read, write))
+}
@@ -60,6 +60,31 @@
 //! // now it's safe to drop those things in any order.
 //! ```
 //!
+//! The same goes for file descriptors that are
+//! only borrowed, as a `BorrowedFd`, which works
+//! anywhere a file does. A `BorrowedFd` can't
+//! outlive whatever owns the fd, and the
+//! `Completion` can't outlive the `BorrowedFd`,
+//! so closing the fd while an operation is
+//! still using it won't compile either:
+//!
+//! ```compile_fail
+//! use std::os::unix::io::AsFd;
+//!
+//! let rio = rio::new().unwrap();
+//! let file = std::fs::File::open("file").unwrap();
+//! let fd = file.as_fd();
+//! let buf = vec![0; 64];
+//!
+//! let completion = rio.read_at(&fd, &buf, 0);
+//!
+//! // fails, because `fd` borrows `file`, and the
+//! // completion borrows `fd`
+//! drop(file);
+//!
+//! completion.wait();
+//! ```
+//!
 //!
 //! Really shines with O_DIRECT:
 //!
@@ -203,15 +228,31 @@ mod metrics;
 mod io_uring;
 
 #[cfg(target_os = "linux")]
-pub use io_uring::{Config, Ordering, Rio, Uring};
+pub use io_uring::{
+    consts, io_uring_cqe, io_uring_sqe, kernel_info,
+    BenchResult, BuffersRegistration, CacheHint, Config,
+    Cursor, Expired, FilledBuf, FilesRegistration,
+    KernelInfo, Ordering, PollFlags, PollMultishot, PoolBuf,
+    PreppedOp, RecvMultishot, RecvResult, RegisteredFd,
+    ResolvedAddr, RingBuf, RingMessage, RingState, Rio,
+    RioStream, RwFlags, SocketAddrStorage, SubmitStats,
+    SyncFileRangeFlags, TimeoutToken, TransientRetry, Uring,
+    VectoredStop,
+};
 
-pub use completion::Completion;
+pub use {
+    completion::{Completion, KeepAlive, OwnedCompletion},
+    metrics::MetricsSnapshot,
+};
+
+#[cfg(all(target_os = "linux", feature = "testing"))]
+pub use {completion::Injector, io_uring::SqFault};
 
 use {
-    completion::{pair, Filler},
+    completion::{pair, Filler, Wakeup},
     histogram::Histogram,
     lazy::Lazy,
-    metrics::{Measure, M},
+    metrics::Metrics,
 };
 
 /// Create a new IO system.
@@ -219,8 +260,54 @@ pub fn new() -> io::Result<Rio> {
     Config::default().start()
 }
 
+/// Create a new IO system with a submission
+/// queue of the given depth, which must be
+/// between 1 and 32768 (inclusive), and is
+/// rounded up to a power of two.
+///
+/// # Examples
+///
+/// ```no_run
+/// let ring = rio::with_depth(64).unwrap();
+///
+/// assert_eq!(ring.sq_capacity(), 64);
+/// ```
+pub fn with_depth(depth: usize) -> io::Result<Rio> {
+    Config {
+        depth,
+        ..Config::default()
+    }
+    .start()
+}
+
 /// Encompasses various types of IO structures that
 /// can be operated on as if they were a libc::iovec
+///
+/// # Pinned buffers
+///
+/// A buffer like a `Pin<Box<[u8]>>` is written
+/// by dereferencing it to the slice that it pins,
+/// and read into by binding a `&mut [u8]` to it,
+/// just like any other mutable buffer. Its memory
+/// is guaranteed not to move, but the borrow held
+/// by the `Completion` already keeps any buffer
+/// in place until the kernel is done with it, so
+/// both are equally safe to use.
+///
+/// ```no_run
+/// use std::pin::Pin;
+///
+/// let ring = rio::new().unwrap();
+/// let file = std::fs::File::create("pinned").unwrap();
+///
+/// let mut buf: Pin<Box<[u8]>> =
+///     Box::pin(*b"pinned buffer");
+///
+/// ring.write_at(&file, &*buf, 0).wait().unwrap();
+///
+/// let slice: &mut [u8] = &mut buf;
+/// ring.read_at(&file, &slice, 0).wait().unwrap();
+/// ```
 pub trait AsIoVec {
     /// Returns the address of this object.
     fn into_new_iovec(&self) -> libc::iovec;
@@ -295,16 +382,35 @@ impl<A: ?Sized + AsMut<[u8]>> AsIoVecMut for A {}
 pub trait FromCqe {
     /// Describes a conversion from a successful
     /// `io_uring_cqe` to a desired output type.
-    fn from_cqe(cqe: io_uring::io_uring_cqe) -> Self;
+    fn from_cqe(cqe: io_uring_cqe) -> Self;
+
+    /// Describes a conversion from the result of
+    /// an operation, which may have failed, to a
+    /// desired output type. By default, errors
+    /// are passed through as-is.
+    fn from_result(
+        res: io::Result<io_uring_cqe>,
+    ) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        res.map(Self::from_cqe)
+    }
 }
 
 impl FromCqe for usize {
-    fn from_cqe(cqe: io_uring::io_uring_cqe) -> usize {
+    fn from_cqe(cqe: io_uring_cqe) -> usize {
         use std::convert::TryFrom;
         usize::try_from(cqe.res).unwrap()
     }
 }
 
 impl FromCqe for () {
-    fn from_cqe(_: io_uring::io_uring_cqe) {}
+    fn from_cqe(_: io_uring_cqe) {}
+}
+
+impl FromCqe for io_uring_cqe {
+    fn from_cqe(cqe: io_uring_cqe) -> io_uring_cqe {
+        cqe
+    }
 }
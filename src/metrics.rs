@@ -1,8 +1,7 @@
 #![allow(unused_results)]
 #![allow(clippy::print_stdout)]
 
-#[cfg(feature = "measure_allocs")]
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
 
 #[cfg(not(target_arch = "x86_64"))]
 use std::time::{Duration, Instant};
@@ -14,8 +13,8 @@ use crate::Lazy;
 
 use super::*;
 
-/// A metric collector for all pagecache users running in this
-/// process.
+/// A metric collector for all rings running in this process
+/// with `Config::aggregate_metrics` set.
 pub static M: Lazy<Metrics, fn() -> Metrics> =
     Lazy::new(Metrics::default);
 
@@ -58,6 +57,8 @@ pub struct Measure<'h> {
     _start: f64,
     #[cfg(not(feature = "no_metrics"))]
     histo: &'h Histogram,
+    #[cfg(not(feature = "no_metrics"))]
+    aggregate: Option<&'h Histogram>,
     #[cfg(feature = "no_metrics")]
     _pd: PhantomData<&'h ()>,
 }
@@ -71,6 +72,8 @@ impl<'h> Measure<'h> {
             _pd: PhantomData,
             #[cfg(not(feature = "no_metrics"))]
             histo: _histo,
+            #[cfg(not(feature = "no_metrics"))]
+            aggregate: None,
             _start: clock(),
         }
     }
@@ -80,10 +83,43 @@ impl<'h> Drop for Measure<'h> {
     #[inline]
     fn drop(&mut self) {
         #[cfg(not(feature = "no_metrics"))]
-        self.histo.measure(clock() - self._start);
+        {
+            let delta = clock() - self._start;
+            self.histo.measure(delta);
+            if let Some(aggregate) = self.aggregate {
+                aggregate.measure(delta);
+            }
+        }
     }
 }
 
+/// A snapshot of the activity of a single ring,
+/// returned by `Uring::metrics_snapshot`. Apart
+/// from `ops` and `sq_poll_wakeups`, these are
+/// always 0 with the `no_metrics` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The number of operations that have been
+    /// pushed onto the submission queue,
+    /// including any that were retried.
+    pub ops: u64,
+    /// The number of times that a thread has
+    /// waited on a completion.
+    pub waits: u64,
+    /// The number of calls to `io_uring_enter`
+    /// that submitted SQEs.
+    pub sq_enters: u64,
+    /// The number of calls to `io_uring_enter`
+    /// that waited for completions.
+    pub cq_enters: u64,
+    /// The number of batches of completions
+    /// that have been reaped.
+    pub reaps: u64,
+    /// The number of times that the SQPOLL
+    /// kernel thread had to be woken up.
+    pub sq_poll_wakeups: u64,
+}
+
 #[derive(Default, Debug)]
 pub struct Metrics {
     pub sq_mu_wait: Histogram,
@@ -95,8 +131,13 @@ pub struct Metrics {
     pub get_sqe: Histogram,
     pub reap_ready: Histogram,
     pub wait: Histogram,
+    pub ping: Histogram,
     pub ticket_queue_push: Histogram,
     pub ticket_queue_pop: Histogram,
+    pub sq_poll_wakeups: AtomicU64,
+    pub ops: AtomicU64,
+    /// Whether to also record everything into `M`.
+    pub aggregate: bool,
 
     #[cfg(feature = "measure_allocs")]
     pub allocations: AtomicU64,
@@ -104,15 +145,211 @@ pub struct Metrics {
     pub allocated_bytes: AtomicU64,
 }
 
-impl Drop for Metrics {
-    fn drop(&mut self) {
+impl Metrics {
+    /// Measures an event into the histogram picked
+    /// out by `histo`, and into the same one in `M`
+    /// if this ring's metrics are aggregated.
+    pub(crate) fn measure(
+        &self,
+        histo: fn(&Metrics) -> &Histogram,
+    ) -> Measure<'_> {
+        #[allow(unused_mut)]
+        let mut measure = Measure::new(histo(self));
         #[cfg(not(feature = "no_metrics"))]
-        self.print_profile()
+        if self.aggregate {
+            measure.aggregate = Some(histo(&M));
+        }
+        measure
     }
-}
 
-#[cfg(not(feature = "no_metrics"))]
-impl Metrics {
+    /// Counts `n` operations pushed onto the
+    /// submission queue.
+    pub(crate) fn add_ops(&self, n: u64) {
+        self.ops.fetch_add(n, Relaxed);
+        if self.aggregate {
+            M.ops.fetch_add(n, Relaxed);
+        }
+    }
+
+    /// Counts a wakeup of the SQPOLL thread.
+    pub(crate) fn add_sq_poll_wakeup(&self) {
+        self.sq_poll_wakeups.fetch_add(1, Relaxed);
+        if self.aggregate {
+            M.sq_poll_wakeups.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            ops: self.ops.load(Relaxed),
+            waits: self.wait.count(),
+            sq_enters: self.enter_sqe.count(),
+            cq_enters: self.enter_cqe.count(),
+            reaps: self.reap_ready.count(),
+            sq_poll_wakeups: self
+                .sq_poll_wakeups
+                .load(Relaxed),
+        }
+    }
+
+    /// Renders these metrics in the Prometheus text
+    /// exposition format, with each histogram as a
+    /// summary labeled by the stage it measures.
+    #[cfg(feature = "prometheus")]
+    #[allow(
+        clippy::float_arithmetic,
+        clippy::cast_precision_loss
+    )]
+    pub(crate) fn prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let histos: [(&str, &str, &Histogram, &str); 12] = [
+            (
+                "sq_mu_wait",
+                "sq",
+                &self.sq_mu_wait,
+                "waiting for the submission queue lock",
+            ),
+            (
+                "sq_mu_hold",
+                "sq",
+                &self.sq_mu_hold,
+                "holding the submission queue lock",
+            ),
+            (
+                "enter_sqe",
+                "sq",
+                &self.enter_sqe,
+                "in io_uring_enter submitting SQEs",
+            ),
+            (
+                "get_sqe",
+                "sq",
+                &self.get_sqe,
+                "waiting for a free SQE",
+            ),
+            (
+                "ticket_queue_pop",
+                "sq",
+                &self.ticket_queue_pop,
+                "waiting for a free ticket",
+            ),
+            (
+                "cq_mu_wait",
+                "cq",
+                &self.cq_mu_wait,
+                "waiting for the completion queue lock",
+            ),
+            (
+                "cq_mu_hold",
+                "cq",
+                &self.cq_mu_hold,
+                "holding the completion queue lock",
+            ),
+            (
+                "enter_cqe",
+                "cq",
+                &self.enter_cqe,
+                "in io_uring_enter waiting for CQEs",
+            ),
+            (
+                "ticket_queue_push",
+                "cq",
+                &self.ticket_queue_push,
+                "returning tickets after completion",
+            ),
+            (
+                "reap_ready",
+                "wait",
+                &self.reap_ready,
+                "reaping ready completions",
+            ),
+            (
+                "wait",
+                "wait",
+                &self.wait,
+                "waiting on completions",
+            ),
+            (
+                "ping",
+                "wait",
+                &self.ping,
+                "on a round trip through the ring",
+            ),
+        ];
+
+        let mut out = String::new();
+        for (name, stage, histo, help) in &histos {
+            let metric = format!("rio_{}_seconds", name);
+            writeln!(
+                out,
+                "# HELP {} Time spent {}.",
+                metric, help
+            )
+            .unwrap();
+            writeln!(out, "# TYPE {} summary", metric)
+                .unwrap();
+            for q in &[0.5, 0.9, 0.99, 0.999] {
+                writeln!(
+                    out,
+                    "{}{{stage=\"{}\",quantile=\"{}\"}} {}",
+                    metric,
+                    stage,
+                    q,
+                    histo.percentile(q * 100.) / 1e9,
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "{}_sum{{stage=\"{}\"}} {}",
+                metric,
+                stage,
+                histo.sum() as f64 / 1e9,
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{}_count{{stage=\"{}\"}} {}",
+                metric,
+                stage,
+                histo.count(),
+            )
+            .unwrap();
+        }
+
+        let counters: [(&str, &AtomicU64, &str); 2] = [
+            (
+                "ops",
+                &self.ops,
+                "Ops pushed onto the submission queue.",
+            ),
+            (
+                "sq_poll_wakeups",
+                &self.sq_poll_wakeups,
+                "Wakeups of the SQPOLL kernel thread.",
+            ),
+        ];
+
+        for (name, counter, help) in &counters {
+            let metric = format!("rio_{}_total", name);
+            writeln!(out, "# HELP {} {}", metric, help)
+                .unwrap();
+            writeln!(out, "# TYPE {} counter", metric)
+                .unwrap();
+            writeln!(
+                out,
+                "{} {}",
+                metric,
+                counter.load(Relaxed)
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    #[cfg(not(feature = "no_metrics"))]
     pub fn print_profile(&self) {
         println!(
             "rio profile:\n\
@@ -181,6 +418,10 @@ impl Metrics {
             lat("enter sqe", &self.enter_sqe),
             lat("ticket q pop", &self.ticket_queue_pop),
         ]);
+        println!(
+            "sq poll wakeups: {}",
+            self.sq_poll_wakeups.load(Relaxed)
+        );
 
         println!(
             "{}",
@@ -206,6 +447,7 @@ impl Metrics {
         p(vec![
             lat("reap_ready", &self.reap_ready),
             lat("wait", &self.wait),
+            lat("ping", &self.ping),
         ]);
 
         println!(
@@ -0,0 +1,328 @@
+#![cfg(feature = "testing")]
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake},
+};
+
+fn cqe(res: i32) -> rio::io_uring_cqe {
+    rio::io_uring_cqe {
+        user_data: 0,
+        res,
+        flags: 0,
+    }
+}
+
+#[derive(Default)]
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_inject_wait() {
+    let ring = rio::new().unwrap();
+
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    injector.fill(Ok(cqe(7)));
+    assert_eq!(completion.wait().unwrap(), 7);
+
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    injector.fill(Err(io::Error::from_raw_os_error(
+        libc::EBADF,
+    )));
+    assert_eq!(
+        completion.wait().unwrap_err().raw_os_error(),
+        Some(libc::EBADF)
+    );
+
+    // an injector that is never filled cancels
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    drop(injector);
+    assert_eq!(
+        completion.wait().unwrap_err().raw_os_error(),
+        Some(libc::ECANCELED)
+    );
+}
+
+#[cfg(not(feature = "single_thread"))]
+#[test]
+fn test_inject_wait_blocks_until_filled() {
+    use std::time::{Duration, Instant};
+
+    let ring = rio::new().unwrap();
+
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    let before = Instant::now();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            injector.fill(Ok(cqe(3)));
+        });
+        assert_eq!(completion.wait().unwrap(), 3);
+    });
+    assert!(before.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn test_inject_poll() {
+    let ring = rio::new().unwrap();
+
+    let flag = Arc::new(Flag::default());
+    let waker = flag.clone().into();
+    let mut cx = Context::from_waker(&waker);
+
+    let (mut completion, injector) =
+        ring.inject_completion::<usize>();
+    assert!(Pin::new(&mut completion)
+        .poll(&mut cx)
+        .is_pending());
+
+    // without a reaper, polling wakes itself so
+    // that it gets polled again to reap
+    #[cfg(not(feature = "single_thread"))]
+    assert!(!flag.0.load(Ordering::SeqCst));
+
+    injector.fill(Ok(cqe(5)));
+    assert!(flag.0.load(Ordering::SeqCst));
+    match Pin::new(&mut completion).poll(&mut cx) {
+        Poll::Ready(res) => assert_eq!(res.unwrap(), 5),
+        Poll::Pending => panic!("filled but pending"),
+    }
+}
+
+#[test]
+fn test_inject_drop_reports_errors() {
+    let errors = Arc::new(AtomicUsize::new(0));
+    let handler_errors = errors.clone();
+    let config = rio::Config {
+        drop_error_handler: Some(Arc::new(move |e| {
+            assert_eq!(e.raw_os_error(), Some(libc::EIO));
+            handler_errors.fetch_add(1, Ordering::SeqCst);
+        })),
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    injector
+        .fill(Err(io::Error::from_raw_os_error(libc::EIO)));
+    drop(completion);
+    assert_eq!(errors.load(Ordering::SeqCst), 1);
+
+    // successes are not reported
+    let (completion, injector) =
+        ring.inject_completion::<usize>();
+    injector.fill(Ok(cqe(0)));
+    drop(completion);
+    assert_eq!(errors.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(not(feature = "single_thread"))]
+#[test]
+fn test_inject_drop_waits_until_filled() {
+    use std::time::{Duration, Instant};
+
+    let ring = rio::new().unwrap();
+
+    let flag = Arc::new(Flag::default());
+    let waker = flag.into();
+    let mut cx = Context::from_waker(&waker);
+
+    let (mut completion, injector) =
+        ring.inject_completion::<usize>();
+    assert!(Pin::new(&mut completion)
+        .poll(&mut cx)
+        .is_pending());
+
+    // dropping a polled completion tries to cancel
+    // it, but still waits for it to be filled
+    let before = Instant::now();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            injector.fill(Ok(cqe(1)));
+        });
+        drop(completion);
+    });
+    assert!(before.elapsed() >= Duration::from_millis(50));
+}
+
+#[cfg(not(feature = "single_thread"))]
+#[test]
+fn test_inject_drop_with_every_ticket_taken() {
+    use std::{
+        io::Write, os::unix::net::UnixStream,
+        time::Duration,
+    };
+
+    // depth 1 gives the ring just 2 tickets
+    let ring = rio::with_depth(1).unwrap();
+    let (mut a, b) = UnixStream::pair().unwrap();
+
+    let buf1: &mut [u8] = &mut [0; 1];
+    let buf2: &mut [u8] = &mut [0; 1];
+    let recv1 = ring.recv(&b, &buf1);
+    let recv2 = ring.recv(&b, &buf2);
+    ring.submit_all();
+
+    let flag = Arc::new(Flag::default());
+    let waker = flag.into();
+    let mut cx = Context::from_waker(&waker);
+
+    let (mut completion, injector) =
+        ring.inject_completion::<usize>();
+    assert!(Pin::new(&mut completion)
+        .poll(&mut cx)
+        .is_pending());
+
+    // there's no ticket to cancel it with, and
+    // none frees up until the recvs complete, so
+    // the drop just waits for it to be filled
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            injector.fill(Ok(cqe(1)));
+        });
+        drop(completion);
+    });
+
+    a.write_all(b"ab").unwrap();
+    assert_eq!(recv1.wait().unwrap(), 1);
+    assert_eq!(recv2.wait().unwrap(), 1);
+}
+
+#[test]
+fn test_panic_diagnostics() {
+    if std::env::var_os("RIO_PANIC_DIAGNOSTICS_CHILD")
+        .is_some()
+    {
+        let config = rio::Config {
+            panic_diagnostics: true,
+            ..rio::Config::default()
+        };
+        let ring = config.start().unwrap();
+
+        // rio panics on a negative result that isn't
+        // an error, which the kernel never returns
+        let (completion, injector) =
+            ring.inject_completion::<usize>();
+        injector.fill(Ok(cqe(-1)));
+        let _ = completion.wait();
+        return;
+    }
+
+    // the hook is process-wide, so this runs the
+    // panic in a child process to read its output
+    let output = std::process::Command::new(
+        std::env::current_exe().unwrap(),
+    )
+    .args(["--exact", "test_panic_diagnostics"])
+    .arg("--nocapture")
+    .env("RIO_PANIC_DIAGNOSTICS_CHILD", "1")
+    .output()
+    .unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("rio ring fd "), "{}", stderr);
+    assert!(
+        stderr.contains("0 ops in-flight"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("MetricsSnapshot"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_inject_sq_fault() {
+    let ring = rio::new().unwrap();
+
+    // each fault fails the submission that runs into
+    // it, instead of taking the process down
+    for &fault in
+        &[rio::SqFault::TailMoved, rio::SqFault::Dropped]
+    {
+        ring.inject_sq_fault(fault);
+        let err = ring.submit_stats().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+
+        // and the fault only happens once, so the
+        // next submission doesn't report it again
+        ring.submit_stats().unwrap();
+        ring.nop().wait().unwrap();
+    }
+}
+
+#[test]
+fn test_wait_after_sq_fault() {
+    let ring = rio::new().unwrap();
+
+    // the SQEs that a faulty submission handed to
+    // the kernel still complete, and waiting on
+    // them doesn't try to submit them again
+    for &fault in
+        &[rio::SqFault::TailMoved, rio::SqFault::Dropped]
+    {
+        let nop = ring.nop();
+        ring.inject_sq_fault(fault);
+        assert!(ring.submit_stats().is_err());
+        nop.wait().unwrap();
+        ring.nop().wait().unwrap();
+    }
+}
+
+#[test]
+fn test_wait_reports_sq_fault() {
+    let ring = rio::new().unwrap();
+
+    // waiting on an op whose submission runs into a
+    // fault fails with it instead of panicking, and
+    // the op is still waited for as it's dropped
+    for &fault in
+        &[rio::SqFault::TailMoved, rio::SqFault::Dropped]
+    {
+        let nop = ring.nop();
+        ring.inject_sq_fault(fault);
+        let err = nop.wait().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        ring.nop().wait().unwrap();
+    }
+
+    // and so does polling it
+    let waker = Arc::new(Flag::default()).into();
+    let mut cx = Context::from_waker(&waker);
+    for &fault in
+        &[rio::SqFault::TailMoved, rio::SqFault::Dropped]
+    {
+        let mut nop = ring.nop();
+        ring.inject_sq_fault(fault);
+        match Pin::new(&mut nop).poll(&mut cx) {
+            Poll::Ready(res) => assert_eq!(
+                res.unwrap_err().kind(),
+                io::ErrorKind::Other
+            ),
+            Poll::Pending => panic!("failed but pending"),
+        }
+        drop(nop);
+        ring.nop().wait().unwrap();
+    }
+}
@@ -0,0 +1,355 @@
+#[test]
+fn test_config_from_env() {
+    std::env::set_var("RIO_DEPTH", "64");
+    std::env::set_var("RIO_SQ_POLL", "true");
+    std::env::set_var("RIO_SQ_POLL_AFFINITY", "3");
+    std::env::set_var("RIO_PRINT_PROFILE", "0");
+
+    let config = rio::Config::from_env().unwrap();
+    assert_eq!(config.depth, 64);
+    assert!(config.sq_poll);
+    assert_eq!(config.sq_poll_affinity, 3);
+    assert!(!config.print_profile_on_drop);
+
+    std::env::set_var("RIO_DEPTH", "0");
+    let err = rio::Config::from_env().unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    std::env::set_var("RIO_DEPTH", "64");
+    std::env::set_var("RIO_SQ_POLL", "maybe");
+    let err = rio::Config::from_env().unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    for var in &[
+        "RIO_DEPTH",
+        "RIO_SQ_POLL",
+        "RIO_SQ_POLL_AFFINITY",
+        "RIO_PRINT_PROFILE",
+    ] {
+        std::env::remove_var(var);
+    }
+}
+
+#[test]
+fn test_suggest_depth() {
+    assert_eq!(rio::Config::suggest_depth(200), 256);
+    assert_eq!(rio::Config::suggest_depth(256), 256);
+    assert_eq!(rio::Config::suggest_depth(0), 16);
+    assert_eq!(rio::Config::suggest_depth(3), 16);
+    assert_eq!(rio::Config::suggest_depth(4097), 4096);
+    assert_eq!(
+        rio::Config::suggest_depth(usize::MAX),
+        4096
+    );
+
+    let config = rio::Config {
+        depth: rio::Config::suggest_depth(1000),
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+    assert_eq!(ring.sq_capacity(), 1024);
+}
+
+#[test]
+fn test_warn_on_drain() {
+    let ring = rio::Config {
+        warn_on_drain: true,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    for _ in 0..10 {
+        ring.nop_ordered(rio::Ordering::Drain)
+            .wait()
+            .unwrap();
+    }
+    assert_eq!(ring.drain_warnings(), 0);
+
+    for _ in 0..2000 {
+        ring.nop_ordered(rio::Ordering::Drain)
+            .wait()
+            .unwrap();
+    }
+    assert!(ring.drain_warnings() >= 1);
+
+    let quiet = rio::new().unwrap();
+    for _ in 0..2000 {
+        quiet
+            .nop_ordered(rio::Ordering::Drain)
+            .wait()
+            .unwrap();
+    }
+    assert_eq!(quiet.drain_warnings(), 0);
+}
+
+#[test]
+fn test_reap_batch_max() {
+    let ring = rio::Config {
+        depth: 8,
+        reap_batch_max: 1,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    // more than fit in the completion queue, so
+    // submitting them all relies on tickets being
+    // handed back as completions are reaped.
+    let completions: Vec<_> =
+        (0..1000).map(|_| ring.nop()).collect();
+    for completion in completions {
+        completion.wait().unwrap();
+    }
+}
+
+#[test]
+fn test_multi_sqe_tiny_depth() {
+    use std::{os::unix::net::UnixStream, time::Duration};
+
+    // depth 2 gives the ring 4 tickets, fewer than
+    // there are threads that each need 2 at once,
+    // which used to leave them each holding one
+    // while waiting for another forever
+    let ring = rio::Config {
+        depth: 2,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    let path = std::env::temp_dir().join(format!(
+        "rio_multi_sqe_tiny_depth_{}",
+        std::process::id()
+    ));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    std::thread::scope(|s| {
+        for thread in 0..8_u64 {
+            let ring = &ring;
+            let file = &file;
+            s.spawn(move || {
+                let (_a, b) = UnixStream::pair().unwrap();
+                let at = thread * 8;
+                let out = [b'a' + thread as u8; 8];
+                let buf = vec![0; 8];
+                for _ in 0..50 {
+                    ring.write_at_preallocated(
+                        file, &out, at,
+                    )
+                    .wait()
+                    .unwrap();
+                    ring.datasync_range(file, at, 8)
+                        .wait()
+                        .unwrap();
+                    let (write, read) = ring.linked_pair(
+                        rio::PreppedOp::write_at(
+                            file, &out, at,
+                        ),
+                        rio::PreppedOp::read_at(
+                            file, &buf, at,
+                        ),
+                    );
+                    write.wait().unwrap();
+                    assert_eq!(read.wait().unwrap(), 8);
+                    assert_eq!(buf, out);
+                    let idle = ring
+                        .recv_timeout(
+                            &b,
+                            &buf,
+                            Duration::from_micros(100),
+                        )
+                        .wait()
+                        .unwrap();
+                    assert_eq!(idle, None);
+                }
+            });
+        }
+    });
+
+    // a chain that needs more room than the ring
+    // has fails instead of waiting forever
+    let (_a, b) = UnixStream::pair().unwrap();
+    let err = ring
+        .send_header_and_file(&b, b"header", &file, 0, 8)
+        .wait()
+        .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_ticket_return_batch() {
+    use std::os::unix::net::UnixStream;
+
+    // a batch that is never reached, and a delay
+    // that never runs out, so tickets are only
+    // handed back when the reaper runs dry
+    let ring = rio::Config {
+        depth: 4,
+        ticket_return_batch: 1000,
+        ticket_return_delay_us: u32::MAX,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    let completions: Vec<_> =
+        (0..1000).map(|_| ring.nop()).collect();
+    for completion in completions {
+        completion.wait().unwrap();
+    }
+
+    // the recv only completes once the send is
+    // submitted, which needs one of the tickets
+    // that the reaper would otherwise be holding
+    let (a, b) = UnixStream::pair().unwrap();
+    let buf = vec![0; 2];
+    let nops: Vec<_> = (0..2).map(|_| ring.nop()).collect();
+    let recv = ring.recv(&a, &buf);
+    for nop in nops {
+        nop.wait().unwrap();
+    }
+    ring.send(&b, b"hi").wait().unwrap();
+    assert_eq!(recv.wait().unwrap(), 2);
+    assert_eq!(buf, b"hi");
+}
+
+#[cfg(not(feature = "no_metrics"))]
+#[test]
+fn test_submit_batch_min() {
+    fn sq_enters(submit_batch_min: usize) -> u64 {
+        let ring = rio::Config {
+            submit_batch_min,
+            submit_batch_timeout_us: u32::MAX,
+            ..rio::Config::default()
+        }
+        .start()
+        .unwrap();
+
+        let before = ring.metrics_snapshot().sq_enters;
+        let nops: Vec<_> = (0..32)
+            .map(|_| {
+                let nop = ring.nop();
+                ring.submit_all();
+                nop
+            })
+            .collect();
+        for nop in nops {
+            nop.wait().unwrap();
+        }
+        ring.metrics_snapshot().sq_enters - before
+    }
+
+    // every submit_all enters the kernel without
+    // batching, but only every 8th does with it
+    assert_eq!(sq_enters(0), 32);
+    assert_eq!(sq_enters(8), 4);
+
+    // a batch that never fills is still submitted
+    // once something waits on it
+    let ring = rio::Config {
+        submit_batch_min: 8,
+        submit_batch_timeout_us: u32::MAX,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+    let nop = ring.nop();
+    ring.submit_all();
+    nop.wait().unwrap();
+}
+
+#[test]
+fn test_cq_spin_us() {
+    let ring = rio::Config {
+        cq_spin_us: Some(50),
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    // completions that arrive while the reaper
+    // spins and ones that show up after it has
+    // given up and blocked are both reaped
+    for _ in 0..100 {
+        ring.nop().wait().unwrap();
+    }
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    ring.nop().wait().unwrap();
+}
+
+#[test]
+fn test_group_by_fd() {
+    use std::{
+        io::Read,
+        os::unix::io::{AsRawFd, FromRawFd},
+    };
+
+    let ring = rio::Config {
+        group_by_fd: true,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    // two fds for the write end of the same pipe, so
+    // the order that writes through them were
+    // submitted in shows up in what's read out
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let mut reader =
+        unsafe { std::fs::File::from_raw_fd(fds[0]) };
+    let writer =
+        unsafe { std::fs::File::from_raw_fd(fds[1]) };
+    let other = writer.try_clone().unwrap();
+
+    // reading from the write end fails, which
+    // cancels whatever it's linked to
+    let buf = vec![0_u8; 1];
+    let a = ring.write_at(&other, b"a", 0);
+    let (failing, linked) = ring.linked_pair(
+        rio::PreppedOp::read_at(&writer, &buf, 0),
+        rio::PreppedOp::write_at(&other, b"x", 0),
+    );
+    let b = ring.write_at(&writer, b"b", 0);
+    let c = ring.write_at(&other, b"c", 0);
+    ring.submit_all();
+
+    assert!(failing.wait().is_err());
+    assert_eq!(
+        linked.wait().unwrap_err().raw_os_error(),
+        Some(libc::ECANCELED)
+    );
+    for write in [a, b, c] {
+        assert_eq!(write.wait().unwrap(), 1);
+    }
+
+    // the writes through each fd were submitted
+    // together, in the order they were made, and
+    // the chain stayed in one piece, so only its
+    // own second half was canceled
+    let mut out = [0; 3];
+    reader.read_exact(&mut out).unwrap();
+    if writer.as_raw_fd() < other.as_raw_fd() {
+        assert_eq!(&out, b"bac");
+    } else {
+        assert_eq!(&out, b"acb");
+    }
+}
@@ -0,0 +1,1576 @@
+use std::{
+    fs::OpenOptions, os::unix::io::AsRawFd, path::PathBuf,
+};
+
+fn tmp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "rio_{}_{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn test_madvise() {
+    let path = tmp_path("madvise");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.set_len(4096 * 4).unwrap();
+
+    let len = 4096 * 4;
+
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    assert_ne!(addr, libc::MAP_FAILED);
+
+    let ring = rio::new().unwrap();
+
+    unsafe {
+        ring.madvise(
+            addr as *const u8,
+            len,
+            libc::MADV_WILLNEED,
+        )
+        .wait()
+        .unwrap();
+    }
+
+    unsafe {
+        libc::munmap(addr, len);
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_read_into_raw() {
+    let path = tmp_path("read_into_raw");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let data: Vec<u8> =
+        (0..4096 * 2).map(|i| (i % 251) as u8).collect();
+    ring.write_at(&file, &data, 0).wait().unwrap();
+
+    let len = 4096;
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(addr, libc::MAP_FAILED);
+
+    // fill the page with the file's second page
+    let read = unsafe {
+        ring.read_into_raw(&file, addr.cast(), len, 4096)
+    }
+    .wait()
+    .unwrap();
+    assert_eq!(read, len);
+
+    let page = unsafe {
+        std::slice::from_raw_parts(addr as *const u8, len)
+    };
+    assert_eq!(page, &data[4096..]);
+
+    unsafe {
+        libc::munmap(addr, len);
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_copy_progress() {
+    use std::sync::{Arc, Mutex};
+
+    let src_path = tmp_path("copy_progress_src");
+    let dst_path = tmp_path("copy_progress_dst");
+    let open = |path: &PathBuf| {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap()
+    };
+    let src = open(&src_path);
+    let dst = open(&dst_path);
+
+    let ring = rio::new().unwrap();
+
+    // several windows' worth of chunks, and a
+    // partial one at the end
+    let data: Vec<u8> = (0..64 * 1024 * 20 + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let len = data.len() as u64;
+    ring.write_at(&src, &data, 0).wait().unwrap();
+
+    let totals = Arc::new(Mutex::new(vec![]));
+    let progress_totals = totals.clone();
+    let copied = ring
+        .copy(
+            &src,
+            0,
+            &dst,
+            0,
+            len,
+            Some(Arc::new(move |total| {
+                progress_totals.lock().unwrap().push(total)
+            })),
+        )
+        .unwrap();
+    assert_eq!(copied, len);
+
+    let totals = totals.lock().unwrap().clone();
+    assert!(totals.len() >= 21, "{:?}", totals);
+    assert!(totals.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(*totals.last().unwrap(), len);
+
+    let buf = vec![0_u8; data.len()];
+    let read = ring.read_at(&dst, &buf, 0).wait().unwrap();
+    assert_eq!(read, data.len());
+    assert_eq!(buf, data);
+
+    // asking for more than there is copies what
+    // there is
+    let copied =
+        ring.copy(&src, 1000, &dst, 0, len, None).unwrap();
+    assert_eq!(copied, len - 1000);
+
+    std::fs::remove_file(src_path).unwrap();
+    std::fs::remove_file(dst_path).unwrap();
+}
+
+#[test]
+fn test_borrowed_fd() {
+    use std::os::unix::io::AsFd;
+
+    let path = tmp_path("borrowed_fd");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    let fd = file.as_fd();
+
+    let written =
+        ring.write_at(&fd, b"borrowed", 0).wait().unwrap();
+    assert_eq!(written, 8);
+
+    let buf = vec![0; 8];
+    let read = ring.read_at(&fd, &buf, 0).wait().unwrap();
+    assert_eq!(read, 8);
+    assert_eq!(buf, b"borrowed");
+
+    // the completions are done, so the owner can go
+    drop(file);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_cursor() {
+    let path = tmp_path("cursor");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let data: Vec<u8> = (0..96_u8).collect();
+    let mut writer = ring.cursor(&file);
+    for chunk in data.chunks(32) {
+        let written = writer.write(&chunk).wait().unwrap();
+        assert_eq!(written, 32);
+    }
+    assert_eq!(writer.position(), 96);
+
+    let mut reader = ring.cursor(&file);
+    let mut read = vec![];
+    for _ in 0..3 {
+        let buf = vec![0_u8; 32];
+        let len = reader.read(&buf).wait().unwrap();
+        read.extend_from_slice(&buf[..len]);
+    }
+
+    assert_eq!(reader.position(), 96);
+    assert_eq!(read, data);
+
+    // a completion that's never waited on doesn't
+    // move the offset, even though the write lands
+    drop(writer.write(&[7_u8; 4]));
+    assert_eq!(writer.position(), 96);
+    assert_eq!(file.metadata().unwrap().len(), 100);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_drop_error_handler() {
+    use std::sync::{Arc, Mutex};
+
+    let errors = Arc::new(Mutex::new(vec![]));
+    let errors2 = errors.clone();
+
+    let config = rio::Config {
+        drop_error_handler: Some(Arc::new(move |e| {
+            errors2.lock().unwrap().push(e.raw_os_error());
+        })),
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    let path = tmp_path("drop_error_handler");
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    // reading from a write-only file fails
+    let buf = vec![0_u8; 16];
+    drop(ring.read_at(&file, &buf, 0));
+
+    assert_eq!(
+        *errors.lock().unwrap(),
+        vec![Some(libc::EBADF)]
+    );
+
+    // errors that are waited on are not reported
+    assert!(ring.read_at(&file, &buf, 0).wait().is_err());
+    assert_eq!(errors.lock().unwrap().len(), 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_write_at_offset_overflow() {
+    let path = tmp_path("offset_overflow");
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    let buf = vec![0_u8; 64];
+
+    let err = ring
+        .write_at(&file, &buf, u64::MAX - 10)
+        .wait()
+        .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    // past the largest signed offset, but without
+    // wrapping around u64
+    let err = ring
+        .write_at(&file, &buf, i64::MAX as u64)
+        .wait()
+        .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    assert_eq!(file.metadata().unwrap().len(), 0);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_read_uninit() {
+    let path = tmp_path("read_uninit");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.write_at(&file, b"hello world", 0).wait().unwrap();
+
+    let mut buf = vec![std::mem::MaybeUninit::uninit(); 64];
+    let read = ring
+        .read_uninit(&file, &mut buf, 6)
+        .wait()
+        .unwrap();
+    assert_eq!(read, b"world");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_pinned_buffers() {
+    let path = tmp_path("pinned_buffers");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let out: std::pin::Pin<Box<[u8]>> =
+        Box::pin(*b"pinned buffer");
+    let written = ring.write_at(&file, &*out, 0).wait();
+    assert_eq!(written.unwrap(), out.len());
+
+    let mut back: std::pin::Pin<Box<[u8]>> =
+        vec![0_u8; out.len()].into_boxed_slice().into();
+    let slice: &mut [u8] = &mut back;
+    let read = ring.read_at(&file, &slice, 0).wait();
+    assert_eq!(read.unwrap(), out.len());
+    assert_eq!(back, out);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_file_from_cqe() {
+    use rio::FromCqe;
+    use std::os::unix::io::{IntoRawFd, OwnedFd};
+
+    let path = tmp_path("file_from_cqe");
+    std::fs::write(&path, b"hello").unwrap();
+
+    let fd =
+        std::fs::File::open(&path).unwrap().into_raw_fd();
+    let cqe = rio::io_uring_cqe {
+        user_data: 0,
+        res: fd,
+        flags: 0,
+    };
+
+    let file = std::fs::File::from_cqe(cqe);
+    assert_eq!(file.as_raw_fd(), fd);
+    assert_eq!(file.metadata().unwrap().len(), 5);
+
+    let owned = OwnedFd::from(file);
+    let fd = owned.into_raw_fd();
+    let owned = OwnedFd::from_cqe(rio::io_uring_cqe {
+        user_data: 0,
+        res: fd,
+        flags: 0,
+    });
+    drop(owned);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "single_thread")]
+#[test]
+fn test_single_thread() {
+    let path = tmp_path("single_thread");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let reapers = std::fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter(|task| {
+            let comm =
+                task.as_ref().unwrap().path().join("comm");
+            std::fs::read_to_string(comm)
+                .map_or(false, |name| {
+                    name.trim() == "rio-reaper"
+                })
+        })
+        .count();
+    assert_eq!(reapers, 0);
+
+    ring.write_at(&file, b"hello", 0).wait().unwrap();
+
+    let buf = &mut [0_u8; 5];
+    let read = ring.read_at(&file, buf, 0).wait().unwrap();
+    assert_eq!(read, 5);
+    assert_eq!(buf, b"hello");
+
+    // polling reaps for itself, waiting a bit
+    // for completions rather than spinning
+    struct Noop;
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {}
+    }
+    let waker = std::sync::Arc::new(Noop).into();
+    let mut cx = std::task::Context::from_waker(&waker);
+
+    let (timeout, _token) =
+        ring.timeout(std::time::Duration::from_millis(50));
+    let mut timeout = Box::pin(timeout);
+    let mut polls = 0;
+    while std::future::Future::poll(
+        timeout.as_mut(),
+        &mut cx,
+    )
+    .is_pending()
+    {
+        polls += 1;
+    }
+    assert!(polls < 100, "polled {} times", polls);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_at_exact_or_eof() {
+    let path = tmp_path("read_at_exact_or_eof");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.write_at(&file, b"hello", 0).wait().unwrap();
+
+    let buf: &mut [u8] = &mut [0; 64];
+    let read = ring
+        .read_at_exact_or_eof(&file, &buf, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, Some(5));
+
+    let past_end = ring
+        .read_at_exact_or_eof(&file, &buf, 5)
+        .wait()
+        .unwrap();
+    assert_eq!(past_end, None);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_at_prefix() {
+    let path = tmp_path("read_at_prefix");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.write_at(&file, b"hello", 0).wait().unwrap();
+
+    let buf = &mut [7; 16];
+    let mut read =
+        ring.read_at_prefix(&file, buf, 0).wait().unwrap();
+    assert_eq!(read.len(), 5);
+    assert_eq!(read.filled(), b"hello");
+    assert_eq!(read.unfilled(), &[7; 11]);
+
+    let past_end =
+        ring.read_at_prefix(&file, buf, 5).wait().unwrap();
+    assert!(past_end.is_empty());
+    assert_eq!(past_end.filled(), b"");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_at_verified() {
+    let path = tmp_path("read_at_verified");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    // a block whose last byte is a checksum of the
+    // rest, followed by one whose checksum is off
+    fn checksum(data: &[u8]) -> u8 {
+        data.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+    }
+    fn verify(block: &[u8]) -> bool {
+        let (data, sum) = block.split_at(block.len() - 1);
+        checksum(data) == sum[0]
+    }
+    let mut good: Vec<u8> = (0..63).collect();
+    good.push(checksum(&good));
+    let mut bad = good.clone();
+    bad[0] ^= 1;
+    std::io::Write::write_all(&mut &file, &good).unwrap();
+    std::io::Write::write_all(&mut &file, &bad).unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let buf = &mut [0; 64];
+    let read = ring
+        .read_at_verified(&file, buf, 0, verify)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 64);
+    assert_eq!(&buf[..], &good[..]);
+
+    let err = ring
+        .read_at_verified(&file, buf, 64, verify)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // a panic in `verify` fails the read, rather
+    // than the reaper, so the ring keeps working
+    let err = ring
+        .read_at_verified(&file, buf, 0, |_| panic!())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    ring.nop().wait().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_at_decoded() {
+    let path = tmp_path("read_at_decoded");
+    std::fs::write(&path, b"olleh!").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+
+    // "decoding" reverses the bytes, and fails on
+    // anything with a '!' in it
+    fn decode(data: &[u8]) -> std::io::Result<Vec<u8>> {
+        if data.contains(&b'!') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "bad block",
+            ));
+        }
+        Ok(data.iter().rev().copied().collect())
+    }
+
+    let ring = rio::new().unwrap();
+
+    let buf = &mut [0; 5];
+    let decoded = ring
+        .read_at_decoded(&file, buf, 0, decode)
+        .wait()
+        .unwrap();
+    assert_eq!(decoded, b"hello");
+
+    // a short read only decodes what was read
+    let decoded = ring
+        .read_at_decoded(&file, buf, 2, |data| {
+            Ok(data.to_vec())
+        })
+        .wait()
+        .unwrap();
+    assert_eq!(decoded, b"leh!");
+
+    let err = ring
+        .read_at_decoded(&file, buf, 1, decode)
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // a decoder that panics fails the read, rather
+    // than the reaper
+    let err = ring
+        .read_at_decoded(
+            &file,
+            buf,
+            0,
+            |_| -> std::io::Result<Vec<u8>> { panic!() },
+        )
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    ring.nop().wait().unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_io_slices_at() {
+    use std::io::{IoSlice, IoSliceMut};
+
+    let path = tmp_path("io_slices_at");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let header = b"header:";
+    let payload = b"the payload";
+    let bufs =
+        [IoSlice::new(header), IoSlice::new(payload)];
+    let wrote = ring
+        .write_io_slices_at(&file, &bufs, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(wrote, header.len() + payload.len());
+
+    let mut header_buf = [0; 7];
+    let mut payload_buf = [0; 11];
+    let mut bufs = [
+        IoSliceMut::new(&mut header_buf),
+        IoSliceMut::new(&mut payload_buf),
+    ];
+    let read = ring
+        .read_io_slices_at(&file, &mut bufs, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, wrote);
+    assert_eq!(&header_buf, header);
+    assert_eq!(&payload_buf, payload);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_into_ring() {
+    let path = tmp_path("read_into_ring");
+    std::fs::write(&path, b"abcdefghijklmnopqrst").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+
+    let ring = rio::new().unwrap();
+    let mut buf = rio::RingBuf::with_capacity(8);
+
+    let read = ring
+        .read_into_ring(&file, &mut buf, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 8);
+    assert_eq!(
+        buf.as_slices(),
+        (&b"abcdefgh"[..], &b""[..])
+    );
+
+    // the free space is at the front of the buffer
+    buf.consume(3);
+    let read = ring
+        .read_into_ring(&file, &mut buf, 8)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 3);
+    assert_eq!(
+        buf.as_slices(),
+        (&b"defgh"[..], &b"ijk"[..])
+    );
+
+    // and now it wraps around the end, so the read
+    // is split between the tail and the head
+    buf.consume(6);
+    assert_eq!(buf.free(), 6);
+    let read = ring
+        .read_into_ring(&file, &mut buf, 11)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 6);
+    assert_eq!(
+        buf.as_slices(),
+        (&b"jklmnop"[..], &b"q"[..])
+    );
+
+    // a full buffer has nowhere to read into
+    let read = ring
+        .read_into_ring(&file, &mut buf, 17)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 0);
+    assert_eq!(buf.len(), 8);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_rw_flags() {
+    use rio::RwFlags;
+
+    let path = tmp_path("rw_flags");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    ring.write_at(&file, b"abc", 0).wait().unwrap();
+
+    // the offset is ignored when appending, so
+    // this doesn't overwrite what is there
+    let wrote = ring
+        .write_at_with_flags(
+            &file,
+            b"def",
+            0,
+            RwFlags::APPEND | RwFlags::DSYNC,
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(wrote, 3);
+    assert_eq!(file.metadata().unwrap().len(), 6);
+
+    let buf = vec![0; 6];
+    let read = ring
+        .read_at_with_flags(
+            &file,
+            &buf,
+            0,
+            RwFlags::empty(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(read, 6);
+    assert_eq!(&buf, b"abcdef");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_records() {
+    let path = tmp_path("read_records");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    // 8 records of 16 bytes, each filled with its
+    // own index, then half of a 9th
+    let mut contents: Vec<u8> =
+        (0..8_u8).flat_map(|i| vec![i; 16]).collect();
+    contents.extend_from_slice(&[8; 8]);
+    std::io::Write::write_all(&mut &file, &contents)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let buf = &mut [0xFF; 64];
+    let read = ring
+        .read_records(&file, 16, 2, 3, buf)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 3);
+    for (record, i) in buf.chunks(16).zip(2..5_u8) {
+        assert_eq!(record, &[i; 16]);
+    }
+    // nothing past the 3 records is touched
+    assert_eq!(&buf[48..], &[0xFF; 16]);
+
+    // the trailing partial record isn't counted
+    let read = ring
+        .read_records(&file, 16, 6, 4, buf)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 2);
+
+    let err = ring
+        .read_records(&file, 16, 0, 5, buf)
+        .wait()
+        .unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_vectored_fill_at() {
+    let path = tmp_path("read_vectored_fill_at");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.write_at(&file, b"0123456789", 0).wait().unwrap();
+
+    // 12 bytes of room, but only 10 to read, so
+    // it stops 2 bytes into the last buffer
+    let bufs = [[b'x'; 4], [b'x'; 4], [b'x'; 4]];
+    let stop = ring
+        .read_vectored_fill_at(&file, &bufs, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(
+        stop,
+        rio::VectoredStop {
+            read: 10,
+            index: 2,
+            offset: 2,
+        }
+    );
+    assert_eq!(&bufs[0], b"0123");
+    assert_eq!(&bufs[1], b"4567");
+    assert_eq!(&bufs[2], b"89xx");
+
+    // filling every buffer stops past the last
+    let bufs = [[0_u8; 5], [0; 5]];
+    let stop = ring
+        .read_vectored_fill_at(&file, &bufs, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(
+        (stop.read, stop.index, stop.offset),
+        (10, 2, 0)
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_at_cache_hint() {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    #[repr(align(4096))]
+    struct Aligned([u8; 4096]);
+
+    let ring = rio::new().unwrap();
+
+    let cached_path = tmp_path("cache_hint_cached");
+    let cached_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&cached_path)
+        .unwrap();
+    ring.write_at(&cached_file, &[1; 4096], 0)
+        .wait()
+        .unwrap();
+
+    let buf: &mut [u8] = &mut [0; 4096];
+    let (read, cached) = ring
+        .read_at_cache_hint(&cached_file, &buf, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 4096);
+    assert_eq!(buf, &[1; 4096][..]);
+
+    // O_DIRECT bypasses the page cache for both the
+    // write and the read, so the read usually waits
+    // on the device.
+    let direct_path = tmp_path("cache_hint_direct");
+    let direct_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(&direct_path)
+        .unwrap();
+    let aligned = &mut Aligned([2; 4096]);
+    ring.write_at(&direct_file, &&mut aligned.0[..], 0)
+        .wait()
+        .unwrap();
+
+    aligned.0 = [0; 4096];
+    let aligned_buf = &mut aligned.0[..];
+    let (read, direct) = ring
+        .read_at_cache_hint(&direct_file, &aligned_buf, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 4096);
+    assert_eq!(aligned_buf, &[2; 4096][..]);
+
+    // which hint each read gets depends on timing,
+    // like an O_DIRECT read that finishes before
+    // `enter` returns, but without SQPOLL there is
+    // always one
+    for hint in [cached, direct] {
+        assert!(
+            matches!(
+                hint,
+                rio::CacheHint::Cached
+                    | rio::CacheHint::Uncached
+            ),
+            "{:?}",
+            hint
+        );
+    }
+
+    std::fs::remove_file(&cached_path).unwrap();
+    std::fs::remove_file(&direct_path).unwrap();
+}
+
+#[test]
+fn test_write_at_nowait_falls_back() {
+    use std::{
+        fs::File, io::Read, os::unix::io::FromRawFd,
+    };
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let mut rx = unsafe { File::from_raw_fd(fds[0]) };
+    let tx = unsafe { File::from_raw_fd(fds[1]) };
+
+    // fill the pipe, so that a write that may not
+    // block fails with EAGAIN
+    let capacity = unsafe {
+        libc::fcntl(
+            tx.as_raw_fd(),
+            libc::F_SETPIPE_SZ,
+            4096,
+        )
+    };
+    assert!(capacity >= 4096);
+    let filler = vec![0_u8; capacity as usize];
+    std::io::Write::write_all(&mut &tx, &filler).unwrap();
+
+    let ring = rio::new().unwrap();
+    let completion =
+        ring.write_at_nowait(&tx, vec![7; 100], 0);
+    // make sure it has run into the full pipe
+    // before we start draining it
+    ring.submit_all();
+    std::thread::sleep(std::time::Duration::from_millis(
+        10,
+    ));
+
+    let mut drained = filler;
+    rx.read_exact(&mut drained).unwrap();
+
+    let (written, buf) = completion.wait().unwrap();
+    assert_eq!(written, 100);
+    assert_eq!(buf, vec![7; 100]);
+
+    let mut landed = vec![0; 100];
+    rx.read_exact(&mut landed).unwrap();
+    assert_eq!(landed, buf);
+}
+
+#[test]
+fn test_write_at_with_nowait_flag_fails() {
+    use std::{fs::File, os::unix::io::FromRawFd};
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let _rx = unsafe { File::from_raw_fd(fds[0]) };
+    let tx = unsafe { File::from_raw_fd(fds[1]) };
+
+    let capacity = unsafe {
+        libc::fcntl(
+            tx.as_raw_fd(),
+            libc::F_SETPIPE_SZ,
+            4096,
+        )
+    };
+    assert!(capacity >= 4096);
+    let filler = vec![0_u8; capacity as usize];
+    std::io::Write::write_all(&mut &tx, &filler).unwrap();
+
+    // unlike `write_at_nowait`, this hands the
+    // EAGAIN back instead of waiting for room
+    let ring = rio::new().unwrap();
+    let err = ring
+        .write_at_with_flags(
+            &tx,
+            &[7; 100],
+            0,
+            rio::RwFlags::NOWAIT,
+        )
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EAGAIN));
+}
+
+#[test]
+fn test_transient_retry() {
+    use std::{
+        os::unix::io::RawFd,
+        time::{Duration, Instant},
+    };
+
+    struct Fd(RawFd);
+
+    impl AsRawFd for Fd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0
+        }
+    }
+
+    let config = rio::Config {
+        transient_retry: Some(rio::TransientRetry {
+            errnos: vec![libc::ENOSPC, libc::EBADF],
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(30),
+        }),
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    // writes to /dev/full always fail with ENOSPC,
+    // so this is retried until it runs out of
+    // attempts, after waiting 20ms, then 30ms, and
+    // then 30ms again
+    let full = OpenOptions::new()
+        .write(true)
+        .open("/dev/full")
+        .unwrap();
+    let before = Instant::now();
+    let err =
+        ring.write_at(&full, b"hi", 0).wait().unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+    assert!(before.elapsed() >= Duration::from_millis(80));
+
+    // a read from an fd that isn't open yet fails
+    // with EBADF, but succeeds when it's retried
+    // after the fd has been opened
+    let path = tmp_path("transient_retry");
+    std::fs::write(&path, b"hello").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let fd = Fd(900);
+    assert_eq!(
+        unsafe { libc::fcntl(fd.0, libc::F_GETFD) },
+        -1
+    );
+
+    let buf: &mut [u8] = &mut [0; 5];
+    let completion = ring.read_at(&fd, &buf, 0);
+    ring.submit_all();
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(
+        unsafe { libc::dup2(file.as_raw_fd(), fd.0) },
+        fd.0
+    );
+    assert_eq!(completion.wait().unwrap(), 5);
+    assert_eq!(buf, b"hello");
+
+    unsafe { libc::close(fd.0) };
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sync_file_range_with_flags() {
+    let path = tmp_path("sync_file_range_with_flags");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.write_at(&file, &[1; 4096], 0).wait().unwrap();
+
+    // WAIT_BEFORE used to make this fail with EBADF
+    let full_sync = rio::SyncFileRangeFlags::new()
+        .wait_before()
+        .write()
+        .wait_after();
+    ring.sync_file_range_with_flags(
+        &file,
+        0,
+        4096,
+        full_sync,
+        rio::Ordering::None,
+    )
+    .wait()
+    .unwrap();
+
+    assert_eq!(
+        rio::SyncFileRangeFlags::pessimistic(),
+        rio::SyncFileRangeFlags::new().write().wait_after()
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_registered_append() {
+    let path = tmp_path("registered_append");
+    let files = [OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap()];
+
+    // appends go after whatever is already there,
+    // whatever offset the writes would have had.
+    std::io::Write::write_all(&mut &files[0], b"log\n")
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    let registered = ring.register_files(&files).unwrap();
+
+    let log = registered.fd(0);
+    let records: [&[u8]; 3] =
+        [b"first\n", b"second\n", b"third\n"];
+    for record in &records {
+        let written =
+            registered.append(&log, record).wait().unwrap();
+        assert_eq!(written, record.len());
+    }
+    drop(registered);
+
+    let contents = std::fs::read(&path).unwrap();
+    assert_eq!(contents, b"log\nfirst\nsecond\nthird\n");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_poll_add() {
+    use rio::PollFlags;
+    use std::{fs::File, os::unix::io::FromRawFd};
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let rx = unsafe { File::from_raw_fd(fds[0]) };
+    let tx = unsafe { File::from_raw_fd(fds[1]) };
+
+    let ring = rio::new().unwrap();
+
+    // an empty pipe isn't readable until it's written to
+    let readable = ring.poll_add(&rx, PollFlags::IN);
+    std::io::Write::write_all(&mut &tx, b"x").unwrap();
+    let ready = readable.wait().unwrap();
+    assert_eq!(ready, PollFlags::IN);
+
+    let ready = ring
+        .poll_add(&tx, PollFlags::IN | PollFlags::OUT)
+        .wait()
+        .unwrap();
+    assert!(ready.contains(PollFlags::OUT));
+    assert!(!ready.contains(PollFlags::IN));
+}
+
+#[test]
+fn test_poll_add_multishot() {
+    use rio::PollFlags;
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        os::unix::io::FromRawFd,
+    };
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let rx = unsafe { File::from_raw_fd(fds[0]) };
+    let tx = unsafe { File::from_raw_fd(fds[1]) };
+
+    let ring = rio::new().unwrap();
+
+    // a single poll keeps firing for every write
+    let mut events =
+        ring.poll_add_multishot(&rx, PollFlags::IN);
+    for i in 0..5_u8 {
+        (&tx).write_all(&[i]).unwrap();
+        let ready = events.next().unwrap().unwrap();
+        assert!(ready.contains(PollFlags::IN));
+
+        let mut buf = [0];
+        (&rx).read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [i]);
+    }
+    assert_eq!(ring.metrics_snapshot().ops, 1);
+
+    // dropping it removes the poll, so that the
+    // pipe can be polled again from scratch
+    drop(events);
+    let before = ring.metrics_snapshot().ops;
+    (&tx).write_all(b"x").unwrap();
+    let ready =
+        ring.poll_add(&rx, PollFlags::IN).wait().unwrap();
+    assert_eq!(ready, PollFlags::IN);
+    assert_eq!(ring.metrics_snapshot().ops, before + 1);
+}
+
+#[test]
+fn test_datasync_range() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let path = tmp_path("datasync_range");
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"header\n").unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.fdatasync(&file).wait().unwrap();
+
+    let end = file.seek(SeekFrom::End(0)).unwrap();
+    let record = b"appended record\n";
+    ring.write_at(&file, record, end).wait().unwrap();
+    ring.datasync_range(&file, end, record.len())
+        .wait()
+        .unwrap();
+
+    // a fresh handle sees the new size and data
+    let contents = std::fs::read(&path).unwrap();
+    assert_eq!(contents, b"header\nappended record\n");
+
+    // on a small ring, it reuses the SQE slots of
+    // the writes before it, which mustn't leave
+    // anything behind that the kernel rejects
+    let small = rio::Config {
+        depth: 2,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+    for _ in 0..4 {
+        small.write_at(&file, record, end).wait().unwrap();
+        small
+            .datasync_range(&file, end, record.len())
+            .wait()
+            .unwrap();
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_write_at_preallocated() {
+    let path = tmp_path("write_at_preallocated");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let data: Vec<u8> =
+        (0..1 << 20).map(|i| (i % 251) as u8).collect();
+    let wrote = ring
+        .write_at_preallocated(&file, &data, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(wrote, data.len());
+
+    let metadata = file.metadata().unwrap();
+    assert_eq!(metadata.len(), 1 << 20);
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+
+    // nothing to reserve for an empty write
+    let empty: &[u8] = &[];
+    let wrote = ring
+        .write_at_preallocated(&file, &empty, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(wrote, 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_linked_pair() {
+    let path = tmp_path("linked_pair");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    let out: &[u8] = b"linked write";
+    let buf: &mut [u8] = &mut [0; 12];
+
+    let (write, read) = ring.linked_pair(
+        rio::PreppedOp::write_at(&file, &out, 4096),
+        rio::PreppedOp::read_at(&file, &buf, 4096),
+    );
+    assert_eq!(write.wait().unwrap(), out.len());
+    assert_eq!(read.wait().unwrap(), out.len());
+    assert_eq!(buf, out);
+
+    // the read is canceled when the write fails
+    let (write, read) = ring.linked_pair(
+        rio::PreppedOp::write_at(&file, &out, u64::MAX),
+        rio::PreppedOp::read_at(&file, &buf, 0),
+    );
+    assert_eq!(
+        write.wait().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    assert_eq!(
+        read.wait().unwrap_err().raw_os_error(),
+        Some(libc::ECANCELED)
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_registration_update() {
+    let a_path = tmp_path("registration_update_a");
+    let b_path = tmp_path("registration_update_b");
+    std::fs::write(&a_path, b"aaaaa").unwrap();
+    std::fs::write(&b_path, b"bbbbb").unwrap();
+    let files = [std::fs::File::open(&a_path).unwrap()];
+    let other = std::fs::File::open(&b_path).unwrap();
+
+    let ring = rio::new().unwrap();
+    let registered = ring.register_files(&files).unwrap();
+
+    let fd = registered.fd(0);
+    let buf = vec![0; 5];
+    registered.read_at(&fd, &buf, 0).wait().unwrap();
+    assert_eq!(buf, b"aaaaa");
+
+    let other_fd = registered.update(0, &other).unwrap();
+
+    // the slot now has another file in it, which
+    // the RegisteredFd from before doesn't expect
+    #[cfg(debug_assertions)]
+    {
+        let err = registered
+            .read_at(&fd, &buf, 0)
+            .wait()
+            .unwrap_err();
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+        assert!(
+            err.to_string().contains("another file"),
+            "{}",
+            err
+        );
+    }
+
+    // but the one that update returned does, as
+    // does a fresh one
+    registered.read_at(&other_fd, &buf, 0).wait().unwrap();
+    assert_eq!(buf, b"bbbbb");
+    assert_eq!(registered.fd(0), other_fd);
+    assert_eq!(registered.fds(), vec![other_fd]);
+
+    registered.remove(0).unwrap();
+    #[cfg(debug_assertions)]
+    assert!(registered
+        .read_at(&other_fd, &buf, 0)
+        .wait()
+        .is_err());
+    assert_eq!(
+        registered
+            .read_at(&registered.fd(0), &buf, 0)
+            .wait()
+            .unwrap_err()
+            .raw_os_error(),
+        Some(libc::EBADF)
+    );
+
+    drop(registered);
+    std::fs::remove_file(&a_path).unwrap();
+    std::fs::remove_file(&b_path).unwrap();
+}
+
+#[test]
+fn test_registered_fd_routing() {
+    use std::convert::TryFrom;
+
+    let a_path = tmp_path("registered_fd_routing_a");
+    let b_path = tmp_path("registered_fd_routing_b");
+    std::fs::write(&a_path, b"aaaaa").unwrap();
+    std::fs::write(&b_path, b"bbbbb").unwrap();
+    let a = std::fs::File::open(&a_path).unwrap();
+
+    // fill the slot whose index is a's descriptor
+    // with b, so that the same number reads a
+    // different file on each path
+    let a_fd = usize::try_from(a.as_raw_fd()).unwrap();
+    let table: Vec<std::fs::File> = (0..=a_fd)
+        .map(|_| std::fs::File::open(&b_path).unwrap())
+        .collect();
+
+    let ring = rio::new().unwrap();
+    let registered = ring.register_files(&table).unwrap();
+    let fd = registered.fd(a_fd);
+
+    let buf = vec![0; 5];
+    ring.read_at(&a, &buf, 0).wait().unwrap();
+    assert_eq!(buf, b"aaaaa");
+    registered.read_at(&fd, &buf, 0).wait().unwrap();
+    assert_eq!(buf, b"bbbbb");
+
+    drop(registered);
+    std::fs::remove_file(&a_path).unwrap();
+    std::fs::remove_file(&b_path).unwrap();
+}
+
+#[test]
+fn test_registration_unsupported() {
+    let ring = rio::new().unwrap();
+    let files: [std::fs::File; 0] = [];
+
+    let err = ring.register_files(&files).unwrap_err();
+    let info = rio::kernel_info().unwrap();
+    if info.at_least(5, 1) {
+        // registering nothing is a bad argument, not a
+        // sign that registration isn't supported
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    } else {
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::Unsupported
+        );
+        assert!(err.to_string().contains(
+            "IORING_REGISTER_FILES needs linux 5.1"
+        ));
+    }
+}
+
+#[test]
+fn test_registration_guards() {
+    let path = tmp_path("registration_guards");
+    let files = [OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap()];
+
+    let ring = rio::new().unwrap();
+
+    let registered_files =
+        ring.register_files(&files).unwrap();
+    registered_files
+        .write_at(&registered_files.fd(0), b"hello", 0)
+        .wait()
+        .unwrap();
+
+    // a RegisteredFd from a bigger registration is
+    // out of bounds in this one
+    let other_ring = rio::new().unwrap();
+    let other_files = [
+        files[0].try_clone().unwrap(),
+        files[0].try_clone().unwrap(),
+    ];
+    let other_registered =
+        other_ring.register_files(&other_files).unwrap();
+    assert!(registered_files
+        .write_at(&other_registered.fd(1), b"", 0)
+        .wait()
+        .is_err());
+
+    // files are already registered, so the kernel
+    // refuses to register any more.
+    let busy = ring.register_files(&files).unwrap_err();
+    assert_eq!(busy.raw_os_error(), Some(libc::EBUSY));
+
+    drop(registered_files);
+    drop(ring.register_files(&files).unwrap());
+
+    let bufs = [vec![0_u8; 5]];
+    let registered_bufs =
+        ring.register_buffers(&bufs).unwrap();
+    let read = registered_bufs
+        .read_at(&files[0], 0, 0)
+        .wait()
+        .unwrap();
+    assert_eq!(read, 5);
+
+    let busy = ring.register_buffers(&bufs).unwrap_err();
+    assert_eq!(busy.raw_os_error(), Some(libc::EBUSY));
+
+    drop(registered_bufs);
+    assert_eq!(bufs[0], b"hello");
+    drop(ring.register_buffers(&bufs).unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_read_at_bytes() {
+    let path = tmp_path("read_at_bytes");
+    std::fs::write(&path, b"hello, pooled world").unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+
+    let ring = rio::new().unwrap();
+
+    let bytes =
+        ring.read_at_bytes(&file, 6, 7).wait().unwrap();
+    assert_eq!(&bytes[..], b"pooled");
+    let prefix = bytes.slice(..3);
+    drop(bytes);
+    assert_eq!(&prefix[..], b"poo");
+    drop(prefix);
+
+    // the buffer is reused now that it's back, and
+    // only what this read filled in is exposed
+    let bytes =
+        ring.read_at_bytes(&file, 64, 14).wait().unwrap();
+    assert_eq!(&bytes[..], b"world");
+
+    std::fs::remove_file(&path).unwrap();
+}
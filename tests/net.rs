@@ -0,0 +1,1059 @@
+use std::{
+    future::Future,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream, UdpSocket},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+};
+
+use futures_core::Stream;
+
+fn tcp_pair() -> (TcpStream, TcpStream) {
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+    let a =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let (b, _) = listener.accept().unwrap();
+    (a, b)
+}
+
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on_next<S: Stream + Unpin>(
+    stream: &mut S,
+) -> Option<S::Item> {
+    let waker =
+        Arc::new(Unparker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match Pin::new(&mut *stream).poll_next(&mut cx) {
+            Poll::Ready(item) => return item,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn poll_once<F: Future + Unpin>(
+    future: &mut F,
+) -> Poll<F::Output> {
+    let waker =
+        Arc::new(Unparker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    Pin::new(future).poll(&mut cx)
+}
+
+#[test]
+fn test_recv_into_vec() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    a.write_all(b"hello").unwrap();
+
+    let buf = Vec::with_capacity(4096);
+    let buf = ring.recv_into_vec(&b, buf).wait().unwrap();
+
+    assert_eq!(buf.len(), 5);
+    assert_eq!(buf, b"hello".to_vec());
+    assert!(buf.capacity() >= 4096);
+}
+
+#[test]
+fn test_recv_framed() {
+    let config = rio::Config {
+        max_frame_len: 1024,
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    let body: Vec<u8> =
+        (0..=255).cycle().take(1000).collect();
+    let mut frame = 1000_u32.to_be_bytes().to_vec();
+    frame.extend_from_slice(&body);
+    // a second frame right behind it, which must
+    // be left for the next recv_framed
+    frame.extend_from_slice(&[0, 0, 0, 2, 7, 8]);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            // dribble it out, so the prefix and body
+            // each take more than one recv
+            for chunk in frame.chunks(3) {
+                a.write_all(chunk).unwrap();
+                a.flush().unwrap();
+                std::thread::sleep(
+                    std::time::Duration::from_micros(200),
+                );
+            }
+        });
+
+        let received =
+            ring.recv_framed(&b, 4).wait().unwrap();
+        assert_eq!(received, body);
+    });
+    assert_eq!(
+        ring.recv_framed(&b, 4).wait().unwrap(),
+        [7, 8]
+    );
+
+    // a length over max_frame_len is rejected
+    a.write_all(&[0, 0, 4, 1]).unwrap();
+    let err = ring.recv_framed(&b, 4).wait().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let err = ring.recv_framed(&b, 9).wait().unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    // hanging up partway through a frame
+    let (mut a, b) = tcp_pair();
+    a.write_all(&[0, 5, 1, 2]).unwrap();
+    drop(a);
+    let err = ring.recv_framed(&b, 2).wait().unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+}
+
+#[test]
+fn test_detach_owned_on_drop() {
+    use std::{
+        os::unix::net::UnixStream,
+        time::{Duration, Instant},
+    };
+
+    let config = rio::Config {
+        detach_owned_on_drop: true,
+        ..rio::Config::default()
+    };
+    let ring = config.clone().start().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    // nothing has been sent, so this would block
+    // forever if it waited for the recv
+    let before = Instant::now();
+    drop(ring.recv_into_vec(&b, Vec::with_capacity(16)));
+    assert!(before.elapsed() < Duration::from_secs(1));
+
+    // the recv is still in flight, and dropping the
+    // ring waits for it, so it has to complete
+    a.write_all(b"hello").unwrap();
+    drop(ring);
+
+    // unless it never would, in which case
+    // dropping the ring cancels it
+    let ring = config.start().unwrap();
+    let (_c, d) = UnixStream::pair().unwrap();
+    drop(ring.recv_into_vec(&d, Vec::with_capacity(16)));
+    drop(ring);
+
+    // with cancel_on_drop, the recv is canceled
+    // rather than left to complete on its own
+    let config = rio::Config {
+        detach_owned_on_drop: true,
+        cancel_on_drop: true,
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+    drop(ring.recv_into_vec(&b, Vec::with_capacity(16)));
+    drop(ring);
+}
+
+#[test]
+fn test_send_all() {
+    let ring = rio::new().unwrap();
+    let (a, mut b) = tcp_pair();
+
+    // much bigger than the default socket buffers,
+    // so the first send can only send part of it.
+    let len = 16 * 1024 * 1024;
+    let buf: Vec<u8> =
+        (0..len).map(|i| (i % 251) as u8).collect();
+
+    let reader = thread::spawn(move || {
+        thread::sleep(std::time::Duration::from_millis(50));
+        let mut received = vec![0_u8; len];
+        b.read_exact(&mut received).unwrap();
+        received
+    });
+
+    ring.send_all(&a, &buf).wait().unwrap();
+
+    assert_eq!(reader.join().unwrap(), buf);
+
+    // sending nothing is done without the kernel
+    let before = ring.dump_ring_state().sq_tail;
+    ring.send_all(&a, &buf[..0]).wait().unwrap();
+    assert_eq!(ring.dump_ring_state().sq_tail, before);
+}
+
+#[test]
+fn test_recv_uninit() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    a.write_all(b"hello").unwrap();
+
+    let mut buf =
+        vec![std::mem::MaybeUninit::uninit(); 4096];
+    let received =
+        ring.recv_uninit(&b, &mut buf).wait().unwrap();
+
+    assert_eq!(received, b"hello");
+}
+
+#[test]
+fn test_recv() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    a.write_all(b"hello").unwrap();
+
+    let buf = vec![0_u8; 64];
+    let n = ring.recv(&b, &buf).wait().unwrap();
+
+    assert_eq!(&buf[..n], b"hello");
+}
+
+#[test]
+fn test_recv_hinted() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    a.write_all(b"hello").unwrap();
+    a.write_all(b"world").unwrap();
+
+    // give the second segment a moment to arrive
+    std::thread::sleep(std::time::Duration::from_millis(
+        10,
+    ));
+
+    let buf = vec![0_u8; 5];
+
+    // this flag is only reported by linux 5.19 and up
+    let hinted =
+        rio::kernel_info().unwrap().at_least(5, 19);
+
+    let first = ring.recv_hinted(&b, &buf).wait().unwrap();
+    assert_eq!(first.bytes, 5);
+    assert_eq!(first.more_available, hinted);
+    assert_eq!(&buf, b"hello");
+
+    let second = ring.recv_hinted(&b, &buf).wait().unwrap();
+    assert_eq!(second.bytes, 5);
+    assert!(!second.more_available);
+    assert_eq!(&buf, b"world");
+
+    // a plain recv reports the same hint in its flags
+    a.write_all(b"again").unwrap();
+    a.write_all(b"done!").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(
+        10,
+    ));
+
+    let (n, flags) =
+        ring.recv(&b, &buf).wait_with_flags().unwrap();
+    assert_eq!(n, 5);
+    assert_eq!(
+        flags & rio::consts::IORING_CQE_F_SOCK_NONEMPTY
+            != 0,
+        hinted
+    );
+    let (n, flags) =
+        ring.recv(&b, &buf).wait_with_flags().unwrap();
+    assert_eq!(n, 5);
+    assert_eq!(
+        flags & rio::consts::IORING_CQE_F_SOCK_NONEMPTY,
+        0
+    );
+    assert_eq!(&buf, b"done!");
+}
+
+#[test]
+fn test_recv_timeout() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+    let timeout = std::time::Duration::from_millis(20);
+
+    let buf = vec![0_u8; 64];
+
+    let idle = ring.recv_timeout(&b, &buf, timeout).wait();
+    assert_eq!(idle.unwrap(), None);
+
+    a.write_all(b"hello").unwrap();
+
+    let n = ring.recv_timeout(&b, &buf, timeout).wait();
+    assert_eq!(n.unwrap(), Some(5));
+    assert_eq!(&buf[..5], b"hello");
+}
+
+#[test]
+fn test_recv_from_v6() {
+    let ring = rio::new().unwrap();
+
+    let socket = UdpSocket::bind("[::1]:0").unwrap();
+    let peer = UdpSocket::bind("[::1]:0").unwrap();
+
+    peer.send_to(b"hello", socket.local_addr().unwrap())
+        .unwrap();
+
+    let buf = vec![0_u8; 64];
+    let (n, from) =
+        ring.recv_from(&socket, &buf).wait().unwrap();
+
+    assert_eq!(&buf[..n], b"hello");
+    assert!(from.is_ipv6());
+    assert_eq!(from, peer.local_addr().unwrap());
+}
+
+#[test]
+fn test_send_to() {
+    let ring = rio::new().unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let n = ring
+        .send_to(
+            &socket,
+            b"hello",
+            &peer.local_addr().unwrap(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(n, 5);
+
+    let mut buf = [0_u8; 64];
+    let (n, from) = peer.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"hello");
+    assert_eq!(from, socket.local_addr().unwrap());
+}
+
+#[test]
+fn test_send_to_resolved() {
+    let ring = rio::new().unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let addr =
+        rio::ResolvedAddr::from(peer.local_addr().unwrap());
+    assert_eq!(addr.addr(), peer.local_addr().unwrap());
+
+    for i in 0..5_u8 {
+        let n = ring
+            .send_to_resolved(&socket, &[i; 3], &addr)
+            .wait()
+            .unwrap();
+        assert_eq!(n, 3);
+    }
+
+    let mut buf = [0_u8; 64];
+    for i in 0..5_u8 {
+        let (n, from) = peer.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], &[i; 3]);
+        assert_eq!(from, socket.local_addr().unwrap());
+    }
+}
+
+#[test]
+fn test_recv_multishot() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    // fewer buffers than messages, so this only
+    // works if dropped buffers are recycled.
+    ring.provide_buffers(7, 2, 64).unwrap();
+
+    let mut bufs = ring.recv_multishot(&b, 7);
+
+    for i in 0..5_u8 {
+        let msg = [i; 10];
+        a.write_all(&msg).unwrap();
+
+        let buf = bufs.next().unwrap().unwrap();
+        assert_eq!(&*buf, &msg[..]);
+
+        // handing it back is only queued, and goes
+        // out along with the next submission
+        let before = ring.dump_ring_state();
+        drop(buf);
+        let after = ring.dump_ring_state();
+        assert_eq!(after.sq_tail, before.sq_tail);
+        assert_eq!(after.sqe_tail, before.sqe_tail + 1);
+    }
+
+    drop(a);
+    assert!(bufs.next().is_none());
+}
+
+#[test]
+fn test_recv_multishot_after_recv_framed() {
+    // with so few tickets, the multishot reuses
+    // the one the finished frames were on, which
+    // must not still point at a frame
+    let config = rio::Config {
+        depth: 2,
+        max_frame_len: 16,
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+    let (mut a, b) = tcp_pair();
+    ring.provide_buffers(0, 2, 64).unwrap();
+
+    for i in 0..2_u8 {
+        a.write_all(&[0, 0, 0, 1, i]).unwrap();
+        assert_eq!(
+            ring.recv_framed(&b, 4).wait().unwrap(),
+            [i]
+        );
+    }
+
+    let mut bufs = ring.recv_multishot(&b, 0);
+
+    for msg in &[b"hello", b"world"] {
+        a.write_all(*msg).unwrap();
+
+        let buf = bufs.next().unwrap().unwrap();
+        assert_eq!(&*buf, *msg);
+    }
+}
+
+#[test]
+fn test_recv_multishot_stream() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    ring.provide_buffers(0, 4, 64).unwrap();
+
+    let mut bufs = ring.recv_multishot(&b, 0);
+
+    for msg in &[&b"hello"[..], b"there", b"world"] {
+        a.write_all(msg).unwrap();
+
+        let buf =
+            block_on_next(&mut bufs).unwrap().unwrap();
+        assert_eq!(&*buf, *msg);
+    }
+
+    drop(a);
+    assert!(block_on_next(&mut bufs).is_none());
+}
+
+#[test]
+fn test_recv_multishot_drop_cancels() {
+    let ring = rio::new().unwrap();
+    let (_a, b) = tcp_pair();
+
+    ring.provide_buffers(3, 1, 64).unwrap();
+
+    // nothing is ever sent, so dropping this must
+    // cancel the pending receive.
+    let bufs = ring.recv_multishot(&b, 3);
+    drop(bufs);
+
+    let err = ring.provide_buffers(3, 1, 64).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+}
+
+#[test]
+fn test_cancel_on_drop() {
+    let config = rio::Config {
+        cancel_on_drop: true,
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    let (_a, b) = tcp_pair();
+
+    // nothing is ever sent, so without canceling
+    // the recv this drop would block forever.
+    let buf = vec![0_u8; 64];
+    let before = std::time::Instant::now();
+    drop(ring.recv(&b, &buf));
+    assert!(
+        before.elapsed()
+            < std::time::Duration::from_secs(1)
+    );
+
+    ring.nop().wait().unwrap();
+}
+
+#[test]
+fn test_cancel_fd() {
+    use std::os::unix::io::AsRawFd;
+
+    let ring = rio::new().unwrap();
+    let (_a, b) = tcp_pair();
+
+    // nothing is ever sent, so both stay in-flight
+    let buf1 = vec![0_u8; 64];
+    let buf2 = vec![0_u8; 64];
+    let recv1 = ring.recv(&b, &buf1);
+    let recv2 = ring.recv(&b, &buf2);
+    ring.submit_all();
+
+    let canceled =
+        ring.cancel_fd(b.as_raw_fd()).wait().unwrap();
+    assert_eq!(canceled, 2);
+
+    for recv in [recv1, recv2] {
+        assert_eq!(
+            recv.wait().unwrap_err().raw_os_error(),
+            Some(libc::ECANCELED)
+        );
+    }
+}
+
+#[test]
+fn test_drain_waits_for_prior_ops() {
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+
+    // the recv can't complete until we send it
+    // something, and the drained nop must not
+    // complete before it does.
+    let buf: &mut [u8] = &mut [0; 5];
+    let mut recv = ring.recv(&b, &buf);
+    let mut nop = ring.nop_ordered(rio::Ordering::Drain);
+
+    assert!(poll_once(&mut recv).is_pending());
+    assert!(poll_once(&mut nop).is_pending());
+    thread::sleep(std::time::Duration::from_millis(100));
+    assert!(poll_once(&mut nop).is_pending());
+
+    a.write_all(b"hello").unwrap();
+
+    nop.wait().unwrap();
+    match poll_once(&mut recv) {
+        Poll::Ready(res) => assert_eq!(res.unwrap(), 5),
+        Poll::Pending => {
+            panic!("drained nop completed before the recv")
+        }
+    }
+}
+
+#[test]
+fn test_send_header_and_file() {
+    let path = std::env::temp_dir().join(format!(
+        "rio_send_header_and_file_{}",
+        std::process::id()
+    ));
+    // bigger than a pipe holds by default
+    let body: Vec<u8> =
+        (0..100_000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &body).unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+
+    let ring = rio::new().unwrap();
+    let (mut a, b) = tcp_pair();
+    let header = b"HTTP/1.1 200 OK\r\n\r\n";
+
+    let expected_len = header.len() + body.len();
+    let reader = thread::spawn(move || {
+        let mut received = vec![0; expected_len];
+        a.read_exact(&mut received).unwrap();
+        received
+    });
+
+    let sent = ring
+        .send_header_and_file(
+            &b,
+            header,
+            &file,
+            0,
+            body.len(),
+        )
+        .wait()
+        .unwrap();
+    assert_eq!(sent, body.len());
+
+    let received = reader.join().unwrap();
+    assert_eq!(&received[..header.len()], header);
+    assert_eq!(&received[header.len()..], &body[..]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_accept_into() {
+    let ring = rio::new().unwrap();
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut addr = rio::SocketAddrStorage::new();
+    assert!(addr.addr().is_err());
+
+    // the same storage is reused for each accept
+    for _ in 0..2 {
+        let accept = ring.accept_into(&listener, &mut addr);
+        let client = TcpStream::connect(
+            listener.local_addr().unwrap(),
+        )
+        .unwrap();
+        let stream = accept.wait().unwrap();
+
+        assert_eq!(
+            addr.addr().unwrap(),
+            client.local_addr().unwrap()
+        );
+        assert_eq!(
+            stream.peer_addr().unwrap(),
+            client.local_addr().unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_accept_nonblocking_listener() {
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    // whether or not this kernel fails it with
+    // EAGAIN first, the accept waits for a
+    // connection to arrive
+    let ring = rio::Config {
+        retry_accept_eagain: true,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+    let accept = ring.accept(&listener);
+    ring.submit_all();
+    std::thread::sleep(std::time::Duration::from_millis(
+        10,
+    ));
+    let client =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let stream = accept.wait().unwrap();
+    assert_eq!(
+        stream.peer_addr().unwrap(),
+        client.local_addr().unwrap()
+    );
+}
+
+#[test]
+fn test_accept_batch() {
+    let ring = rio::new().unwrap();
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+
+    // all of these are waiting in the backlog
+    // before anything is accepted
+    let clients: Vec<_> = (0..4)
+        .map(|_| {
+            TcpStream::connect(
+                listener.local_addr().unwrap(),
+            )
+            .unwrap()
+        })
+        .collect();
+
+    let mut accepted: Vec<_> = ring
+        .accept_batch(&listener, clients.len())
+        .into_iter()
+        .map(|accept| {
+            accept.wait().unwrap().peer_addr().unwrap()
+        })
+        .collect();
+    let mut expected: Vec<_> = clients
+        .iter()
+        .map(|client| client.local_addr().unwrap())
+        .collect();
+    accepted.sort();
+    expected.sort();
+    assert_eq!(accepted, expected);
+
+    // never more than fit in the submission queue
+    let ring = rio::Config {
+        depth: 16,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+    let accepts = ring.accept_batch(&listener, 1000);
+    assert_eq!(accepts.len(), 16);
+    let _clients: Vec<_> = (0..16)
+        .map(|_| {
+            TcpStream::connect(
+                listener.local_addr().unwrap(),
+            )
+            .unwrap()
+        })
+        .collect();
+    for accept in accepts {
+        accept.wait().unwrap();
+    }
+}
+
+#[test]
+fn test_rio_stream_shuts_down_on_drop() {
+    let ring = rio::new().unwrap();
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let accept = ring.accept(&listener);
+    let mut client =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let mut stream = ring.stream(accept.wait().unwrap());
+
+    // usable as a plain TcpStream until dropped
+    stream.write_all(b"bye").unwrap();
+    assert_eq!(
+        stream.peer_addr().unwrap(),
+        client.local_addr().unwrap()
+    );
+    drop(stream);
+
+    // the peer sees everything that was sent,
+    // and then the shutdown as EOF
+    client
+        .set_read_timeout(Some(
+            std::time::Duration::from_secs(5),
+        ))
+        .unwrap();
+    let mut received = vec![];
+    client.read_to_end(&mut received).unwrap();
+    assert_eq!(received, b"bye");
+
+    // into_inner takes it back out of the ring's
+    // hands, so it stays open
+    let accept = ring.accept(&listener);
+    let mut client =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let mut stream =
+        ring.stream(accept.wait().unwrap()).into_inner();
+    stream.write_all(b"hi").unwrap();
+    let mut buf = [0; 2];
+    client.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hi");
+}
+
+#[test]
+fn test_accept_fixed() {
+    let ring = rio::new().unwrap();
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+    let slots = ring.register_file_slots(1).unwrap();
+
+    let accept = slots.accept_fixed(&listener, 0);
+    let mut client =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let fd = accept.wait().unwrap();
+    assert_eq!(fd.index(), 0);
+
+    client.write_all(b"hello").unwrap();
+
+    let buf: &mut [u8] = &mut [0; 5];
+    let received = slots.recv(&fd, &buf).wait().unwrap();
+    assert_eq!(received, 5);
+    assert_eq!(buf, b"hello");
+}
+
+/// A bare-bones `select!` over two futures,
+/// resolving to the output of whichever is
+/// ready first and dropping the other.
+struct Select<A, B>(A, B);
+
+enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A: Future + Unpin, B: Future + Unpin> Future
+    for Select<A, B>
+{
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        if let Poll::Ready(a) =
+            Pin::new(&mut self.0).poll(cx)
+        {
+            return Poll::Ready(Either::Left(a));
+        }
+        if let Poll::Ready(b) =
+            Pin::new(&mut self.1).poll(cx)
+        {
+            return Poll::Ready(Either::Right(b));
+        }
+        Poll::Pending
+    }
+}
+
+#[test]
+fn test_select_drops_pending_recv() {
+    let ring = rio::new().unwrap();
+    let (_a, b) = tcp_pair();
+
+    // nothing is ever sent, so only the "timeout"
+    // can win, and the recv has to be dropped
+    // without blocking forever.
+    let buf: &mut [u8] = &mut [0; 5];
+    let recv = ring.recv(&b, &buf);
+    let timeout = std::future::ready(());
+
+    let mut select = Select(recv, timeout);
+    match poll_once(&mut select) {
+        Poll::Ready(Either::Right(())) => {}
+        _ => panic!("expected the timeout to win"),
+    }
+    drop(select);
+}
+
+#[test]
+fn test_broadcast() {
+    let ring = rio::new().unwrap();
+
+    let pairs: Vec<_> =
+        (0..8).map(|_| tcp_pair()).collect();
+    let senders: Vec<&TcpStream> =
+        pairs.iter().map(|(a, _)| a).collect();
+
+    let completions = ring.broadcast(&senders, b"hello");
+    assert_eq!(completions.len(), 8);
+    for completion in completions {
+        assert_eq!(completion.wait().unwrap(), 5);
+    }
+
+    for (_, mut b) in pairs {
+        let mut buf = [0; 5];
+        b.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+}
+
+#[test]
+fn test_broadcast_concurrent_tiny_depth() {
+    // depth 2 gives the ring 4 tickets, so threads
+    // that each took them one at a time used to
+    // end up each holding some while waiting for
+    // the rest forever
+    let ring = rio::Config {
+        depth: 2,
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+
+    thread::scope(|s| {
+        for _ in 0..8 {
+            let ring = &ring;
+            s.spawn(move || {
+                let pairs: Vec<_> =
+                    (0..4).map(|_| tcp_pair()).collect();
+                let senders: Vec<&TcpStream> =
+                    pairs.iter().map(|(a, _)| a).collect();
+
+                for _ in 0..500 {
+                    for completion in
+                        ring.broadcast(&senders, b"hello")
+                    {
+                        assert_eq!(
+                            completion.wait().unwrap(),
+                            5
+                        );
+                    }
+                }
+
+                for (_, mut b) in pairs {
+                    let mut buf = [0; 2500];
+                    b.read_exact(&mut buf).unwrap();
+                    assert!(buf
+                        .chunks(5)
+                        .all(|c| c == b"hello"));
+                }
+            });
+        }
+    });
+}
+
+/// Receives a byte and the descriptor sent along
+/// with it by `Uring::send_fds`.
+fn recv_fd(socket: std::os::unix::io::RawFd) -> i32 {
+    let mut byte = [0_u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    let mut control = [0_u64; 8];
+    let mut msg: libc::msghdr =
+        unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = std::mem::size_of_val(&control);
+
+    unsafe {
+        assert_eq!(libc::recvmsg(socket, &mut msg, 0), 1);
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        assert!(!cmsg.is_null());
+        assert_eq!((*cmsg).cmsg_level, libc::SOL_SOCKET);
+        assert_eq!((*cmsg).cmsg_type, libc::SCM_RIGHTS);
+        libc::CMSG_DATA(cmsg).cast::<i32>().read_unaligned()
+    }
+}
+
+#[test]
+fn test_send_fds() {
+    use std::os::unix::{
+        io::{AsRawFd, FromRawFd},
+        net::UnixStream,
+    };
+
+    if let Some(socket) =
+        std::env::var_os("RIO_SEND_FDS_CHILD")
+    {
+        let socket =
+            socket.to_str().unwrap().parse().unwrap();
+        let fd = recv_fd(socket);
+        let mut file =
+            unsafe { std::fs::File::from_raw_fd(fd) };
+        file.write_all(b"from the child").unwrap();
+        return;
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "rio_send_fds_{}",
+        std::process::id()
+    ));
+    let file = std::fs::File::create(&path).unwrap();
+
+    // the child's end has to survive the exec, so
+    // unlike std's, this pair isn't CLOEXEC
+    let mut fds = [0; 2];
+    let ret = unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_STREAM,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+    assert_eq!(ret, 0);
+    let ours = unsafe { UnixStream::from_raw_fd(fds[0]) };
+    let theirs = unsafe { UnixStream::from_raw_fd(fds[1]) };
+
+    let mut child = std::process::Command::new(
+        std::env::current_exe().unwrap(),
+    )
+    .args(["--exact", "test_send_fds"])
+    .env("RIO_SEND_FDS_CHILD", fds[1].to_string())
+    .stdout(std::process::Stdio::null())
+    .spawn()
+    .unwrap();
+    drop(theirs);
+
+    let ring = rio::new().unwrap();
+    let sent = ring
+        .send_fds(&ours, &[file.as_raw_fd()], b"x")
+        .wait()
+        .unwrap();
+    assert_eq!(sent, 1);
+
+    // the child has its own copy of the file now,
+    // and writes to it through that
+    drop(file);
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        b"from the child"
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_recv_fds() {
+    use std::os::unix::{
+        fs::MetadataExt, io::AsRawFd, net::UnixStream,
+    };
+
+    let path = std::env::temp_dir().join(format!(
+        "rio_recv_fds_{}",
+        std::process::id()
+    ));
+    let file = std::fs::File::create(&path).unwrap();
+    let (a, b) = UnixStream::pair().unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.send_fds(&a, &[file.as_raw_fd()], b"x")
+        .wait()
+        .unwrap();
+
+    let buf = [0_u8; 1];
+    let (received, fds) =
+        ring.recv_fds(&b, &buf, 4).wait().unwrap();
+    assert_eq!(received, 1);
+    assert_eq!(&buf, b"x");
+    assert_eq!(fds.len(), 1);
+    assert_ne!(fds[0].as_raw_fd(), file.as_raw_fd());
+
+    // it's another descriptor for the same file
+    let received_file = std::fs::File::from(
+        fds.into_iter().next().unwrap(),
+    );
+    let ours = file.metadata().unwrap();
+    let theirs = received_file.metadata().unwrap();
+    assert_eq!(ours.dev(), theirs.dev());
+    assert_eq!(ours.ino(), theirs.ino());
+
+    // and nothing is made of data sent without any
+    ring.send(&a, b"y").wait().unwrap();
+    let (received, fds) =
+        ring.recv_fds(&b, &buf, 4).wait().unwrap();
+    assert_eq!(received, 1);
+    assert!(fds.is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_recv_fds_dropped() {
+    use std::{
+        os::unix::{io::AsRawFd, net::UnixStream},
+        time::Duration,
+    };
+
+    let (a, b) = UnixStream::pair().unwrap();
+    let (ours, mut theirs) = UnixStream::pair().unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.send_fds(&a, &[ours.as_raw_fd()], b"x")
+        .wait()
+        .unwrap();
+    drop(ours);
+
+    // the descriptor that was received, but never
+    // claimed, is closed along with the receive,
+    // which leaves nothing open on our end
+    let buf = [0_u8; 1];
+    drop(ring.recv_fds(&b, &buf, 4));
+
+    theirs
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let mut eof = [0_u8; 1];
+    assert_eq!(theirs.read(&mut eof).unwrap(), 0);
+}
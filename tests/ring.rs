@@ -0,0 +1,988 @@
+use std::os::unix::io::AsRawFd;
+
+#[test]
+fn test_ring_fd() {
+    let a = rio::new().unwrap();
+    let b = rio::new().unwrap();
+
+    assert!(a.as_raw_fd() > 0);
+    assert!(b.as_raw_fd() > 0);
+    assert_ne!(a.as_raw_fd(), b.as_raw_fd());
+}
+
+#[test]
+fn test_submit_stats() {
+    let ring = rio::new().unwrap();
+
+    let completions: Vec<_> =
+        (0..10).map(|_| ring.nop()).collect();
+
+    let stats = ring.submit_stats().unwrap();
+    assert_eq!(stats.submitted, 10);
+    assert_eq!(stats.sq_space_left, 256);
+    assert!(stats.in_flight <= 10);
+
+    for completion in completions {
+        completion.wait().unwrap();
+    }
+
+    let stats = ring.submit_stats().unwrap();
+    assert_eq!(stats.submitted, 0);
+    assert_eq!(stats.sq_space_left, 256);
+    assert_eq!(stats.in_flight, 0);
+
+    // recvs stay in-flight until there's data
+    let (mut a, b) =
+        std::os::unix::net::UnixStream::pair().unwrap();
+    let bufs = [[0_u8; 1]; 4];
+    let recvs: Vec<_> =
+        bufs.iter().map(|buf| ring.recv(&b, buf)).collect();
+
+    let stats = ring.submit_stats().unwrap();
+    assert_eq!(stats.submitted, 4);
+    assert_eq!(stats.in_flight, 4);
+
+    std::io::Write::write_all(&mut a, b"abcd").unwrap();
+    for recv in recvs {
+        assert_eq!(recv.wait().unwrap(), 1);
+    }
+    let mut received: Vec<u8> =
+        bufs.iter().map(|buf| buf[0]).collect();
+    received.sort_unstable();
+    assert_eq!(received, b"abcd");
+    assert_eq!(ring.submit_stats().unwrap().in_flight, 0);
+
+    // tickets move between shards while others
+    // submit and reap, which must never make the
+    // count go out of range
+    std::thread::scope(|s| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        ring.nop().wait().unwrap();
+                    }
+                })
+            })
+            .collect();
+        while threads.iter().any(|t| !t.is_finished()) {
+            let stats = ring.submit_stats().unwrap();
+            assert!(stats.in_flight <= ring.cq_entries());
+        }
+    });
+}
+
+#[test]
+fn test_ring_entries() {
+    let ring = rio::with_depth(64).unwrap();
+    assert_eq!(ring.sq_capacity(), 64);
+    assert!(ring.cq_entries() >= ring.sq_capacity());
+}
+
+#[test]
+fn test_with_depth() {
+    let ring = rio::with_depth(64).unwrap();
+    assert_eq!(ring.sq_capacity(), 64);
+
+    // the kernel rounds it up
+    let ring = rio::with_depth(100).unwrap();
+    assert_eq!(ring.sq_capacity(), 128);
+
+    for depth in [0, 32769] {
+        let err = rio::with_depth(depth).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    // with raw_params, the kernel can clamp it
+    let mut params = rio::Config::default()
+        .raw_params
+        .unwrap_or_default();
+    params.flags = rio::consts::IORING_SETUP_CLAMP;
+    let ring = rio::Config {
+        depth: 65536,
+        raw_params: Some(params),
+        ..rio::Config::default()
+    }
+    .start()
+    .unwrap();
+    assert_eq!(ring.sq_capacity(), 32768);
+}
+
+#[test]
+fn test_submit_all_then_wait() {
+    let ring = rio::new().unwrap();
+
+    let completion = ring.nop();
+    ring.submit_all();
+    completion.wait().unwrap();
+}
+
+#[test]
+fn test_iowq_max_workers() {
+    let path = std::env::temp_dir().join(format!(
+        "rio_iowq_max_workers_{}",
+        std::process::id()
+    ));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let config = rio::Config {
+        iowq_max_workers: Some((1, 1)),
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    // fsyncs always go to a worker, so with a
+    // single one they have to take turns
+    let bufs: Vec<[u8; 512]> =
+        (0..16_u8).map(|i| [i; 512]).collect();
+    let writes: Vec<_> = bufs
+        .iter()
+        .zip(0_u64..)
+        .map(|(buf, i)| ring.write_at(&file, buf, i * 512))
+        .collect();
+    let syncs: Vec<_> =
+        (0..8).map(|_| ring.fsync(&file)).collect();
+    for write in writes {
+        assert_eq!(write.wait().unwrap(), 512);
+    }
+    for sync in syncs {
+        sync.wait().unwrap();
+    }
+
+    ring.set_iowq_max_workers(2, 0).unwrap();
+    ring.fsync(&file).wait().unwrap();
+
+    let contents = std::fs::read(&path).unwrap();
+    assert_eq!(contents.len(), 16 * 512);
+    assert!(contents[15 * 512..].iter().all(|b| *b == 15));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_register_personality() {
+    let ring = rio::new().unwrap();
+
+    let personality = ring.register_personality().unwrap();
+    assert!(personality > 0);
+
+    ring.with_personality(personality, || ring.nop())
+        .wait()
+        .unwrap();
+
+    ring.unregister_personality(personality).unwrap();
+
+    // the kernel rejects ops with an unknown
+    // personality, which shows the id really is
+    // set on the SQE.
+    let err = ring
+        .with_personality(personality, || ring.nop())
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+
+    ring.nop().wait().unwrap();
+}
+
+#[test]
+fn test_submit_raw_nop() {
+    let ring = rio::new().unwrap();
+
+    let mut keep_alive = rio::KeepAlive::new();
+    let held = keep_alive.hold(vec![0_u8; 16]);
+    assert!(!held.is_null());
+
+    let completion = unsafe {
+        ring.submit_raw(
+            |sqe| {
+                // IORING_OP_NOP
+                sqe.opcode = 0;
+            },
+            keep_alive,
+        )
+    };
+
+    let cqe = completion.wait().unwrap();
+    assert_eq!(cqe.res, 0);
+}
+
+#[test]
+fn test_eintr_retries() {
+    use rio::consts::{
+        IORING_NOP_INJECT_RESULT, IORING_OP_NOP,
+    };
+
+    let config = rio::Config {
+        eintr_retries: 2,
+        ..rio::Config::default()
+    };
+    let ring = config.start().unwrap();
+
+    // a nop that has the kernel complete it with
+    // EINTR, every time it's submitted
+    let interrupted_nop = || unsafe {
+        ring.submit_raw(
+            |sqe| {
+                sqe.opcode = IORING_OP_NOP;
+                sqe.__bindgen_anon_1.rw_flags =
+                    IORING_NOP_INJECT_RESULT as _;
+                sqe.len = libc::EINTR.wrapping_neg() as u32;
+            },
+            rio::KeepAlive::new(),
+        )
+    };
+
+    let before = ring.dump_ring_state().sq_tail;
+    let res = interrupted_nop().wait();
+    if res.is_ok() {
+        // older kernels ignore the nop's flags
+        return;
+    }
+    assert_eq!(
+        res.unwrap_err().raw_os_error(),
+        Some(libc::EINTR)
+    );
+
+    // it was only failed after being retried
+    // twice, so it was submitted three times
+    let after = ring.dump_ring_state().sq_tail;
+    assert_eq!(after.wrapping_sub(before), 3);
+
+    // the ring is fine afterwards
+    ring.nop().wait().unwrap();
+}
+
+#[test]
+fn test_sq_poll_wakeups() {
+    let config = rio::Config {
+        sq_poll: true,
+        sq_poll_idle: 10,
+        ..rio::Config::default()
+    };
+    let ring = match config.start() {
+        Ok(ring) => ring,
+        Err(e)
+            if e.kind()
+                == std::io::ErrorKind::PermissionDenied =>
+        {
+            // SQPOLL needs privileges on older kernels
+            return;
+        }
+        Err(e) => panic!("failed to start ring: {:?}", e),
+    };
+
+    ring.nop().wait().unwrap();
+    let before = ring.sq_poll_wakeups();
+
+    // let the SQPOLL thread go idle
+    std::thread::sleep(std::time::Duration::from_millis(
+        100,
+    ));
+
+    ring.nop().wait().unwrap();
+    assert!(ring.sq_poll_wakeups() > before);
+}
+
+#[test]
+fn test_submit_all_nothing_to_submit() {
+    let config = rio::Config {
+        sq_poll: true,
+        sq_poll_idle: 10,
+        ..rio::Config::default()
+    };
+    let ring = match config.start() {
+        Ok(ring) => ring,
+        Err(e)
+            if e.kind()
+                == std::io::ErrorKind::PermissionDenied =>
+        {
+            // SQPOLL needs privileges on older kernels
+            return;
+        }
+        Err(e) => panic!("failed to start ring: {:?}", e),
+    };
+
+    ring.nop().wait().unwrap();
+
+    // once the SQPOLL thread is idle, submitting
+    // would wake it up, but there is nothing to
+    // submit, so this shouldn't enter at all
+    std::thread::sleep(std::time::Duration::from_millis(
+        100,
+    ));
+    let before = ring.metrics_snapshot().sq_enters;
+    let wakeups = ring.sq_poll_wakeups();
+    for _ in 0..100 {
+        ring.submit_all();
+    }
+    assert_eq!(ring.metrics_snapshot().sq_enters, before);
+    assert_eq!(ring.sq_poll_wakeups(), wakeups);
+}
+
+#[test]
+fn test_coop_taskrun() {
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream},
+    };
+
+    let config = rio::Config {
+        coop_taskrun: true,
+        ..rio::Config::default()
+    };
+    let ring = match config.start() {
+        Ok(ring) => ring,
+        Err(e)
+            if e.kind()
+                == std::io::ErrorKind::InvalidInput =>
+        {
+            // COOP_TASKRUN came in 5.19
+            return;
+        }
+        Err(e) => panic!("failed to start ring: {:?}", e),
+    };
+
+    ring.nop().wait().unwrap();
+
+    // completions that need finishing off by the
+    // submitting thread are still delivered
+    let listener =
+        TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut a =
+        TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap();
+    let (b, _) = listener.accept().unwrap();
+    let recv =
+        ring.recv_into_vec(&b, Vec::with_capacity(8));
+    ring.submit_all();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(
+                std::time::Duration::from_millis(50),
+            );
+            a.write_all(b"hello").unwrap();
+        });
+        assert_eq!(recv.wait().unwrap(), b"hello");
+    });
+}
+
+#[test]
+fn test_single_issuer() {
+    let config = rio::Config {
+        coop_taskrun: true,
+        single_issuer: true,
+        ..rio::Config::default()
+    };
+    let res = config.start();
+
+    // the reaper thread submits too
+    #[cfg(not(feature = "single_thread"))]
+    assert_eq!(
+        res.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    #[cfg(feature = "single_thread")]
+    {
+        let ring = match res {
+            Ok(ring) => ring,
+            Err(e)
+                if e.kind()
+                    == std::io::ErrorKind::InvalidInput =>
+            {
+                // SINGLE_ISSUER came in 6.0
+                return;
+            }
+            Err(e) => {
+                panic!("failed to start ring: {:?}", e)
+            }
+        };
+
+        for _ in 0..10 {
+            ring.nop().wait().unwrap();
+        }
+        let nops: Vec<_> =
+            (0..10).map(|_| ring.nop()).collect();
+        ring.drain_completions(nops).unwrap();
+    }
+}
+
+#[test]
+fn test_sq_poll_full_sq() {
+    let config = rio::Config {
+        depth: 4,
+        sq_poll: true,
+        sq_poll_idle: 1000,
+        ..rio::Config::default()
+    };
+    let ring = match config.start() {
+        Ok(ring) => ring,
+        Err(e)
+            if e.kind()
+                == std::io::ErrorKind::PermissionDenied =>
+        {
+            // SQPOLL needs privileges on older kernels
+            return;
+        }
+        Err(e) => panic!("failed to start ring: {:?}", e),
+    };
+
+    // far more than fit in the SQ, so most pushes
+    // find it full and have to wait for the SQPOLL
+    // thread to drain it.
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let nops: Vec<_> = (0..8)
+                        .map(|_| ring.nop())
+                        .collect();
+                    ring.drain_completions(nops).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // the SQPOLL thread never had time to go idle,
+    // so it should hardly ever have been woken up.
+    assert!(ring.sq_poll_wakeups() <= 1);
+}
+
+#[test]
+fn test_prewarm() {
+    use std::time::{Duration, Instant};
+
+    let ring = rio::new().unwrap();
+    let state = ring.dump_ring_state();
+    ring.prewarm().unwrap();
+
+    // it pushed a full submission queue of NOPs
+    // through the ring, and reaped all of them
+    let warmed = ring.dump_ring_state();
+    let sq_capacity = ring.sq_capacity() as u32;
+    assert_eq!(warmed.sq_tail - state.sq_tail, sq_capacity);
+    assert_eq!(warmed.cq_head - state.cq_head, sq_capacity);
+    assert_eq!(warmed.cq_head, warmed.cq_tail);
+    assert_eq!(ring.submit_stats().unwrap().in_flight, 0);
+
+    let before = Instant::now();
+    ring.nop().wait().unwrap();
+    let first = before.elapsed();
+
+    let before = Instant::now();
+    for _ in 0..100 {
+        ring.nop().wait().unwrap();
+    }
+    let average = before.elapsed() / 100;
+
+    // this is only best-effort, since scheduling
+    // noise can easily dwarf a single NOP.
+    assert!(
+        first < average * 50 + Duration::from_millis(5),
+        "first op took {:?}, later ops {:?} on average",
+        first,
+        average,
+    );
+}
+
+#[test]
+fn test_kernel_info() {
+    let info = rio::kernel_info().unwrap();
+
+    assert!(info.major >= 5, "{:?}", info);
+    assert!(info.release.starts_with(&format!(
+        "{}.{}",
+        info.major, info.minor
+    )));
+    assert_ne!(info.features, 0);
+    assert!(info.feature_names().contains(&"SINGLE_MMAP"));
+    assert!(info
+        .to_string()
+        .starts_with(&format!("kernel {}.", info.major)));
+}
+
+#[test]
+fn test_drop_rio_with_outstanding_completion() {
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    let ring = rio::new().unwrap();
+    let other = ring.clone();
+    let (a, mut b) = UnixStream::pair().unwrap();
+
+    // dropping a clone doesn't drop the ring out
+    // from under completions of other clones
+    let completion =
+        other.recv_into_vec(&a, Vec::with_capacity(16));
+    drop(ring);
+    b.write_all(b"hi").unwrap();
+    assert_eq!(completion.wait().unwrap(), b"hi");
+
+    // dropping the last clone waits for operations
+    // that nothing will ever wait on
+    let completion = other.nop();
+    std::mem::forget(completion);
+
+    // and cancels the ones that would never
+    // complete on their own, like a recv on a
+    // socket that nothing will ever be sent on
+    std::mem::forget(
+        other.recv_into_vec(&a, Vec::with_capacity(16)),
+    );
+    drop(other);
+}
+
+#[test]
+fn test_ping() {
+    let ring = rio::new().unwrap();
+
+    let latency = ring.ping().unwrap();
+    assert!(latency > std::time::Duration::from_secs(0));
+    assert!(latency < std::time::Duration::from_secs(1));
+}
+
+#[cfg(not(feature = "no_metrics"))]
+#[test]
+fn test_metrics_per_ring() {
+    let a = rio::new().unwrap();
+    let b = rio::new().unwrap();
+
+    for _ in 0..10 {
+        a.nop().wait().unwrap();
+    }
+    for _ in 0..3 {
+        b.nop().wait().unwrap();
+    }
+
+    let a_metrics = a.metrics_snapshot();
+    let b_metrics = b.metrics_snapshot();
+    assert_eq!(a_metrics.ops, 10);
+    assert_eq!(b_metrics.ops, 3);
+    assert!(a_metrics.waits >= 10);
+    assert!(b_metrics.waits >= 3);
+    assert!(b_metrics.waits < 10);
+}
+
+#[cfg(feature = "prometheus")]
+#[test]
+fn test_metrics_prometheus() {
+    let ring = rio::new().unwrap();
+    for _ in 0..10 {
+        ring.nop().wait().unwrap();
+    }
+
+    let text = ring.metrics_prometheus();
+
+    // every sample must belong to a family that
+    // was declared by a preceding TYPE line
+    let mut families = std::collections::HashMap::new();
+    for line in text.lines() {
+        if let Some(comment) = line.strip_prefix("# ") {
+            let mut parts = comment.splitn(3, ' ');
+            let kind = parts.next().unwrap();
+            let name = parts.next().unwrap();
+            let rest = parts.next().unwrap();
+            if kind == "TYPE" {
+                assert!(
+                    rest == "summary" || rest == "counter",
+                    "{}",
+                    line
+                );
+                families.insert(name.to_string(), rest);
+            } else {
+                assert_eq!(kind, "HELP", "{}", line);
+            }
+            continue;
+        }
+
+        let (series, value) =
+            line.rsplit_once(' ').expect(line);
+        value.parse::<f64>().expect(line);
+
+        let name = match series.split_once('{') {
+            Some((name, labels)) => {
+                let labels =
+                    labels.strip_suffix('}').expect(line);
+                for label in labels.split(',') {
+                    let (key, value) =
+                        label.split_once('=').expect(line);
+                    assert!(!key.is_empty(), "{}", line);
+                    assert!(
+                        value.len() >= 2
+                            && value.starts_with('"')
+                            && value.ends_with('"'),
+                        "{}",
+                        line
+                    );
+                }
+                name
+            }
+            None => series,
+        };
+        assert!(
+            name.chars().all(|c| c.is_ascii_alphanumeric()
+                || c == '_'
+                || c == ':'),
+            "{}",
+            line
+        );
+
+        let family = name
+            .strip_suffix("_sum")
+            .or_else(|| name.strip_suffix("_count"))
+            .filter(|f| families.contains_key(*f))
+            .unwrap_or(name);
+        assert!(families.contains_key(family), "{}", line);
+    }
+
+    for name in &[
+        "rio_wait_seconds",
+        "rio_enter_sqe_seconds",
+        "rio_enter_cqe_seconds",
+        "rio_sq_mu_wait_seconds",
+        "rio_cq_mu_wait_seconds",
+        "rio_ping_seconds",
+    ] {
+        assert_eq!(families.get(*name), Some(&"summary"));
+    }
+    assert_eq!(
+        families.get("rio_ops_total"),
+        Some(&"counter")
+    );
+    assert!(
+        text.contains("rio_ops_total 10\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(
+            "rio_wait_seconds_count{stage=\"wait\"}"
+        ),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_register_buffers_memlock() {
+    if std::env::var_os("RIO_MEMLOCK_CHILD").is_some() {
+        let ring = rio::new().unwrap();
+
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        unsafe {
+            assert_eq!(
+                libc::getrlimit(
+                    libc::RLIMIT_MEMLOCK,
+                    &mut limit
+                ),
+                0
+            );
+        }
+        limit.rlim_cur = 64 * 1024;
+        unsafe {
+            assert_eq!(
+                libc::setrlimit(
+                    libc::RLIMIT_MEMLOCK,
+                    &limit
+                ),
+                0
+            );
+        }
+
+        // CAP_IPC_LOCK exempts us from the rlimit
+        let status =
+            std::fs::read_to_string("/proc/self/status")
+                .unwrap();
+        let cap_eff = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .unwrap();
+        let caps = u64::from_str_radix(cap_eff.trim(), 16)
+            .unwrap();
+        // CAP_IPC_LOCK is capability 14
+        let ipc_lock = caps & (1 << 14) != 0;
+
+        let bufs = vec![vec![0_u8; 1024 * 1024]];
+        match ring.register_buffers(&bufs) {
+            Ok(_) => assert!(ipc_lock),
+            Err(e) => assert!(
+                e.to_string().contains("memlock rlimit"),
+                "{}",
+                e
+            ),
+        }
+
+        let small = [vec![0_u8; 4096]];
+        let registration =
+            ring.register_buffers(&small).unwrap();
+        drop(registration);
+        return;
+    }
+
+    // the rlimit is process-wide, so this lowers
+    // it in a child process rather than under the
+    // other tests running alongside this one
+    let output = std::process::Command::new(
+        std::env::current_exe().unwrap(),
+    )
+    .args(["--exact", "test_register_buffers_memlock"])
+    .env("RIO_MEMLOCK_CHILD", "1")
+    .output()
+    .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("1 passed"));
+}
+
+#[test]
+fn test_drain_completions() {
+    let ring = rio::new().unwrap();
+
+    let nops: Vec<_> =
+        (0..1000).map(|_| ring.nop()).collect();
+    let results = ring.drain_completions(nops).unwrap();
+    assert_eq!(results.len(), 1000);
+
+    let file = std::fs::File::open("/dev/null").unwrap();
+    let buf: &mut [u8] = &mut [0; 1];
+    let reads = vec![
+        ring.read_at(&file, &buf, 0),
+        ring.read_at(&file, &buf, u64::MAX),
+    ];
+    assert!(ring.drain_completions(reads).is_err());
+}
+
+#[test]
+fn test_wait_any() {
+    use std::{fs::File, os::unix::io::FromRawFd};
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let rx = unsafe { File::from_raw_fd(fds[0]) };
+    let tx = unsafe { File::from_raw_fd(fds[1]) };
+    let null = File::open("/dev/null").unwrap();
+
+    let ring = rio::new().unwrap();
+
+    // the pipe read can't finish until it's
+    // written to, while /dev/null is at eof
+    let slow_buf: &mut [u8] = &mut [0; 8];
+    let fast_buf: &mut [u8] = &mut [0; 8];
+    let mut completions = vec![
+        ring.read_at(&rx, &slow_buf, 0),
+        ring.read_at(&null, &fast_buf, 0),
+    ];
+
+    let (index, res) = ring.wait_any(&mut completions);
+    assert_eq!(index, 1);
+    assert_eq!(res.unwrap(), 0);
+    assert_eq!(completions.len(), 1);
+
+    std::io::Write::write_all(&mut &tx, b"slow").unwrap();
+    let (index, res) = ring.wait_any(&mut completions);
+    assert_eq!(index, 0);
+    assert_eq!(res.unwrap(), 4);
+    assert!(completions.is_empty());
+}
+
+#[test]
+fn test_dump_ring_state() {
+    let ring = rio::new().unwrap();
+
+    let before = ring.dump_ring_state();
+    assert_eq!(before.sq_head, before.sq_tail);
+    assert_eq!(before.cq_head, before.cq_tail);
+    assert!(before.sq_mask > 0);
+    assert!(before.cq_mask > 0);
+
+    let nops: Vec<_> =
+        (0..10).map(|_| ring.nop()).collect();
+    for nop in nops {
+        nop.wait().unwrap();
+    }
+
+    let after = ring.dump_ring_state();
+    assert_eq!(
+        after.sq_tail.wrapping_sub(before.sq_tail),
+        10
+    );
+    assert_eq!(
+        after.cq_tail.wrapping_sub(before.cq_tail),
+        10
+    );
+    assert_eq!(after.sq_head, after.sq_tail);
+    assert_eq!(after.sqe_head, after.sqe_tail);
+    assert_eq!(after.sq_dropped, 0);
+    assert_eq!(after.cq_overflow, 0);
+}
+
+#[test]
+fn test_timeout_update() {
+    use std::time::{Duration, Instant};
+
+    let ring = rio::new().unwrap();
+
+    let before = Instant::now();
+    let (timeout, token) =
+        ring.timeout(Duration::from_millis(20));
+    ring.timeout_update(token, Duration::from_millis(200))
+        .wait()
+        .unwrap();
+    assert_eq!(timeout.wait().unwrap(), rio::Expired);
+    assert!(before.elapsed() >= Duration::from_millis(200));
+
+    // once it has fired, there's nothing to update
+    let err = ring
+        .timeout_update(token, Duration::from_millis(1))
+        .wait()
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+}
+
+#[test]
+fn test_submit_raw_tagged() {
+    let ring = rio::new().unwrap();
+
+    let submit = |tag| unsafe {
+        ring.submit_raw_tagged(
+            |sqe| {
+                // IORING_OP_NOP
+                sqe.opcode = 0;
+            },
+            rio::KeepAlive::new(),
+            tag,
+        )
+    };
+    let first = submit(1000);
+    let second = submit(2000);
+
+    // waited on out of order, and matched up by
+    // their tags alone
+    let cqes =
+        [second.wait().unwrap(), first.wait().unwrap()];
+    let mut tags: Vec<u64> =
+        cqes.iter().map(|cqe| cqe.user_data).collect();
+    tags.sort_unstable();
+    assert_eq!(tags, [1000, 2000]);
+
+    // untagged raw ops are unaffected
+    let untagged = unsafe {
+        ring.submit_raw(
+            |sqe| sqe.opcode = 0,
+            rio::KeepAlive::new(),
+        )
+    };
+    let cqe = untagged.wait().unwrap();
+    assert!(cqe.user_data != 1000 && cqe.user_data != 2000);
+}
+
+#[test]
+fn test_msg_ring() {
+    let a = rio::new().unwrap();
+    let b = rio::new().unwrap();
+    assert_eq!(b.try_recv_msg(), None);
+
+    a.msg_ring(&b, 42, 7).wait().unwrap();
+    let msg = b.recv_msg();
+    assert_eq!(msg, rio::RingMessage { data: 42, len: 7 });
+
+    // any data goes, even what would look like a
+    // poison pill for one of rio's own completions
+    a.msg_ring(&b, u64::MAX, u32::MAX).wait().unwrap();
+    b.msg_ring(&b, 1, 0).wait().unwrap();
+    assert_eq!(b.recv_msg().data, u64::MAX);
+    assert_eq!(b.recv_msg().data, 1);
+    assert_eq!(b.try_recv_msg(), None);
+    assert_eq!(a.try_recv_msg(), None);
+
+    // a blocked receiver is woken up by a message
+    std::thread::scope(|s| {
+        let receiver = s.spawn(|| b.recv_msg());
+        std::thread::sleep(
+            std::time::Duration::from_millis(10),
+        );
+        a.msg_ring(&b, 3, 3).wait().unwrap();
+        assert_eq!(receiver.join().unwrap().data, 3);
+    });
+}
+
+#[test]
+fn test_post_fork_child() {
+    let path = std::env::temp_dir().join(format!(
+        "rio_post_fork_child_{}",
+        std::process::id()
+    ));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    let ring = rio::new().unwrap();
+    ring.nop().wait().unwrap();
+
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        // never return into the test harness, or
+        // panic, in the child
+        let written = ring.post_fork_child().map(|ring| {
+            ring.write_at(&file, b"child", 0).wait()
+        });
+        let code = match written {
+            Ok(Ok(5)) => 0,
+            _ => 1,
+        };
+        unsafe { libc::_exit(code) };
+    }
+
+    let mut status = 0;
+    assert_eq!(
+        unsafe { libc::waitpid(pid, &mut status, 0) },
+        pid
+    );
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 0);
+
+    // the parent's ring is unaffected
+    let buf = &mut [0; 5];
+    let read = ring.read_at(&file, buf, 0).wait().unwrap();
+    assert_eq!(read, 5);
+    assert_eq!(buf, b"child");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_benchmark_nops() {
+    let ring = rio::new().unwrap();
+
+    let result = ring.benchmark_nops(10_000).unwrap();
+    assert_eq!(result.ops, 10_000);
+    assert!(result.ops_per_sec() > 0);
+    assert_eq!(
+        result.total_time(),
+        result.submit_time + result.complete_time
+    );
+}